@@ -0,0 +1,34 @@
+use crate::capture::WindowInfo;
+use crate::i18n;
+use std::process::{Command, Stdio};
+
+// AT-SPI has no call for an application to make the screen reader speak, and
+// Orca itself talks to speech-dispatcher, so announcements go there directly.
+pub fn announce(message: &str) {
+    log::debug!("Announcing: {}", message);
+    let spoken = Command::new("spd-say")
+        .args(["--application-name", "hyprshot-rs", "--priority", "message"])
+        .arg(message)
+        .stdout(Stdio::null())
+        .status();
+    match spoken {
+        Ok(status) if status.success() => {}
+        Ok(status) => log::warn!("spd-say exited with {}", status),
+        Err(e) => log::warn!("failed to run spd-say: {}", e),
+    }
+}
+
+pub fn capture_message(window: Option<&WindowInfo>, clipboard_only: bool) -> String {
+    match (window, clipboard_only) {
+        (Some(window), false) => i18n::trf(
+            "Screenshot of {class} window saved",
+            &[("class", &window.class)],
+        ),
+        (Some(window), true) => i18n::trf(
+            "Screenshot of {class} window copied",
+            &[("class", &window.class)],
+        ),
+        (None, false) => i18n::tr("Screenshot saved").to_string(),
+        (None, true) => i18n::tr("Screenshot copied").to_string(),
+    }
+}
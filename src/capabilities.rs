@@ -0,0 +1,138 @@
+use crate::encode;
+use crate::environment::{self, DesktopEnvironment};
+use crate::record::RecordFormat;
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+fn names<T: ValueEnum>() -> Vec<String> {
+    T::value_variants()
+        .iter()
+        .filter_map(|variant| variant.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+fn has(program: &str) -> bool {
+    environment::find_program(program).is_some()
+}
+
+// Image formats whose encoder is installed; PNG needs none.
+fn formats() -> Vec<String> {
+    encode::Format::value_variants()
+        .iter()
+        .filter(|format| format.encoder().is_none_or(has))
+        .filter_map(|format| format.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+// The backend a capture would use with --backend auto.
+fn default_backend(desktop: DesktopEnvironment) -> &'static str {
+    match desktop {
+        DesktopEnvironment::Kde => "spectacle",
+        DesktopEnvironment::Gnome => "gnome-shell",
+        DesktopEnvironment::X11 => "maim",
+        _ if cfg!(feature = "grim") => "grim",
+        _ => "native",
+    }
+}
+
+pub fn report(modes: Vec<String>) -> Value {
+    let desktop = DesktopEnvironment::detect();
+    // Pixel and layer modes need grim or the native backend.
+    let modes: Vec<String> = modes
+        .into_iter()
+        .filter(|mode| {
+            !desktop.has_desktop_backend() || !matches!(mode.as_str(), "pixel" | "layer")
+        })
+        .collect();
+    let backends = json!([
+        { "name": "grim", "available": cfg!(feature = "grim") && has("grim") },
+        { "name": "native", "available": cfg!(feature = "native") },
+        { "name": "spectacle", "available": has("spectacle") },
+        { "name": "gnome-shell", "available": desktop == DesktopEnvironment::Gnome },
+        { "name": "maim", "available": has("maim") },
+        { "name": "screencast", "available": has("gst-launch-1.0") },
+    ]);
+    let tools: serde_json::Map<String, Value> = environment::TOOLS
+        .iter()
+        .map(|tool| {
+            let path = environment::find_program(tool).map(|path| path.display().to_string());
+            (tool.to_string(), json!(path))
+        })
+        .collect();
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "features": environment::features(),
+        "desktop": format!("{:?}", desktop).to_lowercase(),
+        "modes": modes,
+        "backends": backends,
+        "default_backend": default_backend(desktop),
+        "formats": formats(),
+        "record_formats": names::<RecordFormat>(),
+        "protocols": environment::wayland_protocols(),
+        "tools": tools,
+    })
+}
+
+pub fn print(modes: Vec<String>, as_json: bool) -> Result<()> {
+    let report = report(modes);
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    let list = |key: &str| {
+        report[key]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default()
+    };
+    let backends: Vec<&str> = report["backends"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|backend| backend["available"] == true)
+        .filter_map(|backend| backend["name"].as_str())
+        .collect();
+    let missing: Vec<&String> = report["tools"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(_, path)| path.is_null())
+        .map(|(tool, _)| tool)
+        .collect();
+    println!("version:        {}", env!("CARGO_PKG_VERSION"));
+    println!("features:       {}", list("features"));
+    println!(
+        "desktop:        {}",
+        report["desktop"].as_str().unwrap_or_default()
+    );
+    println!("modes:          {}", list("modes"));
+    println!(
+        "backends:       {} (default: {})",
+        backends.join(", "),
+        report["default_backend"].as_str().unwrap_or_default()
+    );
+    println!("formats:        {}", list("formats"));
+    println!("record formats: {}", list("record_formats"));
+    match report["protocols"].as_array() {
+        Some(protocols) => println!("protocols:      {} advertised", protocols.len()),
+        None => println!("protocols:      unknown (install wayland-info)"),
+    }
+    println!(
+        "missing tools:  {}",
+        missing
+            .iter()
+            .map(|tool| tool.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    Ok(())
+}
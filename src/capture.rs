@@ -1,53 +1,154 @@
+use crate::geometry::Region;
+use crate::i18n;
+use crate::picker::{Item, Picker};
+use crate::raster::Image;
+use crate::runner;
+use crate::utils;
 use anyhow::{Context, Result};
 use serde_json::Value;
-use std::{
-    io::Write,
-    process::{Command, Stdio},
-};
-
-pub fn grab_output(debug: bool) -> Result<String> {
-    let output = Command::new("slurp")
-        .arg("-or")
-        .output()
-        .context("Failed to run slurp")?;
-    if !output.status.success() {
-        return Err(anyhow::anyhow!("slurp failed to select output"));
+use std::sync::OnceLock;
+
+const OUTPUT_HIGHLIGHT: &str = "#5294e255";
+const OUTPUT_BORDER: &str = "#5294e2ff";
+pub const DEFAULT_DIM: &str = "#00000066";
+
+// slurp's tint over everything outside the selection; unset keeps slurp's own.
+static DIM: OnceLock<String> = OnceLock::new();
+
+// "#rrggbb" gets a translucent alpha, since an opaque tint hides the screen.
+pub fn parse_dim(color: &str) -> Result<String> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!(
+            "Invalid dim color '{}' (expected #rrggbb or #rrggbbaa)",
+            color
+        ));
+    }
+    match hex.len() {
+        6 => Ok(format!("#{}66", hex)),
+        8 => Ok(format!("#{}", hex)),
+        _ => Err(anyhow::anyhow!(
+            "Invalid dim color '{}' (expected #rrggbb or #rrggbbaa)",
+            color
+        )),
     }
+}
+
+pub fn set_dim(color: String) {
+    let _ = DIM.set(color);
+}
+
+fn slurp(args: &[&str], boxes: Option<&str>, target: &str) -> Result<String> {
+    let mut args = args.to_vec();
+    if let Some(dim) = DIM.get() {
+        args.extend(["-b", dim.as_str()]);
+    }
+    let output = runner::checked("slurp", &args, boxes.map(str::as_bytes))
+        .context(format!("slurp failed to select {}", target))?;
     let geometry = String::from_utf8(output.stdout)
         .context("slurp output is not valid UTF-8")?
         .trim()
         .to_string();
-    if debug {
-        eprintln!("Output geometry: {}", geometry);
-    }
     if geometry.is_empty() {
         return Err(anyhow::anyhow!("slurp returned empty geometry"));
     }
     Ok(geometry)
 }
 
-pub fn grab_active_output(debug: bool) -> Result<String> {
-    let active_workspace: Value = serde_json::from_slice(
-        &Command::new("hyprctl")
-            .arg("activeworkspace")
-            .arg("-j")
-            .output()
-            .context("Failed to run hyprctl activeworkspace")?
-            .stdout,
-    )?;
-    let monitors: Value = serde_json::from_slice(
-        &Command::new("hyprctl")
-            .arg("monitors")
-            .arg("-j")
-            .output()
-            .context("Failed to run hyprctl monitors")?
-            .stdout,
-    )?;
-
-    if debug {
-        eprintln!("Monitors: {}", monitors);
-        eprintln!("Active workspace: {}", active_workspace);
+// Hyprland reports "mirrorOf": "none" for normal monitors and the source otherwise.
+fn is_mirror(monitor: &Value) -> bool {
+    match &monitor["mirrorOf"] {
+        Value::String(source) => source != "none",
+        Value::Number(_) => true,
+        _ => false,
     }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputPicker {
+    #[default]
+    Slurp,
+    List,
+}
+
+struct Output {
+    name: String,
+    description: String,
+    region: Region,
+}
+
+fn outputs(monitors: &Value, allow_mirrors: bool) -> Vec<Output> {
+    let mut outputs: Vec<Output> = Vec::new();
+    for monitor in monitors.as_array().into_iter().flatten() {
+        let name = monitor["name"].as_str().unwrap_or_default().to_string();
+        let region = Region::of_monitor(monitor);
+        if !allow_mirrors && (is_mirror(monitor) || outputs.iter().any(|o| o.region == region)) {
+            log::debug!("Skipping mirrored output {} at {}", name, region);
+            continue;
+        }
+        outputs.push(Output {
+            name,
+            description: monitor["description"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            region,
+        });
+    }
+    outputs
+}
+
+fn pick_output_from_list(outputs: &[Output], picker: &Picker) -> Result<String> {
+    let items: Vec<Item> = outputs
+        .iter()
+        .map(|o| {
+            Item::new(
+                format!(
+                    "{}  {}x{} at {},{}  {}",
+                    o.name, o.region.width, o.region.height, o.region.x, o.region.y, o.description
+                )
+                .trim_end(),
+            )
+        })
+        .collect();
+    let index = picker
+        .choose(i18n::tr("Output"), &items)?
+        .context(i18n::tr("Output selection cancelled"))?;
+    log::debug!("Picked output {}", outputs[index].name);
+    Ok(outputs[index].region.to_string())
+}
+
+pub fn grab_output(mode: OutputPicker, allow_mirrors: bool, picker: &Picker) -> Result<String> {
+    let style = ["-s", OUTPUT_HIGHLIGHT, "-c", OUTPUT_BORDER, "-w", "4"];
+    // slurp -o offers every wl_output, including mirrors that share (or
+    // pretend to have) the area of the output they copy.
+    let geometry = match mode {
+        OutputPicker::List => pick_output_from_list(
+            &outputs(&utils::hyprctl_json("monitors")?, allow_mirrors),
+            picker,
+        )?,
+        OutputPicker::Slurp if allow_mirrors => {
+            slurp(&[&["-or"], &style[..]].concat(), None, "output")?
+        }
+        OutputPicker::Slurp => {
+            let boxes = outputs(&utils::hyprctl_json("monitors")?, false)
+                .iter()
+                .map(|o| format!("{} {}", o.region, o.name))
+                .collect::<Vec<_>>()
+                .join("\n");
+            slurp(&[&["-r"], &style[..]].concat(), Some(&boxes), "output")?
+        }
+    };
+    log::debug!("Output geometry: {}", geometry);
+    Ok(geometry)
+}
+
+pub fn grab_active_output() -> Result<String> {
+    let active_workspace = utils::hyprctl_json("activeworkspace")?;
+    let monitors = utils::hyprctl_json("monitors")?;
+
+    log::debug!("Monitors: {}", monitors);
+    log::debug!("Active workspace: {}", active_workspace);
 
     let current_monitor = monitors
         .as_array()
@@ -57,105 +158,170 @@ pub fn grab_active_output(debug: bool) -> Result<String> {
         })
         .context("No matching monitor found")?;
 
-    if debug {
-        eprintln!("Current output: {}", current_monitor);
-    }
-
-    let x = current_monitor["x"].as_i64().unwrap_or(0);
-    let y = current_monitor["y"].as_i64().unwrap_or(0);
-    let width = current_monitor["width"].as_i64().unwrap_or(0) as f64;
-    let height = current_monitor["height"].as_i64().unwrap_or(0) as f64;
-    let scale = current_monitor["scale"].as_f64().unwrap_or(1.0);
+    log::debug!("Current output: {}", current_monitor);
 
-    let geometry = format!(
-        "{},{} {}x{}",
-        x,
-        y,
-        (width / scale).round() as i32,
-        (height / scale).round() as i32
-    );
-    if debug {
-        eprintln!("Active output geometry: {}", geometry);
-    }
+    let geometry = Region::of_monitor(current_monitor).to_string();
+    log::debug!("Active output geometry: {}", geometry);
     Ok(geometry)
 }
 
-pub fn grab_selected_output(monitor: &str, debug: bool) -> Result<String> {
-    let monitors: Value = serde_json::from_slice(
-        &Command::new("hyprctl")
-            .arg("monitors")
-            .arg("-j")
-            .output()
-            .context("Failed to run hyprctl monitors")?
-            .stdout,
-    )?;
+pub fn grab_selected_output(monitor: &str) -> Result<String> {
+    let monitors = utils::hyprctl_json("monitors")?;
 
     let monitor_data = monitors
         .as_array()
         .and_then(|arr| arr.iter().find(|m| m["name"].as_str() == Some(monitor)))
         .context(format!("Monitor '{}' not found", monitor))?;
 
-    if debug {
-        eprintln!("Capturing monitor: {}", monitor);
-    }
+    log::debug!("Capturing monitor: {}", monitor);
 
-    let x = monitor_data["x"].as_i64().unwrap_or(0);
-    let y = monitor_data["y"].as_i64().unwrap_or(0);
-    let width = monitor_data["width"].as_i64().unwrap_or(0) as f64;
-    let height = monitor_data["height"].as_i64().unwrap_or(0) as f64;
-    let scale = monitor_data["scale"].as_f64().unwrap_or(1.0);
+    let geometry = Region::of_monitor(monitor_data).to_string();
+    log::debug!("Selected output geometry: {}", geometry);
+    Ok(geometry)
+}
 
-    let geometry = format!(
-        "{},{} {}x{}",
-        x,
-        y,
-        (width / scale).round() as i32,
-        (height / scale).round() as i32
-    );
-    if debug {
-        eprintln!("Selected output geometry: {}", geometry);
-    }
+pub fn grab_region() -> Result<String> {
+    let geometry = slurp(&["-d"], None, "region")?;
+    log::debug!("Region geometry: {}", geometry);
     Ok(geometry)
 }
 
-pub fn grab_region(debug: bool) -> Result<String> {
-    let output = Command::new("slurp")
-        .arg("-d")
-        .output()
-        .context("Failed to run slurp")?;
-    if !output.status.success() {
-        return Err(anyhow::anyhow!("slurp failed to select region"));
+pub struct LayerSurface {
+    pub namespace: String,
+    pub monitor: String,
+    pub level: u8,
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+}
+
+impl LayerSurface {
+    pub fn geometry(&self) -> String {
+        Region::new(
+            self.x as i32,
+            self.y as i32,
+            self.width as i32,
+            self.height as i32,
+        )
+        .to_string()
     }
-    let geometry = String::from_utf8(output.stdout)
-        .context("slurp output is not valid UTF-8")?
-        .trim()
-        .to_string();
-    if debug {
-        eprintln!("Region geometry: {}", geometry);
+}
+
+pub fn layer_surfaces() -> Result<Vec<LayerSurface>> {
+    let layers = utils::hyprctl_json("layers")?;
+    log::debug!("Layers: {}", layers);
+
+    let mut surfaces = Vec::new();
+    for (monitor, info) in layers.as_object().into_iter().flatten() {
+        for (level, list) in info["levels"].as_object().into_iter().flatten() {
+            let Ok(level) = level.parse::<u8>() else {
+                continue;
+            };
+            for layer in list.as_array().into_iter().flatten() {
+                let (Some(x), Some(y), Some(width), Some(height)) = (
+                    layer["x"].as_i64(),
+                    layer["y"].as_i64(),
+                    layer["w"].as_i64(),
+                    layer["h"].as_i64(),
+                ) else {
+                    continue;
+                };
+                if width <= 0 || height <= 0 {
+                    continue;
+                }
+                surfaces.push(LayerSurface {
+                    namespace: layer["namespace"].as_str().unwrap_or("").to_string(),
+                    monitor: monitor.clone(),
+                    level,
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+        }
     }
-    if geometry.is_empty() {
-        return Err(anyhow::anyhow!("slurp returned empty geometry"));
+    surfaces.sort_by_key(|l| std::cmp::Reverse(l.level));
+    Ok(surfaces)
+}
+
+fn level_name(level: u8) -> &'static str {
+    match level {
+        0 => "background",
+        1 => "bottom",
+        2 => "top",
+        _ => "overlay",
     }
+}
+
+pub fn grab_layer(filter: &utils::WindowFilter, picker: &Picker) -> Result<String> {
+    let surfaces: Vec<LayerSurface> = layer_surfaces()?
+        .into_iter()
+        .filter(|l| filter.allows(&l.namespace))
+        .collect();
+    if surfaces.is_empty() {
+        return Err(anyhow::anyhow!("No layer surfaces found to capture"));
+    }
+
+    let items: Vec<Item> = surfaces
+        .iter()
+        .map(|l| {
+            Item::new(format!(
+                "{} ({}, {}, {}x{})",
+                if l.namespace.is_empty() {
+                    "unnamed"
+                } else {
+                    &l.namespace
+                },
+                l.monitor,
+                level_name(l.level),
+                l.width,
+                l.height
+            ))
+        })
+        .collect();
+
+    let index = picker
+        .choose(i18n::tr("Layer"), &items)?
+        .context(i18n::tr("Layer selection cancelled"))?;
+    let layer = &surfaces[index];
+    let geometry = layer.geometry();
+    log::debug!("Layer {} geometry: {}", layer.namespace, geometry);
     Ok(geometry)
 }
 
-pub fn grab_window(debug: bool) -> Result<String> {
-    let monitors: Value = serde_json::from_slice(
-        &Command::new("hyprctl")
-            .arg("monitors")
-            .arg("-j")
-            .output()
-            .context("Failed to run hyprctl monitors")?
-            .stdout,
-    )?;
-    let clients: Value = serde_json::from_slice(
-        &Command::new("hyprctl")
-            .arg("clients")
-            .arg("-j")
-            .output()
-            .context("Failed to run hyprctl clients")?
-            .stdout,
-    )?;
+fn is_fullscreen(client: &Value) -> bool {
+    match &client["fullscreen"] {
+        Value::Bool(fullscreen) => *fullscreen && client["fullscreenMode"].as_i64() != Some(1),
+        Value::Number(mode) => mode.as_i64().unwrap_or(0) & 2 != 0,
+        _ => false,
+    }
+}
+
+fn client_geometry(client: &Value, monitors: &Value) -> Option<Region> {
+    if is_fullscreen(client)
+        && let Some(monitor) = monitors.as_array().and_then(|arr| {
+            arr.iter()
+                .find(|m| m["id"].as_i64().is_some() && m["id"] == client["monitor"])
+        })
+    {
+        return Some(Region::of_monitor(monitor));
+    }
+
+    let at = client["at"].as_array()?;
+    let size = client["size"].as_array()?;
+    Some(Region::new(
+        at.first()?.as_i64()? as i32,
+        at.get(1)?.as_i64()? as i32,
+        size.first()?.as_i64()? as i32,
+        size.get(1)?.as_i64()? as i32,
+    ))
+}
+
+fn window_boxes(include_layers: bool, filter: &utils::WindowFilter) -> Result<String> {
+    let monitors = utils::hyprctl_json("monitors")?;
+    let clients = utils::hyprctl_json("clients")?;
 
     let workspace_ids: String = monitors
         .as_array()
@@ -178,126 +344,569 @@ pub fn grab_window(debug: bool) -> Result<String> {
                         .map(|id| workspace_ids.contains(&id.to_string()))
                         .unwrap_or(false)
                 })
+                .filter(|c| filter.allows(c["class"].as_str().unwrap_or("")))
                 .cloned()
                 .collect()
         })
         .unwrap_or_default();
 
-    if debug {
-        eprintln!("Monitors: {}", monitors);
-        eprintln!("Clients: {}", serde_json::to_string(&filtered_clients)?);
-    }
+    log::debug!("Monitors: {}", monitors);
+    log::debug!("Clients: {}", serde_json::to_string(&filtered_clients)?);
+
+    let layer_boxes = if include_layers {
+        layer_surfaces()?
+            .into_iter()
+            .filter(|l| l.level > 0 && filter.allows(&l.namespace))
+            .map(|l| format!("{} {}", l.geometry(), l.namespace))
+            .collect()
+    } else {
+        vec![]
+    };
 
-    let boxes: String = filtered_clients
+    let boxes: String = layer_boxes
         .into_iter()
-        .filter_map(|c| {
-            let at = c["at"].as_array()?;
-            let size = c["size"].as_array()?;
-            let x = at[0].as_i64()?;
-            let y = at[1].as_i64()?;
-            let width = size[0].as_i64()?;
-            let height = size[1].as_i64()?;
-            if width <= 0 || height <= 0 {
+        .chain(filtered_clients.into_iter().filter_map(|c| {
+            let region = client_geometry(&c, &monitors)?;
+            if region.is_empty() {
                 return None;
             }
-            Some(format!(
-                "{},{} {}x{} {}",
-                x,
-                y,
-                width,
-                height,
-                c["title"].as_str().unwrap_or("")
-            ))
-        })
+            Some(format!("{} {}", region, c["title"].as_str().unwrap_or("")))
+        }))
         .collect::<Vec<_>>()
         .join("\n");
 
-    if debug {
-        eprintln!("Window boxes:\n{}", boxes);
-    }
+    log::debug!("Window boxes:\n{}", boxes);
 
     if boxes.is_empty() {
         return Err(anyhow::anyhow!("No valid windows found to capture"));
     }
+    Ok(boxes)
+}
 
-    let mut slurp = Command::new("slurp")
-        .arg("-r")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-        .context("Failed to start slurp")?;
+pub fn grab_window(include_layers: bool, filter: &utils::WindowFilter) -> Result<String> {
+    let boxes = window_boxes(include_layers, filter)?;
+    let geometry = slurp(&["-r"], Some(&boxes), "window")?;
+    log::debug!("Window geometry: {}", geometry);
 
-    slurp
-        .stdin
-        .as_mut()
-        .unwrap()
-        .write_all(boxes.as_bytes())
-        .context("Failed to write to slurp stdin")?;
+    Region::parse(&geometry)?;
+    Ok(geometry)
+}
 
-    let output = slurp.wait_with_output().context("Failed to run slurp")?;
-    if !output.status.success() {
-        return Err(anyhow::anyhow!(
-            "slurp failed to select window: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
-    }
+// A keyboard-only alternative to clicking, for `--interactive list`.
+pub fn pick_window_from_list(
+    include_layers: bool,
+    filter: &utils::WindowFilter,
+    picker: &Picker,
+) -> Result<String> {
+    let boxes = window_boxes(include_layers, filter)?;
+    let windows: Vec<(&str, &str)> = boxes
+        .lines()
+        .filter_map(|line| {
+            let (position, rest) = line.split_once(' ')?;
+            let (size, label) = rest.split_once(' ').unwrap_or((rest, ""));
+            Some((&line[..position.len() + 1 + size.len()], label))
+        })
+        .collect();
+    let items: Vec<Item> = windows
+        .iter()
+        .map(|(geometry, label)| Item::new(format!("{}  {}", label, geometry).trim_start()))
+        .collect();
+    let index = picker
+        .choose(i18n::tr("Window"), &items)?
+        .context(i18n::tr("Window selection cancelled"))?;
+    let geometry = windows[index].0.to_string();
+    log::debug!("Window geometry: {}", geometry);
+    Ok(geometry)
+}
 
-    let geometry = String::from_utf8(output.stdout)
-        .context("slurp output is not valid UTF-8")?
-        .trim()
-        .to_string();
-    if debug {
-        eprintln!("Window geometry: {}", geometry);
-    }
-    if geometry.is_empty() {
-        return Err(anyhow::anyhow!("slurp returned empty geometry"));
+// Each slurp round toggles one window; cancelling a round ends the selection.
+pub fn grab_windows(include_layers: bool, filter: &utils::WindowFilter) -> Result<Vec<String>> {
+    let boxes = window_boxes(include_layers, filter)?;
+    let mut selected: Vec<String> = Vec::new();
+    loop {
+        let geometry = match slurp(&["-r"], Some(&boxes), "window") {
+            Ok(geometry) => geometry,
+            Err(e) if selected.is_empty() => return Err(e),
+            Err(_) => break,
+        };
+        Region::parse(&geometry)?;
+        match selected.iter().position(|g| *g == geometry) {
+            Some(index) => {
+                selected.remove(index);
+                log::debug!("Deselected window {}", geometry);
+            }
+            None => {
+                log::debug!("Selected window {}", geometry);
+                selected.push(geometry);
+            }
+        }
     }
-
-    let parts: Vec<&str> = geometry.split(' ').collect();
-    if parts.len() != 2 || parts[0].split(',').count() != 2 || parts[1].split('x').count() != 2 {
-        return Err(anyhow::anyhow!("Invalid geometry format: '{}'", geometry));
+    if selected.is_empty() {
+        return Err(anyhow::anyhow!("No windows selected"));
     }
+    Ok(selected)
+}
 
-    Ok(geometry)
+pub fn bounding_box(geometries: &[String]) -> Result<String> {
+    let combined = geometries
+        .iter()
+        .map(|g| Region::parse(g))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .reduce(|combined, region| combined.union(&region))
+        .context("No windows to combine")?;
+    log::debug!("Bounding box of {} windows: {}", geometries.len(), combined);
+    Ok(combined.to_string())
 }
 
-pub fn grab_active_window(debug: bool) -> Result<String> {
-    let active_window: Value = serde_json::from_slice(
-        &Command::new("hyprctl")
-            .arg("activewindow")
-            .arg("-j")
-            .output()
-            .context("Failed to run hyprctl activewindow")?
-            .stdout,
-    )?;
+pub fn window_address(geometry: &str) -> Result<String> {
+    let monitors = utils::hyprctl_json("monitors")?;
+    let clients = utils::hyprctl_json("clients")?;
+    let address = clients
+        .as_array()
+        .and_then(|arr| {
+            arr.iter()
+                .find(|c| client_geometry(c, &monitors).is_some_and(|b| b.to_string() == geometry))
+        })
+        .and_then(|c| c["address"].as_str())
+        .context(format!("No window found at {}", geometry))?
+        .to_string();
+    log::debug!("Window at {}: {}", geometry, address);
+    Ok(address)
+}
 
-    if debug {
-        eprintln!("Active window: {}", active_window);
-    }
+pub struct WindowInfo {
+    pub class: String,
+    pub title: String,
+}
 
-    let at = active_window["at"]
+pub fn window_info(geometry: &str) -> Result<Option<WindowInfo>> {
+    let monitors = utils::hyprctl_json("monitors")?;
+    let clients = utils::hyprctl_json("clients")?;
+    Ok(clients
         .as_array()
-        .context("Invalid active window data: missing 'at' field")?;
-    let size = active_window["size"]
+        .and_then(|arr| {
+            arr.iter()
+                .find(|c| client_geometry(c, &monitors).is_some_and(|b| b.to_string() == geometry))
+        })
+        .map(|c| WindowInfo {
+            class: c["class"].as_str().unwrap_or_default().to_string(),
+            title: c["title"].as_str().unwrap_or_default().to_string(),
+        }))
+}
+
+pub fn window_geometry(address: &str) -> Result<Option<String>> {
+    let monitors = utils::hyprctl_json("monitors")?;
+    let clients = utils::hyprctl_json("clients")?;
+    Ok(clients
         .as_array()
-        .context("Invalid active window data: missing 'size' field")?;
+        .and_then(|arr| arr.iter().find(|c| c["address"].as_str() == Some(address)))
+        .and_then(|c| client_geometry(c, &monitors))
+        .filter(|region| !region.is_empty())
+        .map(|region| region.to_string()))
+}
+
+pub fn grab_active_window() -> Result<String> {
+    let active_window = utils::hyprctl_json("activewindow")?;
+
+    log::debug!("Active window: {}", active_window);
+
+    let monitors = if is_fullscreen(&active_window) {
+        utils::hyprctl_json("monitors")?
+    } else {
+        Value::Null
+    };
+    if !monitors.is_null() {
+        log::debug!("Active window is fullscreen, using its monitor geometry");
+    }
 
-    let x = at[0].as_i64().context("Invalid x coordinate")?;
-    let y = at[1].as_i64().context("Invalid y coordinate")?;
-    let width = size[0].as_i64().context("Invalid width")?;
-    let height = size[1].as_i64().context("Invalid height")?;
+    let region = client_geometry(&active_window, &monitors)
+        .context("Invalid active window data: missing 'at' or 'size' field")?;
 
-    if width <= 0 || height <= 0 {
+    if region.is_empty() {
         return Err(anyhow::anyhow!(
             "Invalid window dimensions: width={} or height={}",
-            width,
-            height
+            region.width,
+            region.height
         ));
     }
 
-    let geometry = format!("{},{} {}x{}", x, y, width, height);
-    if debug {
-        eprintln!("Active window geometry: {}", geometry);
+    let geometry = region.to_string();
+    log::debug!("Active window geometry: {}", geometry);
+    Ok(geometry)
+}
+
+pub fn grab_ppm(geometry: &str, scale: &str) -> Result<Image> {
+    let output = runner::checked(
+        "grim",
+        &["-g", geometry, "-s", scale, "-t", "ppm", "-"],
+        None,
+    )
+    .context("grim failed to capture screenshot")?;
+    Image::from_ppm(&output.stdout)
+}
+
+// The monitor a fullscreen active window covers: only there can direct
+// scanout hand grim a black frame.
+fn fullscreen_monitor() -> Option<Region> {
+    let window = utils::hyprctl_json("activewindow").ok()?;
+    if !is_fullscreen(&window) {
+        return None;
     }
+    client_geometry(&window, &utils::hyprctl_json("monitors").ok()?)
+}
+
+pub fn ensure_not_black(geometry: &str) -> Result<()> {
+    const ATTEMPTS: u32 = 3;
+
+    let region = Region::parse(geometry)?;
+    if !fullscreen_monitor().is_some_and(|monitor| monitor.intersects(&region)) {
+        return Ok(());
+    }
+    for attempt in 1..=ATTEMPTS {
+        if !grab_ppm(geometry, "0.25")?.is_black(2) {
+            return Ok(());
+        }
+        log::debug!(
+            "Black frame detected for {} (attempt {}/{})",
+            geometry,
+            attempt,
+            ATTEMPTS
+        );
+        std::thread::sleep(std::time::Duration::from_millis(100 * attempt as u64));
+    }
+
+    Err(anyhow::anyhow!(
+        "{}",
+        i18n::tr(
+            "Captured a black frame: a fullscreen application is probably using direct scanout. \
+             Disable it with `hyprctl keyword render:direct_scanout 0` or pass --allow-black to save anyway"
+        )
+    ))
+}
+
+pub fn grab_point() -> Result<String> {
+    let geometry = slurp(&["-p"], None, "a point")?;
+    log::debug!("Point geometry: {}", geometry);
     Ok(geometry)
 }
+
+fn active_window_state() -> Option<String> {
+    let window = utils::hyprctl_json("activewindow").ok()?;
+    Some(format!(
+        "{} {} {} {}",
+        window["address"], window["at"], window["size"], window["workspace"]["id"]
+    ))
+}
+
+pub fn wait_idle(idle_ms: u64) -> Result<()> {
+    use std::time::{Duration, Instant};
+
+    const POLL: Duration = Duration::from_millis(30);
+    const TIMEOUT: Duration = Duration::from_secs(3);
+
+    let start = Instant::now();
+    let idle = Duration::from_millis(idle_ms);
+    let mut last = active_window_state();
+    let mut stable_since = Instant::now();
+    while stable_since.elapsed() < idle {
+        if start.elapsed() > TIMEOUT {
+            log::debug!(
+                "Window geometry still changing after {:?}, capturing anyway",
+                TIMEOUT
+            );
+            return Ok(());
+        }
+        std::thread::sleep(POLL);
+        let current = active_window_state();
+        if current != last {
+            last = current;
+            stable_since = Instant::now();
+        }
+    }
+    log::debug!(
+        "Window geometry settled after {} ms",
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::Fake;
+
+    const MONITORS: &str = include_str!("../tests/fixtures/hyprctl/monitors.json");
+    const CLIENTS: &str = include_str!("../tests/fixtures/hyprctl/clients.json");
+    const ACTIVE_WORKSPACE: &str = include_str!("../tests/fixtures/hyprctl/activeworkspace.json");
+    const ACTIVE_WINDOW: &str = include_str!("../tests/fixtures/hyprctl/activewindow.json");
+    const ACTIVE_WINDOW_FULLSCREEN: &str =
+        include_str!("../tests/fixtures/hyprctl/activewindow_fullscreen.json");
+
+    fn hyprland() -> Fake {
+        Fake::default()
+            .reply("hyprctl monitors -j", MONITORS)
+            .reply("hyprctl clients -j", CLIENTS)
+    }
+
+    fn filter(exclude_class: Option<&str>) -> utils::WindowFilter {
+        utils::WindowFilter {
+            include_class: None,
+            exclude_class: exclude_class.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn dim_colors_get_a_translucent_alpha() {
+        assert_eq!(parse_dim("#1e1e2e").unwrap(), "#1e1e2e66");
+        assert_eq!(parse_dim("000000aa").unwrap(), "#000000aa");
+        assert!(parse_dim("#12345").is_err());
+        assert!(parse_dim("#gggggg").is_err());
+    }
+
+    #[test]
+    fn grab_window_offers_windows_on_visible_workspaces() {
+        let fake = hyprland().reply("slurp -r", "100,100 800x600\n").install();
+        assert_eq!(
+            grab_window(false, &filter(None)).unwrap(),
+            "100,100 800x600"
+        );
+        assert_eq!(
+            fake.stdin_of("slurp -r").unwrap(),
+            "100,100 800x600 Issue 123\n2148,50 600x400 ~\n1900,900 400x300 Volume Control"
+        );
+    }
+
+    #[test]
+    fn slurp_failure_carries_its_stderr() {
+        Fake::default()
+            .fail_with("slurp -d", 1, "selection cancelled\n")
+            .install();
+        let error = slurp(&["-d"], None, "region").unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "slurp failed to select region: slurp -d: selection cancelled"
+        );
+    }
+
+    #[test]
+    fn grab_window_applies_class_filter() {
+        let fake = hyprland().reply("slurp -r", "100,100 800x600").install();
+        grab_window(false, &filter(Some("kitty|pav*"))).unwrap();
+        assert_eq!(
+            fake.stdin_of("slurp -r").unwrap(),
+            "100,100 800x600 Issue 123"
+        );
+    }
+
+    #[test]
+    fn grab_window_without_windows_fails_before_selection() {
+        let fake = hyprland().install();
+        let err = grab_window(false, &filter(Some("*"))).unwrap_err();
+        assert_eq!(err.to_string(), "No valid windows found to capture");
+        assert!(fake.stdin_of("slurp -r").is_none());
+    }
+
+    #[test]
+    fn grab_window_selection_cancelled() {
+        hyprland().fail("slurp -r", 1).install();
+        let err = grab_window(false, &filter(None)).unwrap_err();
+        assert_eq!(err.to_string(), "slurp failed to select window");
+    }
+
+    #[test]
+    fn grab_window_rejects_malformed_selection() {
+        hyprland().reply("slurp -r", "selection").install();
+        let err = grab_window(false, &filter(None)).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid geometry format"));
+    }
+
+    #[test]
+    fn grab_windows_toggles_until_cancelled() {
+        hyprland()
+            .reply("slurp -r", "100,100 800x600")
+            .reply("slurp -r", "2148,50 600x400")
+            .reply("slurp -r", "100,100 800x600")
+            .reply("slurp -r", "1900,900 400x300")
+            .fail("slurp -r", 1)
+            .install();
+        let picked = grab_windows(false, &filter(None)).unwrap();
+        assert_eq!(picked, ["2148,50 600x400", "1900,900 400x300"]);
+        assert_eq!(bounding_box(&picked).unwrap(), "1900,50 848x1150");
+    }
+
+    #[test]
+    fn grab_region_selection_cancelled() {
+        Fake::default().fail("slurp -d", 1).install();
+        assert_eq!(
+            grab_region().unwrap_err().to_string(),
+            "slurp failed to select region"
+        );
+        Fake::default().reply("slurp -d", "\n").install();
+        assert_eq!(
+            grab_region().unwrap_err().to_string(),
+            "slurp returned empty geometry"
+        );
+    }
+
+    #[test]
+    fn grab_output_skips_mirrors() {
+        let monitors = r#"[
+            { "name": "DP-1", "x": 0, "y": 0, "width": 1920, "height": 1080, "scale": 1.0, "mirrorOf": "none" },
+            { "name": "HDMI-A-1", "x": 0, "y": 0, "width": 1280, "height": 720, "scale": 1.0, "mirrorOf": "0" },
+            { "name": "DP-2", "x": 1920, "y": 0, "width": 1920, "height": 1080, "scale": 1.0, "mirrorOf": "none" },
+            { "name": "DP-3", "x": 1920, "y": 0, "width": 1920, "height": 1080, "scale": 1.0 }
+        ]"#;
+        let args = format!("slurp -r -s {} -c {} -w 4", OUTPUT_HIGHLIGHT, OUTPUT_BORDER);
+        let fake = Fake::default()
+            .reply("hyprctl monitors -j", monitors)
+            .reply(&args, "1920,0 1920x1080")
+            .install();
+        assert_eq!(
+            grab_output(OutputPicker::Slurp, false, &Picker::default()).unwrap(),
+            "1920,0 1920x1080"
+        );
+        assert_eq!(
+            fake.stdin_of(&args).unwrap(),
+            "0,0 1920x1080 DP-1\n1920,0 1920x1080 DP-2"
+        );
+    }
+
+    #[test]
+    fn grab_output_from_list() {
+        let fake = Fake::default()
+            .reply("hyprctl monitors -j", MONITORS)
+            .reply(
+                "rofi -dmenu -i -show-icons -p Output",
+                "HDMI-A-1  1920x1080 at 2048,0\n",
+            )
+            .install();
+        assert_eq!(
+            grab_output(OutputPicker::List, false, &Picker::default()).unwrap(),
+            "2048,0 1920x1080"
+        );
+        assert_eq!(
+            fake.stdin_of("rofi -dmenu -i -show-icons -p Output")
+                .unwrap(),
+            "DP-1  2048x1152 at 0,0\nHDMI-A-1  1920x1080 at 2048,0"
+        );
+    }
+
+    #[test]
+    fn pick_window_from_list_uses_the_menu() {
+        let rofi = "rofi -dmenu -i -show-icons -p Window";
+        let fake = hyprland().reply(rofi, "~  2148,50 600x400\n").install();
+        assert_eq!(
+            pick_window_from_list(false, &filter(None), &Picker::default()).unwrap(),
+            "2148,50 600x400"
+        );
+        assert_eq!(
+            fake.stdin_of(rofi).unwrap(),
+            "Issue 123  100,100 800x600\n~  2148,50 600x400\nVolume Control  1900,900 400x300"
+        );
+    }
+
+    #[test]
+    fn negative_origins_pass_through() {
+        Fake::default()
+            .reply("slurp -d", "-1900,-150 640x480\n")
+            .reply(
+                "hyprctl monitors -j",
+                include_str!("../tests/fixtures/hyprctl/monitors_left.json"),
+            )
+            .install();
+        assert_eq!(grab_region().unwrap(), "-1900,-150 640x480");
+        assert_eq!(
+            grab_selected_output("DP-2").unwrap(),
+            "-1920,-200 1920x1080"
+        );
+    }
+
+    #[test]
+    fn grab_active_output_uses_monitor_of_active_workspace() {
+        Fake::default()
+            .reply("hyprctl monitors -j", MONITORS)
+            .reply("hyprctl activeworkspace -j", ACTIVE_WORKSPACE)
+            .install();
+        assert_eq!(grab_active_output().unwrap(), "2048,0 1920x1080");
+    }
+
+    #[test]
+    fn grab_active_output_divides_by_scale() {
+        Fake::default()
+            .reply("hyprctl monitors -j", MONITORS)
+            .reply("hyprctl activeworkspace -j", r#"{ "id": 1 }"#)
+            .install();
+        assert_eq!(grab_active_output().unwrap(), "0,0 2048x1152");
+    }
+
+    #[test]
+    fn grab_active_output_without_matching_monitor() {
+        Fake::default()
+            .reply("hyprctl monitors -j", MONITORS)
+            .reply("hyprctl activeworkspace -j", r#"{ "id": 7 }"#)
+            .install();
+        assert_eq!(
+            grab_active_output().unwrap_err().to_string(),
+            "No matching monitor found"
+        );
+    }
+
+    #[test]
+    fn grab_active_window_uses_client_box() {
+        Fake::default()
+            .reply("hyprctl activewindow -j", ACTIVE_WINDOW)
+            .install();
+        assert_eq!(grab_active_window().unwrap(), "2148,50 600x400");
+    }
+
+    #[test]
+    fn grab_active_window_fullscreen_uses_monitor() {
+        Fake::default()
+            .reply("hyprctl activewindow -j", ACTIVE_WINDOW_FULLSCREEN)
+            .reply("hyprctl monitors -j", MONITORS)
+            .install();
+        assert_eq!(grab_active_window().unwrap(), "0,0 2048x1152");
+    }
+
+    #[test]
+    fn black_frame_check_skips_other_monitors() {
+        let fake = Fake::default()
+            .reply("hyprctl activewindow -j", ACTIVE_WINDOW_FULLSCREEN)
+            .reply("hyprctl monitors -j", MONITORS)
+            .install();
+        ensure_not_black("2148,50 600x400").unwrap();
+        assert!(
+            !fake
+                .calls
+                .borrow()
+                .iter()
+                .any(|(call, _)| call.starts_with("grim"))
+        );
+    }
+
+    #[test]
+    fn black_frame_check_grabs_the_fullscreen_monitor() {
+        let fake = Fake::default()
+            .reply("hyprctl activewindow -j", ACTIVE_WINDOW_FULLSCREEN)
+            .reply("hyprctl monitors -j", MONITORS)
+            .reply(
+                "grim -g 100,100 800x600 -s 0.25 -t ppm -",
+                "P6\n1 1\n255\n\x7f\x7f\x7f",
+            )
+            .install();
+        ensure_not_black("100,100 800x600").unwrap();
+        assert_eq!(
+            fake.calls.borrow().last().unwrap().0,
+            "grim -g 100,100 800x600 -s 0.25 -t ppm -"
+        );
+    }
+
+    #[test]
+    fn window_info_matches_geometry() {
+        hyprland().install();
+        let info = window_info("2148,50 600x400").unwrap().unwrap();
+        assert_eq!((info.class.as_str(), info.title.as_str()), ("kitty", "~"));
+        assert!(window_info("1,1 1x1").unwrap().is_none());
+    }
+}
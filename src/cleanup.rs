@@ -0,0 +1,127 @@
+use crate::storage;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+// Intermediate files in the runtime directory, named after the process that
+// made them (storage::temp_file). Only these names are ever touched.
+const PREFIXES: [&str; 7] = [
+    "capture-",
+    "post-",
+    "encode-",
+    "icc-",
+    "hdr-",
+    "netrc-",
+    "recording-",
+];
+
+fn owner(name: &str) -> Option<u32> {
+    let rest = name.strip_prefix(storage::TEMP_PREFIX)?;
+    let rest = PREFIXES
+        .iter()
+        .find_map(|prefix| rest.strip_prefix(prefix))?;
+    let digits = rest.split(|c: char| !c.is_ascii_digit()).next()?;
+    digits.parse().ok()
+}
+
+fn is_running(pid: u32) -> bool {
+    pid == std::process::id() || Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+// Files left behind by hyprshot-rs processes that are no longer running.
+pub fn stale_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context(format!("Failed to read '{}'", dir.display())),
+    };
+    let mut stale = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let pid = match name {
+            "record.pid" => std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|pid| pid.trim().parse().ok()),
+            _ => owner(name),
+        };
+        if pid.is_some_and(|pid| !is_running(pid)) && path.is_file() {
+            stale.push(path);
+        }
+    }
+    stale.sort();
+    Ok(stale)
+}
+
+// Runs on startup; a crash or SIGKILL is the only way these are left around.
+// Only the directory hyprshot-rs owns is searched, never the shared temp one.
+pub fn remove_stale() {
+    match storage::private_runtime_dir().and_then(|dir| stale_files(&dir)) {
+        Ok(files) => {
+            for file in files {
+                match std::fs::remove_file(&file) {
+                    Ok(()) => log::debug!("Removed stale temporary file {}", file.display()),
+                    Err(e) => log::debug!("Failed to remove '{}': {}", file.display(), e),
+                }
+            }
+        }
+        Err(e) => log::debug!("Skipping temporary file cleanup: {:#}", e),
+    }
+}
+
+pub fn clean_temp(dry_run: bool) -> Result<()> {
+    let files = stale_files(&storage::private_runtime_dir()?)?;
+    for file in &files {
+        println!("{}", file.display());
+        if !dry_run {
+            std::fs::remove_file(file).context(format!("Failed to remove '{}'", file.display()))?;
+        }
+    }
+    if dry_run {
+        println!("{} stale temporary file(s)", files.len());
+    } else {
+        println!("Removed {} stale temporary file(s)", files.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_files_of_exited_processes_only() {
+        let dir = std::env::temp_dir().join(format!("hyprshot-rs-clean-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // PIDs are capped well below u32::MAX, so this one can't be running.
+        let dead = u32::MAX - 1;
+        let mine = std::process::id();
+        for name in [
+            format!("hyprshot-encode-{}-3-in.png", dead),
+            format!("hyprshot-capture-{}.png", mine),
+            format!("hyprshot-recording-{}-0.mkv", dead),
+            format!("capture-{}.png", dead),
+            "flash.frag".to_string(),
+            format!("hyprshot-notes-{}.txt", dead),
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        std::fs::write(dir.join("record.pid"), dead.to_string()).unwrap();
+
+        let names: Vec<String> = stale_files(&dir)
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            names,
+            [
+                format!("hyprshot-encode-{}-3-in.png", dead),
+                format!("hyprshot-recording-{}-0.mkv", dead),
+                "record.pid".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::crash;
+use crate::environment;
+use crate::i18n;
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum PrimarySelection {
+    Both,
+    Only,
+}
+
+fn selection_targets(primary: Option<PrimarySelection>) -> &'static [bool] {
+    match primary {
+        None => &[false],
+        Some(PrimarySelection::Both) => &[false, true],
+        Some(PrimarySelection::Only) => &[true],
+    }
+}
+
+pub fn description(primary: Option<PrimarySelection>) -> &'static str {
+    match primary {
+        None => i18n::tr("the clipboard"),
+        Some(PrimarySelection::Both) => i18n::tr("the clipboard and primary selection"),
+        Some(PrimarySelection::Only) => i18n::tr("the primary selection"),
+    }
+}
+
+fn copy_x11(
+    data: &[u8],
+    mime: &str,
+    primary: Option<PrimarySelection>,
+    paste_once: bool,
+) -> Result<()> {
+    for &primary in selection_targets(primary) {
+        let mut xclip = Command::new("xclip");
+        if paste_once {
+            xclip.args(["-loops", "1"]);
+        }
+        let mut xclip = match xclip
+            .args([
+                "-selection",
+                if primary { "primary" } else { "clipboard" },
+                "-target",
+                mime,
+            ])
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(anyhow::anyhow!(
+                    "xclip not found; install xclip to copy screenshots on X11"
+                ));
+            }
+            Err(e) => return Err(e).context("Failed to start xclip"),
+        };
+        crash::feeding(xclip.id());
+        xclip
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(data)
+            .context("Failed to write to xclip stdin")?;
+        let xclip_status = xclip.wait().context("Failed to wait for xclip")?;
+        crash::fed(xclip.id());
+        if !xclip_status.success() {
+            return Err(anyhow::anyhow!(
+                "xclip failed to copy screenshot ({})",
+                xclip_status
+            ));
+        }
+    }
+    Ok(())
+}
+
+// wl-copy and xclip fork once the selection is set and serve pastes from the
+// background, so waiting for them here never cuts an offer short. With
+// `paste_once` the background copy goes away after the first paste.
+pub fn copy(
+    data: &[u8],
+    mime: &str,
+    primary: Option<PrimarySelection>,
+    paste_once: bool,
+) -> Result<()> {
+    if environment::is_x11_session() {
+        return copy_x11(data, mime, primary, paste_once);
+    }
+    if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        return Err(anyhow::anyhow!(
+            "WAYLAND_DISPLAY is not set; copying to the clipboard requires a Wayland session"
+        ));
+    }
+
+    for &primary in selection_targets(primary) {
+        let mut wl_copy = Command::new("wl-copy");
+        if primary {
+            wl_copy.arg("--primary");
+        }
+        if paste_once {
+            wl_copy.arg("--paste-once");
+        }
+        let mut wl_copy = match wl_copy
+            .arg("--type")
+            .arg(mime)
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(anyhow::anyhow!(
+                    "wl-copy not found; install wl-clipboard to copy screenshots"
+                ));
+            }
+            Err(e) => return Err(e).context("Failed to start wl-copy"),
+        };
+        crash::feeding(wl_copy.id());
+        wl_copy
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(data)
+            .context("Failed to write to wl-copy stdin")?;
+        let wl_copy_status = wl_copy.wait().context("Failed to wait for wl-copy")?;
+        crash::fed(wl_copy.id());
+        if !wl_copy_status.success() {
+            return Err(anyhow::anyhow!(
+                "wl-copy failed to set {} ({}); the compositor may not support the data-control protocol",
+                if primary {
+                    "the primary selection"
+                } else {
+                    "the clipboard"
+                },
+                wl_copy_status
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn percent_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for byte in bytes {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(*byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+pub fn file_uri(path: &Path) -> String {
+    format!(
+        "file://{}",
+        percent_encode(path.as_os_str().as_encoded_bytes())
+    )
+}
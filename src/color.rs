@@ -0,0 +1,83 @@
+use crate::capture;
+use crate::clipboard;
+use crate::i18n;
+use crate::raster::Image;
+use crate::save::{self, SaveOptions};
+use crate::storage;
+use anyhow::{Context, Result};
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum ColorFormat {
+    Hex,
+    Rgb,
+}
+
+pub fn format_color([r, g, b]: [u8; 3], format: ColorFormat) -> String {
+    match format {
+        ColorFormat::Hex => format!("#{:02X}{:02X}{:02X}", r, g, b),
+        ColorFormat::Rgb => format!("rgb({}, {}, {})", r, g, b),
+    }
+}
+
+fn write_swatch(color: [u8; 3]) -> Result<std::path::PathBuf> {
+    let size = 64;
+    let swatch = Image {
+        width: size,
+        height: size,
+        pixels: color.repeat(size * size),
+    };
+    let path = storage::cache_file("swatch.ppm")?;
+    std::fs::write(&path, swatch.to_ppm()).context("Failed to write color swatch")?;
+    Ok(path)
+}
+
+pub fn pick_color() -> Result<[u8; 3]> {
+    let geometry = capture::grab_point()?;
+    let image = capture::grab_ppm(&geometry, "1")?;
+    if image.width == 0 || image.height == 0 {
+        return Err(anyhow::anyhow!("grim returned an empty image"));
+    }
+    let color = image.pixel(0, 0);
+    log::debug!(
+        "Picked color at {}: {}",
+        geometry,
+        format_color(color, ColorFormat::Hex)
+    );
+    Ok(color)
+}
+
+pub fn pick(format: ColorFormat, opts: &SaveOptions) -> Result<()> {
+    let color = pick_color()?;
+    let text = format_color(color, format);
+    println!("{}", text);
+
+    if !opts.no_clipboard {
+        clipboard::copy(
+            text.as_bytes(),
+            "text/plain;charset=utf-8",
+            opts.primary,
+            opts.paste_once,
+        )?;
+    }
+
+    if !opts.silent {
+        let body = if opts.no_clipboard {
+            text
+        } else {
+            i18n::trf(
+                "<b>{color}</b> copied to {target}.",
+                &[
+                    ("color", &text),
+                    ("target", clipboard::description(opts.primary)),
+                ],
+            )
+        };
+        save::notify(
+            i18n::tr("Color picked"),
+            &body,
+            Some(&write_swatch(color)?),
+            opts,
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,715 @@
+use crate::capture;
+use crate::encode;
+use crate::flash;
+use crate::frame::{self, FrameStyle};
+use crate::geometry::Region;
+use crate::hooks::{self, Hooks};
+use crate::instance;
+use crate::metadata;
+use crate::paths;
+use crate::picker;
+use crate::post;
+use crate::protect;
+use crate::resize;
+use crate::storage;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, TableLike};
+
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    pub file_mode: Option<u32>,
+    pub dir_mode: Option<u32>,
+    pub resize: Option<f64>,
+    pub max_width: Option<u32>,
+    pub include_class: Option<String>,
+    pub exclude_class: Option<String>,
+    pub output_folder: Option<PathBuf>,
+    pub clipboard_only: Option<bool>,
+    pub silent: Option<bool>,
+    pub command: Option<Vec<String>>,
+    pub detach: Option<bool>,
+    pub format: Option<encode::Format>,
+    pub post: Option<Vec<String>>,
+    pub organize: Option<paths::Organize>,
+    pub filename_template: Option<String>,
+    pub interactive: Option<capture::OutputPicker>,
+    pub picker: Option<picker::Kind>,
+    pub picker_theme: Option<String>,
+    pub picker_args: Option<Vec<String>>,
+    pub toolbar: Option<bool>,
+    pub editor: Option<Vec<String>>,
+    pub upload: Option<PathBuf>,
+    pub protected_classes: Option<Vec<String>>,
+    pub protected_action: Option<protect::Action>,
+    pub strip_metadata: Option<metadata::Strip>,
+    pub sign: Option<PathBuf>,
+    pub flash: Option<bool>,
+    pub flash_color: Option<String>,
+    pub shutter_sound: Option<PathBuf>,
+    pub snap: Option<u32>,
+    pub dim: Option<String>,
+    pub thumbnail: Option<u32>,
+    pub thumbnail_cache: Option<bool>,
+    pub min_free_space: Option<u64>,
+    pub low_space: Option<storage::LowSpace>,
+    pub concurrent: Option<instance::Concurrent>,
+    pub clipboard_paste_once: Option<bool>,
+    pub timeout: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    settings: Settings,
+    pub png_compression: Option<encode::PngCompression>,
+    pub jxl_quality: Option<u8>,
+    pub webp_quality: Option<u8>,
+    pub icc_profiles: Vec<(String, PathBuf)>,
+    pub frame: Option<frame::Frame>,
+    pub frame_style: Option<FrameStyle>,
+    pub hooks: Hooks,
+    regions: Vec<(String, Region)>,
+    modes: Vec<(String, Settings)>,
+    profiles: Vec<(String, Settings)>,
+}
+
+const MODES: &[&str] = &["output", "window", "region", "pixel", "layer"];
+
+fn parse_mode(item: Option<&Item>, key: &str) -> Result<Option<u32>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    if let Some(mode) = item.as_integer() {
+        return u32::try_from(mode)
+            .map(Some)
+            .context(format!("Invalid '{}' in config", key));
+    }
+    let mode = item
+        .as_str()
+        .context(format!("'{}' must be an octal string like \"0600\"", key))?;
+    u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+        .map(Some)
+        .context(format!("Invalid octal '{}' value '{}'", key, mode))
+}
+
+fn parse_resize(item: Option<&Item>) -> Result<Option<f64>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    let value = match (item.as_str(), item.as_integer(), item.as_float()) {
+        (Some(s), _, _) => s.to_string(),
+        (_, Some(i), _) => i.to_string(),
+        (_, _, Some(f)) => f.to_string(),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "'resize' must be a percentage like \"50%\""
+            ));
+        }
+    };
+    resize::parse_percent(&value)
+        .map(Some)
+        .map_err(|e| anyhow::anyhow!("Invalid 'resize' in config: {}", e))
+}
+
+fn parse_width(item: Option<&Item>, key: &str) -> Result<Option<u32>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    item.as_integer()
+        .and_then(|width| u32::try_from(width).ok())
+        .filter(|&width| width > 0)
+        .map(Some)
+        .context(format!("'{}' must be a positive number of pixels", key))
+}
+
+fn parse_string(item: Option<&Item>, key: &str) -> Result<Option<String>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    item.as_str()
+        .map(|s| Some(s.to_string()))
+        .context(format!("'{}' must be a string", key))
+}
+
+fn parse_bool(item: Option<&Item>, key: &str) -> Result<Option<bool>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    item.as_bool()
+        .map(Some)
+        .context(format!("'{}' must be true or false", key))
+}
+
+fn parse_command(item: Option<&Item>) -> Result<Option<Vec<String>>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    if let Some(command) = item.as_str() {
+        return Ok(Some(
+            command.split_whitespace().map(str::to_string).collect(),
+        ));
+    }
+    item.as_array()
+        .and_then(|array| {
+            array
+                .iter()
+                .map(|arg| arg.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+        })
+        .filter(|command| !command.is_empty())
+        .map(Some)
+        .context("'command' must be a string or a list of strings")
+}
+
+fn parse_format(item: Option<&Item>) -> Result<Option<encode::Format>> {
+    use clap::ValueEnum;
+
+    let Some(format) = parse_string(item, "format")? else {
+        return Ok(None);
+    };
+    encode::Format::from_str(&format, true)
+        .map(Some)
+        .map_err(|_| anyhow::anyhow!("Unsupported 'format' '{}' in config", format))
+}
+
+fn parse_post(item: Option<&Item>) -> Result<Option<Vec<String>>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    let steps = item
+        .as_array()
+        .and_then(|array| {
+            array
+                .iter()
+                .map(|step| step.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+        })
+        .context("'post' must be a list of steps like [\"autocrop\", \"resize=50%\"]")?;
+    post::parse(&steps).context("Invalid 'post' in config")?;
+    Ok(Some(steps))
+}
+
+fn parse_organize(item: Option<&Item>) -> Result<Option<paths::Organize>> {
+    use clap::ValueEnum;
+
+    let Some(organize) = parse_string(item, "organize")? else {
+        return Ok(None);
+    };
+    paths::Organize::from_str(&organize, true)
+        .map(Some)
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid 'organize' '{}' (expected none, daily or monthly)",
+                organize
+            )
+        })
+}
+
+fn parse_interactive(item: Option<&Item>) -> Result<Option<capture::OutputPicker>> {
+    use clap::ValueEnum;
+
+    let Some(picker) = parse_string(item, "interactive")? else {
+        return Ok(None);
+    };
+    capture::OutputPicker::from_str(&picker, true)
+        .map(Some)
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid 'interactive' '{}' (expected slurp or list)",
+                picker
+            )
+        })
+}
+
+fn parse_picker(item: Option<&Item>) -> Result<Option<picker::Kind>> {
+    use clap::ValueEnum;
+
+    let Some(kind) = parse_string(item, "picker")? else {
+        return Ok(None);
+    };
+    picker::Kind::from_str(&kind, true).map(Some).map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid 'picker' '{}' (expected rofi, wofi, fuzzel, bemenu, dmenu or fzf)",
+            kind
+        )
+    })
+}
+
+fn parse_protected_action(item: Option<&Item>) -> Result<Option<protect::Action>> {
+    use clap::ValueEnum;
+
+    let Some(action) = parse_string(item, "protected_action")? else {
+        return Ok(None);
+    };
+    protect::Action::from_str(&action, true)
+        .map(Some)
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid 'protected_action' '{}' (expected refuse or blur)",
+                action
+            )
+        })
+}
+
+fn parse_strip_metadata(item: Option<&Item>) -> Result<Option<metadata::Strip>> {
+    use clap::ValueEnum;
+
+    let Some(strip) = parse_string(item, "strip_metadata")? else {
+        return Ok(None);
+    };
+    metadata::Strip::from_str(&strip, true)
+        .map(Some)
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid 'strip_metadata' '{}' (expected clipboard or all)",
+                strip
+            )
+        })
+}
+
+fn parse_flash_color(item: Option<&Item>) -> Result<Option<String>> {
+    let Some(color) = parse_string(item, "flash_color")? else {
+        return Ok(None);
+    };
+    flash::parse_color(&color)?;
+    Ok(Some(color))
+}
+
+fn parse_dim(item: Option<&Item>) -> Result<Option<String>> {
+    let Some(color) = parse_string(item, "dim")? else {
+        return Ok(None);
+    };
+    capture::parse_dim(&color)?;
+    Ok(Some(color))
+}
+
+fn parse_size(item: Option<&Item>) -> Result<Option<u64>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    if let Some(bytes) = item.as_integer() {
+        return u64::try_from(bytes)
+            .map(Some)
+            .context("'min_free_space' must not be negative");
+    }
+    let size = item
+        .as_str()
+        .context("'min_free_space' must be a size like \"500M\"")?;
+    storage::parse_size(size).map(Some)
+}
+
+fn parse_low_space(item: Option<&Item>) -> Result<Option<storage::LowSpace>> {
+    use clap::ValueEnum;
+
+    let Some(action) = parse_string(item, "low_space")? else {
+        return Ok(None);
+    };
+    storage::LowSpace::from_str(&action, true)
+        .map(Some)
+        .map_err(|_| anyhow::anyhow!("Invalid 'low_space' '{}' (expected warn or fail)", action))
+}
+
+fn parse_concurrent(item: Option<&Item>) -> Result<Option<instance::Concurrent>> {
+    use clap::ValueEnum;
+
+    let Some(policy) = parse_string(item, "concurrent")? else {
+        return Ok(None);
+    };
+    instance::Concurrent::from_str(&policy, true)
+        .map(Some)
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid 'concurrent' '{}' (expected deny, queue or cancel-previous)",
+                policy
+            )
+        })
+}
+
+fn parse_strings(item: Option<&Item>, key: &str) -> Result<Option<Vec<String>>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    item.as_array()
+        .and_then(|array| {
+            array
+                .iter()
+                .map(|arg| arg.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+        })
+        .map(Some)
+        .context(format!("'{}' must be a list of strings", key))
+}
+
+fn parse_png_compression(item: Option<&Item>) -> Result<Option<encode::PngCompression>> {
+    use clap::ValueEnum;
+
+    let Some(compression) = parse_string(item, "compression")? else {
+        return Ok(None);
+    };
+    encode::PngCompression::from_str(&compression, true)
+        .map(Some)
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid [png] 'compression' '{}' (expected fast, default or best)",
+                compression
+            )
+        })
+}
+
+fn parse_quality(item: Option<&Item>) -> Result<Option<u8>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    item.as_integer()
+        .and_then(|quality| u8::try_from(quality).ok())
+        .filter(|quality| (1..=100).contains(quality))
+        .map(Some)
+        .context("'quality' must be a number from 1 to 100")
+}
+
+fn parse_timeout(item: Option<&Item>) -> Result<Option<u32>> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    item.as_integer()
+        .and_then(|secs| u32::try_from(secs).ok())
+        .filter(|&secs| secs > 0)
+        .map(Some)
+        .context("'timeout' must be a positive number of seconds")
+}
+
+fn format_section<'a>(doc: &'a DocumentMut, name: &str) -> Result<Option<&'a dyn TableLike>> {
+    doc.get(name)
+        .map(|section| {
+            section
+                .as_table_like()
+                .context(format!("[{}] must be a table", name))
+        })
+        .transpose()
+}
+
+fn parse_icc_profiles(section: &dyn TableLike) -> Result<Vec<(String, PathBuf)>> {
+    section
+        .iter()
+        .map(|(output, path)| {
+            let path = path.as_str().context(format!(
+                "[icc] '{}' must be the path of an ICC profile",
+                output
+            ))?;
+            Ok((output.to_string(), paths::expand(Path::new(path))))
+        })
+        .collect()
+}
+
+fn parse_frame(section: &dyn TableLike) -> Result<(Option<frame::Frame>, FrameStyle)> {
+    use clap::ValueEnum;
+
+    let frame = parse_string(section.get("style"), "style")?
+        .map(|style| {
+            frame::Frame::from_str(&style, true).map_err(|_| {
+                anyhow::anyhow!(
+                    "Invalid 'style' '{}' (expected macos, none or custom)",
+                    style
+                )
+            })
+        })
+        .transpose()?;
+    let defaults = FrameStyle::macos();
+    let buttons = match section.get("buttons") {
+        Some(item) => item
+            .as_array()
+            .and_then(|array| {
+                array
+                    .iter()
+                    .map(|color| color.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .context("'buttons' must be a list of colors")?,
+        None => defaults.buttons,
+    };
+    let style = FrameStyle {
+        background: parse_string(section.get("background"), "background")?
+            .unwrap_or(defaults.background),
+        foreground: parse_string(section.get("foreground"), "foreground")?
+            .unwrap_or(defaults.foreground),
+        height: parse_width(section.get("height"), "height")?.unwrap_or(defaults.height),
+        buttons,
+        font: parse_string(section.get("font"), "font")?,
+    };
+    Ok((frame, style))
+}
+
+fn parse_hooks(section: &dyn TableLike) -> Result<Hooks> {
+    let mut commands = Vec::new();
+    for (key, item) in section.iter() {
+        let (_, stage) = hooks::STAGES
+            .iter()
+            .find(|(name, _)| *name == key)
+            .context(format!(
+                "Unknown hook '{}' (expected pre_capture, post_capture, pre_save or post_save)",
+                key
+            ))?;
+        let command = parse_command(Some(item))?.context(format!("Hook '{}' is empty", key))?;
+        commands.push((*stage, command));
+    }
+    Ok(Hooks::new(commands))
+}
+
+fn parse_regions(section: &dyn TableLike) -> Result<Vec<(String, Region)>> {
+    section
+        .iter()
+        .map(|(name, geometry)| {
+            let geometry = geometry.as_str().context(format!(
+                "[regions] '{}' must be a geometry like \"100,100 1280x720\"",
+                name
+            ))?;
+            let region =
+                Region::parse(geometry).context(format!("Invalid [regions] '{}'", name))?;
+            if region.is_empty() {
+                return Err(anyhow::anyhow!("[regions] '{}' is empty", name));
+            }
+            Ok((name.to_string(), region))
+        })
+        .collect()
+}
+
+impl Settings {
+    fn parse(table: &dyn TableLike) -> Result<Settings> {
+        Ok(Settings {
+            file_mode: parse_mode(table.get("file_mode"), "file_mode")?,
+            dir_mode: parse_mode(table.get("dir_mode"), "dir_mode")?,
+            resize: parse_resize(table.get("resize"))?,
+            max_width: parse_width(table.get("max_width"), "max_width")?,
+            include_class: parse_string(table.get("include_class"), "include_class")?,
+            exclude_class: parse_string(table.get("exclude_class"), "exclude_class")?,
+            output_folder: parse_string(table.get("output_folder"), "output_folder")?
+                .map(PathBuf::from),
+            clipboard_only: parse_bool(table.get("clipboard_only"), "clipboard_only")?,
+            silent: parse_bool(table.get("silent"), "silent")?,
+            command: parse_command(table.get("command"))?,
+            detach: parse_bool(table.get("detach"), "detach")?,
+            format: parse_format(table.get("format"))?,
+            post: parse_post(table.get("post"))?,
+            organize: parse_organize(table.get("organize"))?,
+            filename_template: parse_string(table.get("filename_template"), "filename_template")?,
+            interactive: parse_interactive(table.get("interactive"))?,
+            picker: parse_picker(table.get("picker"))?,
+            picker_theme: parse_string(table.get("picker_theme"), "picker_theme")?,
+            picker_args: parse_strings(table.get("picker_args"), "picker_args")?,
+            toolbar: parse_bool(table.get("toolbar"), "toolbar")?,
+            editor: parse_command(table.get("editor"))?,
+            upload: parse_string(table.get("upload"), "upload")?.map(PathBuf::from),
+            protected_classes: parse_strings(table.get("protected_classes"), "protected_classes")?,
+            protected_action: parse_protected_action(table.get("protected_action"))?,
+            strip_metadata: parse_strip_metadata(table.get("strip_metadata"))?,
+            sign: parse_string(table.get("sign"), "sign")?.map(PathBuf::from),
+            flash: parse_bool(table.get("flash"), "flash")?,
+            flash_color: parse_flash_color(table.get("flash_color"))?,
+            shutter_sound: parse_string(table.get("shutter_sound"), "shutter_sound")?
+                .map(PathBuf::from),
+            snap: parse_width(table.get("snap"), "snap")?,
+            dim: parse_dim(table.get("dim"))?,
+            thumbnail: parse_width(table.get("thumbnail"), "thumbnail")?,
+            thumbnail_cache: parse_bool(table.get("thumbnail_cache"), "thumbnail_cache")?,
+            min_free_space: parse_size(table.get("min_free_space"))?,
+            low_space: parse_low_space(table.get("low_space"))?,
+            concurrent: parse_concurrent(table.get("concurrent"))?,
+            clipboard_paste_once: parse_bool(
+                table.get("clipboard_paste_once"),
+                "clipboard_paste_once",
+            )?,
+            timeout: parse_timeout(table.get("timeout"))?,
+        })
+    }
+
+    fn or(self, fallback: &Settings) -> Settings {
+        Settings {
+            file_mode: self.file_mode.or(fallback.file_mode),
+            dir_mode: self.dir_mode.or(fallback.dir_mode),
+            resize: self.resize.or(fallback.resize),
+            max_width: self.max_width.or(fallback.max_width),
+            include_class: self.include_class.or(fallback.include_class.clone()),
+            exclude_class: self.exclude_class.or(fallback.exclude_class.clone()),
+            output_folder: self.output_folder.or(fallback.output_folder.clone()),
+            clipboard_only: self.clipboard_only.or(fallback.clipboard_only),
+            silent: self.silent.or(fallback.silent),
+            command: self.command.or(fallback.command.clone()),
+            detach: self.detach.or(fallback.detach),
+            format: self.format.or(fallback.format),
+            post: self.post.or(fallback.post.clone()),
+            organize: self.organize.or(fallback.organize),
+            filename_template: self
+                .filename_template
+                .or(fallback.filename_template.clone()),
+            interactive: self.interactive.or(fallback.interactive),
+            picker: self.picker.or(fallback.picker),
+            picker_theme: self.picker_theme.or(fallback.picker_theme.clone()),
+            picker_args: self.picker_args.or(fallback.picker_args.clone()),
+            toolbar: self.toolbar.or(fallback.toolbar),
+            editor: self.editor.or(fallback.editor.clone()),
+            upload: self.upload.or(fallback.upload.clone()),
+            protected_classes: self
+                .protected_classes
+                .or(fallback.protected_classes.clone()),
+            protected_action: self.protected_action.or(fallback.protected_action),
+            strip_metadata: self.strip_metadata.or(fallback.strip_metadata),
+            sign: self.sign.or(fallback.sign.clone()),
+            flash: self.flash.or(fallback.flash),
+            flash_color: self.flash_color.or(fallback.flash_color.clone()),
+            shutter_sound: self.shutter_sound.or(fallback.shutter_sound.clone()),
+            snap: self.snap.or(fallback.snap),
+            dim: self.dim.or(fallback.dim.clone()),
+            thumbnail: self.thumbnail.or(fallback.thumbnail),
+            thumbnail_cache: self.thumbnail_cache.or(fallback.thumbnail_cache),
+            min_free_space: self.min_free_space.or(fallback.min_free_space),
+            low_space: self.low_space.or(fallback.low_space),
+            concurrent: self.concurrent.or(fallback.concurrent),
+            clipboard_paste_once: self.clipboard_paste_once.or(fallback.clipboard_paste_once),
+            timeout: self.timeout.or(fallback.timeout),
+        }
+    }
+}
+
+impl Config {
+    pub fn resolve(&self, mode: Option<&str>, profile: Option<&str>) -> Result<Settings> {
+        let mut settings = mode
+            .and_then(|mode| self.modes.iter().find(|(name, _)| name == mode))
+            .map(|(_, settings)| settings.clone().or(&self.settings))
+            .unwrap_or_else(|| self.settings.clone());
+        if let Some(profile) = profile {
+            let (_, overrides) = self
+                .profiles
+                .iter()
+                .find(|(name, _)| name == profile)
+                .context(format!(
+                    "Unknown profile '{}' (define it as [profile.{}] in the config file)",
+                    profile, profile
+                ))?;
+            settings = overrides.clone().or(&settings);
+        }
+        Ok(settings)
+    }
+
+    pub fn region(&self, name: &str) -> Result<Region> {
+        self.regions
+            .iter()
+            .find(|(region, _)| region == name)
+            .map(|(_, region)| *region)
+            .context(format!(
+                "Unknown region '{}' (define it as {} = \"x,y wxh\" under [regions] in the config file)",
+                name, name
+            ))
+    }
+
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let explicit = path.is_some();
+        let Some(path) = path.map(Path::to_path_buf).or_else(storage::config_file) else {
+            return Ok(Config::default());
+        };
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => {
+                return Ok(Config::default());
+            }
+            Err(e) => {
+                return Err(e).context(format!("Failed to read config '{}'", path.display()));
+            }
+        };
+        log::debug!("Loading config: {}", path.display());
+
+        let doc: DocumentMut = contents
+            .parse()
+            .context(format!("Failed to parse config '{}'", path.display()))?;
+        let mut modes = Vec::new();
+        if let Some(sections) = doc.get("mode") {
+            let sections = sections
+                .as_table_like()
+                .context("'mode' must be a table of [mode.NAME] sections")?;
+            for (name, section) in sections.iter() {
+                if !MODES.contains(&name) {
+                    return Err(anyhow::anyhow!(
+                        "Unknown mode section [mode.{}] (expected one of: {})",
+                        name,
+                        MODES.join(", ")
+                    ));
+                }
+                let section = section
+                    .as_table_like()
+                    .context(format!("[mode.{}] must be a table", name))?;
+                let settings =
+                    Settings::parse(section).context(format!("Invalid [mode.{}] section", name))?;
+                modes.push((name.to_string(), settings));
+            }
+        }
+
+        let mut profiles = Vec::new();
+        if let Some(sections) = doc.get("profile") {
+            let sections = sections
+                .as_table_like()
+                .context("'profile' must be a table of [profile.NAME] sections")?;
+            for (name, section) in sections.iter() {
+                let section = section
+                    .as_table_like()
+                    .context(format!("[profile.{}] must be a table", name))?;
+                let settings = Settings::parse(section)
+                    .context(format!("Invalid [profile.{}] section", name))?;
+                profiles.push((name.to_string(), settings));
+            }
+        }
+
+        let png_compression = match format_section(&doc, "png")? {
+            Some(section) => parse_png_compression(section.get("compression"))?,
+            None => None,
+        };
+        let jxl_quality = match format_section(&doc, "jxl")? {
+            Some(section) => {
+                parse_quality(section.get("quality")).context("Invalid [jxl] section")?
+            }
+            None => None,
+        };
+        let webp_quality = match format_section(&doc, "webp")? {
+            Some(section) => {
+                parse_quality(section.get("quality")).context("Invalid [webp] section")?
+            }
+            None => None,
+        };
+
+        let icc_profiles = match format_section(&doc, "icc")? {
+            Some(section) => parse_icc_profiles(section)?,
+            None => Vec::new(),
+        };
+
+        let (frame, frame_style) = match format_section(&doc, "frame")? {
+            Some(section) => {
+                let (frame, style) = parse_frame(section).context("Invalid [frame] section")?;
+                (frame, Some(style))
+            }
+            None => (None, None),
+        };
+
+        let regions = match format_section(&doc, "regions")? {
+            Some(section) => parse_regions(section)?,
+            None => Vec::new(),
+        };
+
+        let hooks = match format_section(&doc, "hooks")? {
+            Some(section) => parse_hooks(section).context("Invalid [hooks] section")?,
+            None => Hooks::default(),
+        };
+
+        Ok(Config {
+            settings: Settings::parse(doc.as_table())?,
+            png_compression,
+            jxl_quality,
+            webp_quality,
+            icc_profiles,
+            frame,
+            frame_style,
+            hooks,
+            regions,
+            modes,
+            profiles,
+        })
+    }
+}
@@ -0,0 +1,155 @@
+use crate::encode::{self, Format};
+use crate::history;
+use crate::thumbnail;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub struct ConvertOptions {
+    pub format: Format,
+    pub lossy: bool,
+    pub quality: Option<u8>,
+    pub jobs: usize,
+    pub keep: bool,
+    pub file_mode: Option<u32>,
+}
+
+struct Converted {
+    old: PathBuf,
+    new: PathBuf,
+    old_size: u64,
+    new_size: u64,
+}
+
+fn collect_files(folder: &Path, format: Format) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(folder)
+        .context(format!("Failed to read directory '{}'", folder.display()))?
+    {
+        let path = entry?.path();
+        if path.is_file()
+            && !thumbnail::is_thumbnail(&path)
+            && Format::from_path(&path).is_some_and(|f| f != format)
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn convert_file(path: &Path, opts: &ConvertOptions) -> Result<Option<Converted>> {
+    let Some(source_format) = Format::from_path(path) else {
+        return Err(anyhow::anyhow!(
+            "Unsupported image format: '{}'",
+            path.display()
+        ));
+    };
+    let target = path.with_extension(opts.format.extension());
+    if source_format == opts.format {
+        log::debug!(
+            "Skipping {}: already {}",
+            path.display(),
+            opts.format.extension()
+        );
+        return Ok(None);
+    }
+    if target.exists() {
+        return Err(anyhow::anyhow!(
+            "Refusing to overwrite existing file '{}'",
+            target.display()
+        ));
+    }
+
+    let data = std::fs::read(path).context(format!("Failed to read '{}'", path.display()))?;
+    let png = encode::decode(&data, source_format)?;
+    let encoded = encode::encode(&png, opts.format, opts.lossy, opts.quality)?;
+
+    let temp = target.with_extension(format!("{}.tmp", opts.format.extension()));
+    std::fs::write(&temp, &encoded).context(format!("Failed to write '{}'", temp.display()))?;
+    if let Some(mode) = opts.file_mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(mode))?;
+    }
+    std::fs::rename(&temp, &target).context(format!("Failed to write '{}'", target.display()))?;
+    if !opts.keep {
+        std::fs::remove_file(path).context(format!("Failed to remove '{}'", path.display()))?;
+    }
+
+    log::debug!("Converted {} -> {}", path.display(), target.display());
+    Ok(Some(Converted {
+        old: path.to_path_buf(),
+        new: target,
+        old_size: data.len() as u64,
+        new_size: encoded.len() as u64,
+    }))
+}
+
+pub fn run(files: Vec<PathBuf>, folder: &Path, opts: &ConvertOptions) -> Result<()> {
+    let files = if files.is_empty() {
+        collect_files(folder, opts.format)?
+    } else {
+        files
+    };
+    let files: Vec<PathBuf> = files
+        .into_iter()
+        .map(|f| f.canonicalize().unwrap_or(f))
+        .collect();
+    log::debug!(
+        "Converting {} file(s) to {} with {} job(s)",
+        files.len(),
+        opts.format.extension(),
+        opts.jobs
+    );
+
+    let queue = Mutex::new(files.iter());
+    let results = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..opts.jobs.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let Some(path) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let result = convert_file(path, opts);
+                    results.lock().unwrap().push((path, result));
+                }
+            });
+        }
+    });
+
+    let mut converted = Vec::new();
+    let mut failures = 0;
+    for (path, result) in results.into_inner().unwrap() {
+        match result {
+            Ok(Some(c)) => converted.push(c),
+            Ok(None) => {}
+            Err(e) => {
+                log::warn!("Failed to convert '{}': {:#}", path.display(), e);
+                failures += 1;
+            }
+        }
+    }
+
+    if !opts.keep {
+        let renames: Vec<(PathBuf, PathBuf)> = converted
+            .iter()
+            .map(|c| (c.old.clone(), c.new.clone()))
+            .collect();
+        history::replace(&renames)?;
+    }
+
+    let old_size: u64 = converted.iter().map(|c| c.old_size).sum();
+    let new_size: u64 = converted.iter().map(|c| c.new_size).sum();
+    println!(
+        "Converted {} file(s): {} KiB -> {} KiB",
+        converted.len(),
+        old_size / 1024,
+        new_size / 1024
+    );
+
+    if failures > 0 {
+        return Err(anyhow::anyhow!("{} file(s) failed to convert", failures));
+    }
+    Ok(())
+}
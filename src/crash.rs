@@ -0,0 +1,112 @@
+use crate::environment;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// Files being written and helper processes being fed, so a panic halfway
+// through doesn't leave a truncated screenshot or a wl-copy serving half an
+// image behind.
+static PARTIAL_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+static DISARMED: AtomicBool = AtomicBool::new(false);
+
+// Panics exit with the same status Rust uses for them, timeouts with the
+// one timeout(1) uses.
+const EXIT_CODE: i32 = 101;
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+pub fn writing(path: &Path) {
+    if let Ok(mut files) = PARTIAL_FILES.lock() {
+        files.push(path.to_path_buf());
+    }
+}
+
+pub fn written(path: &Path) {
+    if let Ok(mut files) = PARTIAL_FILES.lock() {
+        files.retain(|file| file != path);
+    }
+}
+
+pub fn feeding(pid: u32) {
+    if let Ok(mut children) = CHILDREN.lock() {
+        children.push(pid);
+    }
+}
+
+pub fn fed(pid: u32) {
+    if let Ok(mut children) = CHILDREN.lock() {
+        children.retain(|child| *child != pid);
+    }
+}
+
+fn clean_up() {
+    if let Ok(children) = CHILDREN.try_lock() {
+        for pid in children.iter() {
+            unsafe { libc::kill(*pid as i32, libc::SIGTERM) };
+        }
+    }
+    if let Ok(files) = PARTIAL_FILES.try_lock() {
+        for file in files.iter() {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+}
+
+pub fn install() {
+    std::panic::set_hook(Box::new(|info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        let location = info
+            .location()
+            .map(|location| format!(" at {}:{}", location.file(), location.line()))
+            .unwrap_or_default();
+        // Debug level reaches the last-run log without repeating the message below.
+        log::debug!("Panic: {}{}", message, location);
+        clean_up();
+        eprintln!(
+            "Error: hyprshot-rs hit a bug and stopped: {}{}\n\n\
+             Please report this at {}/issues and attach the file written by \
+             `hyprshot-rs debug-bundle`.",
+            message,
+            location,
+            env!("CARGO_PKG_REPOSITORY")
+        );
+        std::process::exit(EXIT_CODE);
+    }));
+}
+
+// Ends the whole run after `timeout`, wherever it is stuck: a selection left
+// open, a portal that never answers, a helper that never exits. Helpers still
+// running are stopped; Wayland and D-Bus connections close with the process.
+pub fn watchdog(timeout: Duration) {
+    std::thread::spawn(move || {
+        std::thread::sleep(timeout);
+        if DISARMED.load(Ordering::SeqCst) {
+            return;
+        }
+        log::debug!("Timed out after {:?}", timeout);
+        for (pid, comm) in environment::children(std::process::id() as i32) {
+            log::debug!("Stopping {} ({})", comm, pid);
+            unsafe { libc::kill(pid, libc::SIGTERM) };
+        }
+        clean_up();
+        eprintln!(
+            "Error: hyprshot-rs timed out after {}s (--timeout)",
+            timeout.as_secs()
+        );
+        std::process::exit(TIMEOUT_EXIT_CODE);
+    });
+}
+
+// The user's own command or editor may stay open as long as they like; from
+// here on the run is no longer stuck, it's waiting on them.
+pub fn disarm() {
+    if !DISARMED.swap(true, Ordering::SeqCst) {
+        log::debug!("Watchdog disarmed");
+    }
+}
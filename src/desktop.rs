@@ -0,0 +1,380 @@
+use crate::geometry::Region;
+use crate::runner;
+use crate::save::{self, SaveOptions};
+use crate::storage;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Region,
+    Window { active: bool },
+    Output { active: bool },
+    NamedOutput(String),
+}
+
+fn temp_capture_path() -> Result<PathBuf> {
+    storage::temp_file(&format!("capture-{}.png", std::process::id()))
+}
+
+fn read_capture(capture_path: &Path) -> Result<Vec<u8>> {
+    let data = std::fs::read(capture_path).context(format!(
+        "Failed to open screenshot file '{}'",
+        capture_path.display()
+    ));
+    let _ = std::fs::remove_file(capture_path);
+    data
+}
+
+pub fn save_geometry_with_kde(
+    target: &Target,
+    save_fullpath: &Path,
+    opts: &SaveOptions,
+) -> Result<save::Delivery> {
+    let capture_path = temp_capture_path()?;
+
+    let mode_flag = match target {
+        Target::Region => "--region",
+        Target::Window { active: true } => "--activewindow",
+        Target::Window { active: false } => "--windowundercursor",
+        Target::Output { active: true } => "--current",
+        Target::Output { active: false } => "--fullscreen",
+        Target::NamedOutput(_) => {
+            return Err(anyhow::anyhow!(
+                "Selecting an output by name is not supported with Spectacle"
+            ));
+        }
+    };
+    log::debug!(
+        "Saving with spectacle ({}): {}",
+        mode_flag,
+        capture_path.display()
+    );
+
+    let ran = runner::checked(
+        "spectacle",
+        &[
+            "--background",
+            "--nonotify",
+            mode_flag,
+            "--output",
+            &capture_path.to_string_lossy(),
+        ],
+        None,
+    );
+    if let Err(e) = ran {
+        let _ = std::fs::remove_file(&capture_path);
+        return Err(e.context("spectacle failed to capture screenshot"));
+    }
+    if !capture_path.exists() {
+        return Err(anyhow::anyhow!("spectacle failed to capture screenshot"));
+    }
+
+    save::deliver(&read_capture(&capture_path)?, save_fullpath, opts)
+}
+
+fn gnome_shell_screenshot(
+    conn: &zbus::blocking::Connection,
+    target: &Target,
+    capture_path: &Path,
+    opts: &SaveOptions,
+) -> Result<()> {
+    let shell = zbus::blocking::Proxy::new(
+        conn,
+        "org.gnome.Shell.Screenshot",
+        "/org/gnome/Shell/Screenshot",
+        "org.gnome.Shell.Screenshot",
+    )
+    .context("Failed to create org.gnome.Shell.Screenshot proxy")?;
+    let filename = capture_path.to_string_lossy().into_owned();
+
+    let (success, filename_used): (bool, String) = match target {
+        Target::Region => {
+            let (x, y, width, height): (i32, i32, i32, i32) = shell
+                .call("SelectArea", &())
+                .context("Failed to select area with GNOME Shell")?;
+            log::debug!(
+                "GNOME Shell selected area: {}",
+                Region::new(x, y, width, height)
+            );
+            shell
+                .call(
+                    "ScreenshotArea",
+                    &(x, y, width, height, opts.flash, filename.as_str()),
+                )
+                .context("GNOME Shell ScreenshotArea failed")?
+        }
+        Target::Window { active: true } => shell
+            .call(
+                "ScreenshotWindow",
+                &(true, false, opts.flash, filename.as_str()),
+            )
+            .context("GNOME Shell ScreenshotWindow failed")?,
+        Target::Window { active: false } => {
+            return Err(anyhow::anyhow!(
+                "GNOME Shell can only capture the active window"
+            ));
+        }
+        Target::NamedOutput(_) => {
+            return Err(anyhow::anyhow!(
+                "Selecting an output by name is not supported on GNOME"
+            ));
+        }
+        Target::Output { .. } => shell
+            .call("Screenshot", &(false, opts.flash, filename.as_str()))
+            .context("GNOME Shell Screenshot failed")?,
+    };
+
+    if !success {
+        return Err(anyhow::anyhow!("GNOME Shell failed to capture screenshot"));
+    }
+    if Path::new(&filename_used) != capture_path {
+        std::fs::rename(&filename_used, capture_path)
+            .context("Failed to move GNOME Shell screenshot")?;
+    }
+    Ok(())
+}
+
+fn path_from_file_uri(uri: &str) -> Result<PathBuf> {
+    let encoded = uri
+        .strip_prefix("file://")
+        .context(format!("Unsupported screenshot URI '{}'", uri))?;
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex: String = iter.by_ref().take(2).map(char::from).collect();
+            bytes.push(
+                u8::from_str_radix(&hex, 16)
+                    .context(format!("Invalid escape in screenshot URI '{}'", uri))?,
+            );
+        } else {
+            bytes.push(byte);
+        }
+    }
+    Ok(PathBuf::from(
+        String::from_utf8(bytes).context("Screenshot URI is not valid UTF-8")?,
+    ))
+}
+
+pub type PortalResults = std::collections::HashMap<String, zbus::zvariant::OwnedValue>;
+
+pub fn portal_request<B>(
+    conn: &zbus::blocking::Connection,
+    interface: &str,
+    method: &str,
+    token: &str,
+    body: &B,
+) -> Result<PortalResults>
+where
+    B: zbus::export::serde::Serialize + zbus::zvariant::DynamicType,
+{
+    use zbus::zvariant::OwnedObjectPath;
+
+    let sender = conn
+        .unique_name()
+        .context("D-Bus connection has no unique name")?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let request_path = format!(
+        "/org/freedesktop/portal/desktop/request/{}/{}",
+        sender, token
+    );
+
+    let request = zbus::blocking::Proxy::new(
+        conn,
+        "org.freedesktop.portal.Desktop",
+        request_path.as_str(),
+        "org.freedesktop.portal.Request",
+    )
+    .context("Failed to create portal request proxy")?;
+    let mut responses = request
+        .receive_signal("Response")
+        .context("Failed to subscribe to portal response")?;
+
+    let portal = zbus::blocking::Proxy::new(
+        conn,
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        interface,
+    )
+    .context(format!("Failed to create {} proxy", interface))?;
+    let _: OwnedObjectPath = portal
+        .call(method, body)
+        .context(format!("{} portal call failed", method))?;
+
+    let message = responses
+        .next()
+        .context(format!("{} portal closed without a response", method))?;
+    let (response, results): (u32, PortalResults) = message
+        .body()
+        .deserialize()
+        .context(format!("Invalid {} portal response", method))?;
+    if response != 0 {
+        return Err(anyhow::anyhow!("{} portal request was cancelled", method));
+    }
+    Ok(results)
+}
+
+fn portal_screenshot(
+    conn: &zbus::blocking::Connection,
+    target: &Target,
+    capture_path: &Path,
+) -> Result<()> {
+    use std::collections::HashMap;
+    use zbus::zvariant::Value;
+
+    let token = format!("hyprshot_rs_{}", std::process::id());
+    let interactive = !matches!(target, Target::Output { .. });
+    let mut options: HashMap<&str, Value> = HashMap::new();
+    options.insert("handle_token", Value::from(token.as_str()));
+    options.insert("interactive", Value::from(interactive));
+    let results = portal_request(
+        conn,
+        "org.freedesktop.portal.Screenshot",
+        "Screenshot",
+        &token,
+        &("", options),
+    )?;
+    let uri: String = results
+        .get("uri")
+        .cloned()
+        .context("Screenshot portal response has no URI")?
+        .try_into()
+        .context("Screenshot portal URI is not a string")?;
+    let portal_path = path_from_file_uri(&uri)?;
+    log::debug!("Screenshot portal saved: {}", portal_path.display());
+
+    if std::fs::rename(&portal_path, capture_path).is_err() {
+        std::fs::copy(&portal_path, capture_path)
+            .context("Failed to copy screenshot from portal")?;
+        let _ = std::fs::remove_file(&portal_path);
+    }
+    Ok(())
+}
+
+pub fn save_geometry_with_gnome(
+    target: &Target,
+    save_fullpath: &Path,
+    opts: &SaveOptions,
+) -> Result<save::Delivery> {
+    let capture_path = temp_capture_path()?;
+    let conn =
+        zbus::blocking::Connection::session().context("Failed to connect to session D-Bus")?;
+
+    log::debug!(
+        "Saving with GNOME Shell ({:?}): {}",
+        target,
+        capture_path.display()
+    );
+
+    if let Err(e) = gnome_shell_screenshot(&conn, target, &capture_path, opts) {
+        log::debug!(
+            "GNOME Shell screenshot failed, falling back to portal: {:#}",
+            e
+        );
+        portal_screenshot(&conn, target, &capture_path)?;
+    }
+
+    save::deliver(&read_capture(&capture_path)?, save_fullpath, opts)
+}
+
+fn x11_command_output(program: &str, args: &[&str]) -> Result<String> {
+    let output = runner::checked(program, args, None)?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+struct X11Monitor {
+    name: String,
+    region: Region,
+}
+
+impl X11Monitor {
+    fn maim_geometry(&self) -> String {
+        let Region {
+            x,
+            y,
+            width,
+            height,
+        } = self.region;
+        format!("--geometry={}x{}+{}+{}", width, height, x, y)
+    }
+}
+
+fn x11_monitors() -> Result<Vec<X11Monitor>> {
+    let listing = x11_command_output("xrandr", &["--listactivemonitors"])?;
+    Ok(listing
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let geometry = fields.nth(2)?;
+            let name = fields.next()?.to_string();
+            let (size, offset) = geometry.split_once('+')?;
+            let (width, height) = size.split_once('x')?;
+            let (x, y) = offset.split_once('+')?;
+            Some(X11Monitor {
+                name,
+                region: Region::new(
+                    x.parse().ok()?,
+                    y.parse().ok()?,
+                    width.split('/').next()?.parse().ok()?,
+                    height.split('/').next()?.parse().ok()?,
+                ),
+            })
+        })
+        .collect())
+}
+
+fn x11_monitor_under_cursor() -> Result<X11Monitor> {
+    let location = x11_command_output("xdotool", &["getmouselocation", "--shell"])?;
+    let coordinate = |key: &str| -> Option<i32> {
+        location
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|v| v.parse().ok())
+    };
+    let x = coordinate("X=").context("xdotool returned no X coordinate")?;
+    let y = coordinate("Y=").context("xdotool returned no Y coordinate")?;
+
+    x11_monitors()?
+        .into_iter()
+        .find(|m| m.region.contains(x, y))
+        .context("No monitor found under the cursor")
+}
+
+pub fn save_geometry_with_x11(
+    target: &Target,
+    save_fullpath: &Path,
+    opts: &SaveOptions,
+) -> Result<save::Delivery> {
+    let mut args: Vec<String> = vec!["--hidecursor".to_string(), "--format=png".to_string()];
+    match target {
+        Target::Region | Target::Window { active: false } => args.push("--select".to_string()),
+        Target::Window { active: true } => {
+            args.push(format!(
+                "--window={}",
+                x11_command_output("xdotool", &["getactivewindow"])?
+            ));
+        }
+        Target::Output { active: false } => {}
+        Target::Output { active: true } => args.push(x11_monitor_under_cursor()?.maim_geometry()),
+        Target::NamedOutput(name) => {
+            let monitor = x11_monitors()?
+                .into_iter()
+                .find(|m| &m.name == name)
+                .context(format!("Monitor '{}' not found", name))?;
+            args.push(monitor.maim_geometry());
+        }
+    }
+    log::debug!("Saving with maim: {}", args.join(" "));
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output =
+        runner::checked("maim", &args, None).context("maim failed to capture screenshot")?;
+    if output.stdout.is_empty() {
+        return Err(anyhow::anyhow!("maim failed to capture screenshot"));
+    }
+
+    save::deliver(&output.stdout, save_fullpath, opts)
+}
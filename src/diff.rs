@@ -0,0 +1,181 @@
+use crate::raster::{self, Image};
+use anyhow::Result;
+use serde_json::json;
+use std::path::Path;
+
+const CELL: usize = 8;
+
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub changed_pixels: usize,
+}
+
+fn change(before: &Image, after: &Image, x: usize, y: usize) -> u8 {
+    before
+        .pixel(x, y)
+        .iter()
+        .zip(after.pixel(x, y).iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or(0)
+}
+
+fn heat_color(intensity: u8) -> [u8; 3] {
+    let v = intensity as usize * 4;
+    match v {
+        0..=255 => [0, v as u8, 255],
+        256..=511 => [0, 255, (511 - v) as u8],
+        512..=767 => [(v - 512) as u8, 255, 0],
+        _ => [255, (1020 - v.min(1020)) as u8, 0],
+    }
+}
+
+pub fn heatmap(before: &Image, after: &Image, threshold: u8) -> Image {
+    let mut pixels = Vec::with_capacity(after.pixels.len());
+    for y in 0..after.height {
+        for x in 0..after.width {
+            let delta = change(before, after, x, y);
+            if delta > threshold {
+                pixels.extend_from_slice(&heat_color(delta));
+            } else {
+                let [r, g, b] = after.pixel(x, y);
+                let gray = ((r as u32 * 30 + g as u32 * 59 + b as u32 * 11) / 100 / 3) as u8;
+                pixels.extend_from_slice(&[gray, gray, gray]);
+            }
+        }
+    }
+    Image {
+        width: after.width,
+        height: after.height,
+        pixels,
+    }
+}
+
+pub fn changed_regions(before: &Image, after: &Image, threshold: u8) -> Vec<Region> {
+    let cols = after.width.div_ceil(CELL);
+    let rows = after.height.div_ceil(CELL);
+    let mut counts = vec![0usize; cols * rows];
+    let mut bounds = vec![(usize::MAX, usize::MAX, 0, 0); cols * rows];
+    for y in 0..after.height {
+        for x in 0..after.width {
+            if change(before, after, x, y) > threshold {
+                let cell = (y / CELL) * cols + x / CELL;
+                counts[cell] += 1;
+                let b = &mut bounds[cell];
+                *b = (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y));
+            }
+        }
+    }
+
+    let mut visited = vec![false; cols * rows];
+    let mut regions = Vec::new();
+    for start in 0..cols * rows {
+        if visited[start] || counts[start] == 0 {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![start];
+        let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+        let mut changed_pixels = 0;
+        while let Some(cell) = stack.pop() {
+            let (cx, cy) = (cell % cols, cell / cols);
+            let (x0, y0, x1, y1) = bounds[cell];
+            left = left.min(x0);
+            top = top.min(y0);
+            right = right.max(x1);
+            bottom = bottom.max(y1);
+            changed_pixels += counts[cell];
+
+            let mut neighbors = Vec::with_capacity(4);
+            if cx > 0 {
+                neighbors.push(cell - 1);
+            }
+            if cx + 1 < cols {
+                neighbors.push(cell + 1);
+            }
+            if cy > 0 {
+                neighbors.push(cell - cols);
+            }
+            if cy + 1 < rows {
+                neighbors.push(cell + cols);
+            }
+            for next in neighbors {
+                if !visited[next] && counts[next] > 0 {
+                    visited[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+
+        regions.push(Region {
+            x: left,
+            y: top,
+            width: right - left + 1,
+            height: bottom - top + 1,
+            changed_pixels,
+        });
+    }
+    regions
+}
+
+pub fn run(
+    before: &Path,
+    after: &Path,
+    heatmap_path: Option<&Path>,
+    threshold: u8,
+    json: bool,
+) -> Result<()> {
+    let before_image = raster::load(before)?;
+    let after_image = raster::load(after)?;
+    if before_image.width != after_image.width || before_image.height != after_image.height {
+        return Err(anyhow::anyhow!(
+            "Images have different sizes: {}x{} and {}x{}",
+            before_image.width,
+            before_image.height,
+            after_image.width,
+            after_image.height
+        ));
+    }
+
+    let regions = changed_regions(&before_image, &after_image, threshold);
+    let changed_pixels: usize = regions.iter().map(|r| r.changed_pixels).sum();
+    log::debug!(
+        "Diff found {} changed pixel(s) in {} region(s)",
+        changed_pixels,
+        regions.len()
+    );
+
+    if let Some(path) = heatmap_path {
+        raster::save(&heatmap(&before_image, &after_image, threshold), path)?;
+    }
+
+    if json {
+        let report = json!({
+            "width": after_image.width,
+            "height": after_image.height,
+            "changed_pixels": changed_pixels,
+            "regions": regions
+                .iter()
+                .map(|r| json!({
+                    "x": r.x,
+                    "y": r.y,
+                    "width": r.width,
+                    "height": r.height,
+                    "changed_pixels": r.changed_pixels,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", report);
+    } else {
+        for r in &regions {
+            println!(
+                "{},{} {}x{} ({} changed pixels)",
+                r.x, r.y, r.width, r.height, r.changed_pixels
+            );
+        }
+    }
+    Ok(())
+}
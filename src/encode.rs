@@ -0,0 +1,246 @@
+use crate::runner;
+use crate::storage;
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Png,
+    Webp,
+    Jxl,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Webp => "webp",
+            Format::Jxl => "jxl",
+        }
+    }
+
+    // The program that encodes the format, checked for by `capabilities`.
+    pub fn encoder(self) -> Option<&'static str> {
+        match self {
+            Format::Png => None,
+            Format::Webp => Some("cwebp"),
+            Format::Jxl => Some("cjxl"),
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Format::Png),
+            "webp" => Some(Format::Webp),
+            "jxl" => Some(Format::Jxl),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best,
+}
+
+impl PngCompression {
+    #[cfg(feature = "grim")]
+    pub fn grim_level(self) -> u8 {
+        match self {
+            PngCompression::Fast => 1,
+            PngCompression::Default => 6,
+            PngCompression::Best => 9,
+        }
+    }
+
+    #[cfg(feature = "native")]
+    pub fn native(self) -> image::codecs::png::CompressionType {
+        use image::codecs::png::CompressionType;
+
+        match self {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Best => CompressionType::Best,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum HdrOutput {
+    Png16,
+    Avif,
+}
+
+impl HdrOutput {
+    pub fn extension(self) -> &'static str {
+        match self {
+            HdrOutput::Png16 => "png",
+            HdrOutput::Avif => "avif",
+        }
+    }
+}
+
+pub fn mime_for_path(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        Some("bmp") => "image/bmp",
+        Some("jxl") => "image/jxl",
+        Some("avif") => "image/avif",
+        _ => "image/png",
+    }
+}
+
+fn temp_paths(input_extension: &str, output_extension: &str) -> Result<(PathBuf, PathBuf)> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let id = format!(
+        "encode-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    Ok((
+        storage::temp_file(&format!("{}-in.{}", id, input_extension))?,
+        storage::temp_file(&format!("{}-out.{}", id, output_extension))?,
+    ))
+}
+
+// Screenshots stay lossless unless --lossy or a --quality below 100 is asked for.
+fn encode_webp(png: &[u8], lossy: bool, quality: Option<u8>) -> Result<Vec<u8>> {
+    let (input, output) = temp_paths("png", "webp")?;
+    std::fs::write(&input, png).context("Failed to write temporary PNG for cwebp")?;
+
+    let setting = match quality {
+        Some(100) => vec!["-lossless".to_string()],
+        Some(quality) => vec!["-q".to_string(), quality.to_string()],
+        None if lossy => vec!["-q".to_string(), "80".to_string()],
+        None => vec!["-lossless".to_string()],
+    };
+    log::debug!("Encoding WebP with cwebp ({})", setting.join(" "));
+    let (input_arg, output_arg) = (input.to_string_lossy(), output.to_string_lossy());
+    let mut args: Vec<&str> = setting.iter().map(String::as_str).collect();
+    args.extend(["-quiet", &input_arg, "-o", &output_arg]);
+    let ran = runner::checked("cwebp", &args, None);
+    let _ = std::fs::remove_file(&input);
+    if let Err(e) = ran {
+        let _ = std::fs::remove_file(&output);
+        return Err(e.context("cwebp failed to encode screenshot"));
+    }
+
+    let encoded = std::fs::read(&output).context("Failed to read cwebp output");
+    let _ = std::fs::remove_file(&output);
+    encoded
+}
+
+fn decode_webp(webp: &[u8]) -> Result<Vec<u8>> {
+    let (input, output) = temp_paths("webp", "png")?;
+    std::fs::write(&input, webp).context("Failed to write temporary WebP for dwebp")?;
+
+    log::debug!("Decoding WebP with dwebp");
+    let ran = runner::checked(
+        "dwebp",
+        &[
+            &input.to_string_lossy(),
+            "-quiet",
+            "-o",
+            &output.to_string_lossy(),
+        ],
+        None,
+    );
+    let _ = std::fs::remove_file(&input);
+    if let Err(e) = ran {
+        let _ = std::fs::remove_file(&output);
+        return Err(e.context("dwebp failed to decode image"));
+    }
+
+    let decoded = std::fs::read(&output).context("Failed to read dwebp output");
+    let _ = std::fs::remove_file(&output);
+    decoded
+}
+
+fn encode_jxl(png: &[u8], lossy: bool, quality: Option<u8>) -> Result<Vec<u8>> {
+    let (input, output) = temp_paths("png", "jxl")?;
+    std::fs::write(&input, png).context("Failed to write temporary PNG for cjxl")?;
+
+    let setting = match quality {
+        Some(quality) => ("--quality", quality.to_string()),
+        None => ("--distance", if lossy { "1.0" } else { "0" }.to_string()),
+    };
+    log::debug!("Encoding JPEG XL with cjxl ({} {})", setting.0, setting.1);
+    let ran = runner::checked(
+        "cjxl",
+        &[
+            &input.to_string_lossy(),
+            &output.to_string_lossy(),
+            setting.0,
+            &setting.1,
+            "--quiet",
+        ],
+        None,
+    );
+    let _ = std::fs::remove_file(&input);
+    if let Err(e) = ran {
+        let _ = std::fs::remove_file(&output);
+        return Err(e.context("cjxl failed to encode screenshot"));
+    }
+
+    let encoded = std::fs::read(&output).context("Failed to read cjxl output");
+    let _ = std::fs::remove_file(&output);
+    encoded
+}
+
+fn decode_jxl(jxl: &[u8]) -> Result<Vec<u8>> {
+    let (input, output) = temp_paths("jxl", "png")?;
+    std::fs::write(&input, jxl).context("Failed to write temporary JPEG XL for djxl")?;
+
+    log::debug!("Decoding JPEG XL with djxl");
+    let ran = runner::checked(
+        "djxl",
+        &[
+            &input.to_string_lossy(),
+            &output.to_string_lossy(),
+            "--quiet",
+        ],
+        None,
+    );
+    let _ = std::fs::remove_file(&input);
+    if let Err(e) = ran {
+        let _ = std::fs::remove_file(&output);
+        return Err(e.context("djxl failed to decode image"));
+    }
+
+    let decoded = std::fs::read(&output).context("Failed to read djxl output");
+    let _ = std::fs::remove_file(&output);
+    decoded
+}
+
+pub fn decode(data: &[u8], format: Format) -> Result<Cow<'_, [u8]>> {
+    match format {
+        Format::Png => Ok(Cow::Borrowed(data)),
+        Format::Webp => Ok(Cow::Owned(decode_webp(data)?)),
+        Format::Jxl => Ok(Cow::Owned(decode_jxl(data)?)),
+    }
+}
+
+pub fn encode<'a>(
+    png: &'a [u8],
+    format: Format,
+    lossy: bool,
+    quality: Option<u8>,
+) -> Result<Cow<'a, [u8]>> {
+    match format {
+        Format::Png => Ok(Cow::Borrowed(png)),
+        Format::Webp => Ok(Cow::Owned(encode_webp(png, lossy, quality)?)),
+        Format::Jxl => Ok(Cow::Owned(encode_jxl(png, lossy, quality)?)),
+    }
+}
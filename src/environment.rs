@@ -0,0 +1,223 @@
+use crate::runner;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DesktopEnvironment {
+    Hyprland,
+    Sway,
+    Kde,
+    Gnome,
+    Cosmic,
+    Niri,
+    X11,
+    Unknown,
+}
+
+pub fn is_x11_session() -> bool {
+    let set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+    !set("WAYLAND_DISPLAY") && set("DISPLAY")
+}
+
+impl DesktopEnvironment {
+    pub fn detect() -> DesktopEnvironment {
+        let detected = is_x11_session()
+            .then_some(DesktopEnvironment::X11)
+            .or_else(Self::from_compositor_env)
+            .or_else(Self::from_current_desktop)
+            .or_else(Self::from_processes)
+            .unwrap_or(DesktopEnvironment::Unknown);
+        log::debug!("Detected desktop environment: {:?}", detected);
+        detected
+    }
+
+    fn from_compositor_env() -> Option<DesktopEnvironment> {
+        let set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+        if set("HYPRLAND_INSTANCE_SIGNATURE") {
+            Some(DesktopEnvironment::Hyprland)
+        } else if set("SWAYSOCK") {
+            Some(DesktopEnvironment::Sway)
+        } else if set("NIRI_SOCKET") {
+            Some(DesktopEnvironment::Niri)
+        } else {
+            None
+        }
+    }
+
+    fn from_current_desktop() -> Option<DesktopEnvironment> {
+        let desktops = std::env::var("XDG_CURRENT_DESKTOP")
+            .or_else(|_| std::env::var("XDG_SESSION_DESKTOP"))
+            .ok()?;
+        desktops.split(':').find_map(Self::from_name)
+    }
+
+    fn from_name(name: &str) -> Option<DesktopEnvironment> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "hyprland" => Some(DesktopEnvironment::Hyprland),
+            "sway" => Some(DesktopEnvironment::Sway),
+            "kde" | "plasma" | "kwin_wayland" => Some(DesktopEnvironment::Kde),
+            "gnome" | "gnome-shell" | "ubuntu" => Some(DesktopEnvironment::Gnome),
+            "cosmic" | "cosmic-comp" => Some(DesktopEnvironment::Cosmic),
+            "niri" => Some(DesktopEnvironment::Niri),
+            _ => None,
+        }
+    }
+
+    fn from_processes() -> Option<DesktopEnvironment> {
+        let entries = std::fs::read_dir("/proc").ok()?;
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.bytes().all(|b| b.is_ascii_digit()))
+            })
+            .filter_map(|entry| std::fs::read_to_string(entry.path().join("comm")).ok())
+            .find_map(|comm| Self::from_name(comm.trim()))
+    }
+
+    pub fn has_desktop_backend(self) -> bool {
+        matches!(
+            self,
+            DesktopEnvironment::Kde | DesktopEnvironment::Gnome | DesktopEnvironment::X11
+        )
+    }
+
+    pub fn uses_hyprctl(self) -> bool {
+        matches!(
+            self,
+            DesktopEnvironment::Hyprland | DesktopEnvironment::Unknown
+        )
+    }
+}
+
+// Helper programs hyprshot-rs runs for one feature or another.
+pub const TOOLS: &[&str] = &[
+    "grim",
+    "slurp",
+    "hyprctl",
+    "hyprpicker",
+    "wl-copy",
+    "magick",
+    "oxipng",
+    "cwebp",
+    "dwebp",
+    "cjxl",
+    "djxl",
+    "avifenc",
+    "wf-recorder",
+    "ffmpeg",
+    "pactl",
+    "openssl",
+    "curl",
+    "ssh",
+    "secret-tool",
+    "spd-say",
+    "xdg-open",
+    "spectacle",
+    "maim",
+    "xclip",
+    "xdotool",
+    "rofi",
+    "wofi",
+    "fuzzel",
+    "bemenu",
+    "dmenu",
+    "fzf",
+];
+
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn features() -> Vec<&'static str> {
+    [
+        ("grim", cfg!(feature = "grim")),
+        ("native", cfg!(feature = "native")),
+        ("dev", cfg!(feature = "dev")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+// Globals the compositor advertises, as listed by wayland-info.
+pub fn wayland_protocols() -> Option<Vec<String>> {
+    let output = runner::output("wayland-info", &[], None).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mut protocols: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("interface: '"))
+        .filter_map(|rest| rest.split_once('\''))
+        .map(|(name, _)| name.to_string())
+        .collect();
+    protocols.sort();
+    protocols.dedup();
+    Some(protocols)
+}
+
+// ("comm", parent pid) from /proc/PID/stat; comm is in parentheses and may
+// itself contain spaces or parentheses.
+fn parse_stat(stat: &str) -> Option<(&str, i32)> {
+    let (start, end) = (stat.find('(')?, stat.rfind(')')?);
+    let ppid = stat
+        .get(end + 1..)?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()?;
+    Some((stat.get(start + 1..end)?, ppid))
+}
+
+// Direct child processes of `parent`, with their command names.
+pub fn children(parent: i32) -> Vec<(i32, String)> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<i32>().ok()?;
+            let stat = std::fs::read_to_string(entry.path().join("stat")).ok()?;
+            let (comm, ppid) = parse_stat(&stat)?;
+            (ppid == parent).then(|| (pid, comm.to_string()))
+        })
+        .collect()
+}
+
+// Whether `pid` is another hyprshot-rs; a pid left in a file by a killed run
+// may since have been handed to an unrelated process.
+pub fn is_hyprshot(pid: i32) -> bool {
+    let comm = |pid: &str| std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok();
+    match (comm(&pid.to_string()), comm("self")) {
+        (Some(theirs), Some(ours)) => theirs == ours,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stat_lines_give_the_command_and_parent() {
+        assert_eq!(
+            parse_stat("4242 (slurp) S 4200 4242 4200 0 -1"),
+            Some(("slurp", 4200))
+        );
+        assert_eq!(parse_stat("7 (a (b) c) R 1 7 7"), Some(("a (b) c", 1)));
+        assert_eq!(parse_stat("garbage"), None);
+    }
+
+    #[test]
+    fn reused_pids_are_not_mistaken_for_hyprshot() {
+        assert!(is_hyprshot(std::process::id() as i32));
+        assert!(!is_hyprshot(1));
+        assert!(!is_hyprshot(i32::MAX));
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use std::io::{ErrorKind, Read};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+pub struct Event {
+    pub name: String,
+    pub data: String,
+}
+
+// Events after which outputs (and their scale or layout) may have changed.
+const OUTPUT_EVENTS: [&str; 5] = [
+    "monitoradded",
+    "monitoraddedv2",
+    "monitorremoved",
+    "monitorremovedv2",
+    "configreloaded",
+];
+
+impl Event {
+    pub fn changes_outputs(&self) -> bool {
+        OUTPUT_EVENTS.contains(&self.name.as_str())
+    }
+}
+
+pub struct EventStream {
+    socket: UnixStream,
+    buffer: Vec<u8>,
+}
+
+fn socket_path() -> Result<PathBuf> {
+    let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")
+        .context("HYPRLAND_INSTANCE_SIGNATURE is not set; is Hyprland running?")?;
+    let candidates = [
+        dirs::runtime_dir().map(|dir| dir.join("hypr").join(&signature)),
+        Some(PathBuf::from("/tmp/hypr").join(&signature)),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(".socket2.sock"))
+        .find(|path| path.exists())
+        .context("Could not find the Hyprland event socket (.socket2.sock)")
+}
+
+impl EventStream {
+    pub fn connect() -> Result<EventStream> {
+        let path = socket_path()?;
+        let socket = UnixStream::connect(&path).context(format!(
+            "Failed to connect to Hyprland event socket '{}'",
+            path.display()
+        ))?;
+        socket
+            .set_nonblocking(true)
+            .context("Failed to configure Hyprland event socket")?;
+        log::debug!("Listening for Hyprland events on {}", path.display());
+        Ok(EventStream {
+            socket,
+            buffer: Vec::new(),
+        })
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+
+    pub fn pending(&mut self) -> Result<Vec<Event>> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            match self.socket.read(&mut chunk) {
+                Ok(0) => return Err(anyhow::anyhow!("Hyprland closed the event socket")),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e).context("Failed to read Hyprland events"),
+            }
+        }
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line[..end]);
+            if let Some((name, data)) = line.split_once(">>") {
+                events.push(Event {
+                    name: name.to_string(),
+                    data: data.to_string(),
+                });
+            }
+        }
+        Ok(events)
+    }
+}
@@ -0,0 +1,44 @@
+use crate::timing::Stage;
+use anyhow::Result;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Point {
+    Selection,
+    Capture,
+    Encode,
+    Save,
+    Upload,
+    Clipboard,
+}
+
+impl From<Stage> for Point {
+    fn from(stage: Stage) -> Point {
+        match stage {
+            Stage::Capture => Point::Capture,
+            Stage::Encode => Point::Encode,
+            Stage::Save => Point::Save,
+            Stage::Clipboard => Point::Clipboard,
+        }
+    }
+}
+
+// Per thread, like the command runner, so tests can inject their own failures.
+#[cfg(any(feature = "dev", test))]
+thread_local! {
+    static INJECTED: std::cell::RefCell<Vec<Point>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+#[cfg(any(feature = "dev", test))]
+pub fn inject(points: Vec<Point>) {
+    INJECTED.with(|injected| *injected.borrow_mut() = points);
+}
+
+pub fn check(
+    #[cfg_attr(not(any(feature = "dev", test)), allow(unused_variables))] point: Point,
+) -> Result<()> {
+    #[cfg(any(feature = "dev", test))]
+    if INJECTED.with(|injected| injected.borrow().contains(&point)) {
+        return Err(anyhow::anyhow!("Injected failure at {:?} stage", point));
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use crate::runner;
+use crate::storage;
+use anyhow::{Context, Result};
+use std::thread::sleep;
+use std::time::Duration;
+
+pub const DEFAULT_COLOR: &str = "#ffffff";
+const DURATION: Duration = Duration::from_millis(120);
+const OPTION: &str = "decoration:screen_shader";
+// What hyprctl reports for (and accepts to reset) a string option that is unset.
+const EMPTY: &str = "[[EMPTY]]";
+
+pub fn parse_color(color: &str) -> Result<[u8; 3]> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+        _ => Err(anyhow::anyhow!(
+            "Invalid flash color '{}' (expected #rrggbb)",
+            color
+        )),
+    }
+}
+
+fn shader(color: [u8; 3]) -> String {
+    let [r, g, b] = color.map(|c| c as f32 / 255.0);
+    format!(
+        "precision mediump float;\n\
+         varying vec2 v_texcoord;\n\
+         uniform sampler2D tex;\n\
+         void main() {{\n    \
+             gl_FragColor = mix(texture2D(tex, v_texcoord), vec4({:.3}, {:.3}, {:.3}, 1.0), 0.85);\n\
+         }}\n",
+        r, g, b
+    )
+}
+
+fn screen_shader() -> Result<String> {
+    let output = runner::checked("hyprctl", &["getoption", OPTION, "-j"], None)
+        .context(format!("hyprctl failed to read {}", OPTION))?;
+    let option: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .context(format!("Failed to parse hyprctl getoption {}", OPTION))?;
+    Ok(option["str"].as_str().unwrap_or(EMPTY).to_string())
+}
+
+fn set_screen_shader(path: &str) -> Result<()> {
+    runner::checked("hyprctl", &["keyword", OPTION, path], None)
+        .context(format!("hyprctl failed to set {}", OPTION))?;
+    Ok(())
+}
+
+// Hyprland has no overlay of its own to borrow, so the flash swaps in a screen
+// shader that tints every output and then puts the user's shader back.
+pub fn show(color: &str) -> Result<()> {
+    let shader_path = storage::runtime_file("flash.frag")?;
+    std::fs::write(&shader_path, shader(parse_color(color)?))
+        .context("Failed to write the flash shader")?;
+    let previous = screen_shader()?;
+    log::debug!("Flashing {} (screen shader was {})", color, previous);
+
+    set_screen_shader(&shader_path.to_string_lossy())?;
+    sleep(DURATION);
+    let restored = set_screen_shader(&previous);
+    let _ = std::fs::remove_file(&shader_path);
+    restored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::Fake;
+
+    #[test]
+    fn show_restores_the_previous_shader() {
+        let shader = storage::runtime_file("flash.frag").unwrap();
+        let flash = format!("hyprctl keyword {} {}", OPTION, shader.display());
+        let restore = "hyprctl keyword decoration:screen_shader ~/.config/hypr/night.frag";
+        let fake = Fake::default()
+            .reply(
+                "hyprctl getoption decoration:screen_shader -j",
+                r#"{"option": "decoration:screen_shader", "str": "~/.config/hypr/night.frag"}"#,
+            )
+            .reply(&flash, "ok")
+            .reply(restore, "ok")
+            .install();
+        show("#ff8000").unwrap();
+        let calls: Vec<String> = fake.calls.borrow().iter().map(|(c, _)| c.clone()).collect();
+        assert_eq!(calls[1..], [flash, restore.to_string()]);
+        assert!(!shader.exists());
+
+        assert_eq!(parse_color("ff8000").unwrap(), [255, 128, 0]);
+        assert!(parse_color("#fff").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use crate::capture;
+use crate::post;
+use crate::utils;
+use anyhow::{Context, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Frame {
+    Macos,
+    None,
+    Custom,
+}
+
+#[derive(Clone, Debug)]
+pub struct FrameStyle {
+    pub background: String,
+    pub foreground: String,
+    pub height: u32,
+    pub buttons: Vec<String>,
+    pub font: Option<String>,
+}
+
+impl FrameStyle {
+    pub fn macos() -> FrameStyle {
+        FrameStyle {
+            background: "#2b2b2e".to_string(),
+            foreground: "#d8d8d8".to_string(),
+            height: 28,
+            buttons: vec![
+                "#ff5f57".to_string(),
+                "#febc2e".to_string(),
+                "#28c840".to_string(),
+            ],
+            font: None,
+        }
+    }
+}
+
+fn png_width(png: &[u8]) -> Result<u32> {
+    png.get(16..20)
+        .filter(|_| png.get(12..16) == Some(b"IHDR"))
+        .map(|width| u32::from_be_bytes([width[0], width[1], width[2], width[3]]))
+        .context("Window chrome can only be drawn on PNG images")
+}
+
+pub fn apply(png: &[u8], geometry: &str, style: &FrameStyle) -> Result<Vec<u8>> {
+    let title = capture::window_info(geometry)?
+        .map(|window| window.title)
+        .unwrap_or_default();
+    log::debug!("Framing {} ({:?})", geometry, title);
+    draw(png, utils::output_scale(geometry)?, &title, style)
+}
+
+// The title bar for an image that wasn't captured from the screen, such as
+// a file given to `process`.
+pub fn draw(png: &[u8], scale: f64, title: &str, style: &FrameStyle) -> Result<Vec<u8>> {
+    let width = png_width(png)?;
+    let px = |v: f64| (v * scale).round() as u32;
+    let height = px(style.height as f64);
+    log::debug!("Drawing {}px title bar", height);
+
+    let unit = style.height as f64 / 28.0;
+    let mut args = vec![
+        "(".to_string(),
+        "-size".to_string(),
+        format!("{}x{}", width, height),
+        format!("xc:{}", style.background),
+    ];
+    for (i, color) in style.buttons.iter().enumerate() {
+        let x = px((20.0 + 20.0 * i as f64) * unit);
+        args.extend([
+            "-fill".to_string(),
+            color.clone(),
+            "-draw".to_string(),
+            format!(
+                "circle {},{} {},{}",
+                x,
+                height / 2,
+                x + px(6.0 * unit),
+                height / 2
+            ),
+        ]);
+    }
+    if let Some(font) = &style.font {
+        args.extend(["-font".to_string(), font.clone()]);
+    }
+    args.extend([
+        "-fill".to_string(),
+        style.foreground.clone(),
+        "-pointsize".to_string(),
+        px(13.0 * unit).to_string(),
+        "-gravity".to_string(),
+        "center".to_string(),
+        "-annotate".to_string(),
+        "+0+0".to_string(),
+        post::escape_text(title),
+        ")".to_string(),
+    ]);
+
+    post::magick(png, &args, &["-append".to_string()], "--frame")
+}
@@ -0,0 +1,384 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+// A rectangle in the compositor's logical layout, written "x,y wxh" like slurp does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Region {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn parse(geometry: &str) -> Result<Region> {
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid geometry format: expected 'x,y wxh', got '{}'",
+                geometry
+            )
+        };
+        let (position, size) = geometry.split_once(' ').ok_or_else(invalid)?;
+        let (x, y) = position.split_once(',').ok_or_else(invalid)?;
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        if y.contains(',') || height.contains('x') {
+            return Err(invalid());
+        }
+
+        // Origins are negative on monitors left of or above the primary one; sizes never are.
+        let region = Region {
+            x: x.parse()
+                .context(format!("Failed to parse x coordinate from '{}'", x))?,
+            y: y.parse()
+                .context(format!("Failed to parse y coordinate from '{}'", y))?,
+            width: width
+                .parse()
+                .context(format!("Failed to parse width from '{}'", width))?,
+            height: height
+                .parse()
+                .context(format!("Failed to parse height from '{}'", height))?,
+        };
+        if region.width < 0 || region.height < 0 {
+            return Err(anyhow::anyhow!(
+                "Invalid geometry size: {}x{} is negative",
+                region.width,
+                region.height
+            ));
+        }
+        Ok(region)
+    }
+
+    // hyprctl reports the monitor mode in physical pixels, before scale and rotation.
+    pub fn of_monitor(monitor: &Value) -> Region {
+        let scale = monitor["scale"].as_f64().unwrap_or(1.0);
+        let width = (monitor["width"].as_i64().unwrap_or(0) as f64 / scale).round() as i32;
+        let height = (monitor["height"].as_i64().unwrap_or(0) as f64 / scale).round() as i32;
+        let (width, height) = if is_rotated(monitor["transform"].as_i64().unwrap_or(0)) {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        Region {
+            x: monitor["x"].as_i64().unwrap_or(0) as i32,
+            y: monitor["y"].as_i64().unwrap_or(0) as i32,
+            width,
+            height,
+        }
+    }
+
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    pub fn intersect(&self, other: &Region) -> Option<Region> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let region = Region {
+            x,
+            y,
+            width: self.right().min(other.right()) - x,
+            height: self.bottom().min(other.bottom()) - y,
+        };
+        (!region.is_empty()).then_some(region)
+    }
+
+    pub fn union(&self, other: &Region) -> Region {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Region {
+            x,
+            y,
+            width: self.right().max(other.right()) - x,
+            height: self.bottom().max(other.bottom()) - y,
+        }
+    }
+
+    pub fn intersects(&self, other: &Region) -> bool {
+        self.intersect(other).is_some()
+    }
+
+    // Crops the region to bounds, e.g. a window hanging off the edge of its monitor.
+    pub fn clamp_to(&self, bounds: &Region) -> Option<Region> {
+        self.intersect(bounds)
+    }
+
+    // Moves every edge outwards by `by` pixels, or inwards when it is negative.
+    pub fn grow(&self, by: i32) -> Region {
+        Region {
+            x: self.x - by,
+            y: self.y - by,
+            width: self.width + 2 * by,
+            height: self.height + 2 * by,
+        }
+    }
+
+    pub fn scale(&self, factor: f64) -> Region {
+        let scaled = |value: i32| (value as f64 * factor).round() as i32;
+        Region {
+            x: scaled(self.x),
+            y: scaled(self.y),
+            width: scaled(self.width),
+            height: scaled(self.height),
+        }
+    }
+
+    // Rounds every edge to the nearest multiple of `grid`, keeping at least one cell.
+    pub fn snap(&self, grid: i32) -> Region {
+        let round = |value: i32| (value as f64 / grid as f64).round() as i32 * grid;
+        let (x, y) = (round(self.x), round(self.y));
+        Region {
+            x,
+            y,
+            width: (round(self.right()) - x).max(grid),
+            height: (round(self.bottom()) - y).max(grid),
+        }
+    }
+
+    // Trims a logical pixel off an odd side (or adds one to a 1px side) until the
+    // side is even in physical pixels at `scale`, as yuv420p video needs.
+    pub fn even(&self, scale: f64) -> Region {
+        let fix = |size: i32| {
+            [size, size - 1, size + 1, size - 2, size + 2]
+                .into_iter()
+                .filter(|&size| size > 0)
+                .find(|&size| (size as f64 * scale).round() as i64 % 2 == 0)
+                .unwrap_or(size)
+        };
+        Region {
+            width: fix(self.width),
+            height: fix(self.height),
+            ..*self
+        }
+    }
+}
+
+// wl_output transforms 1, 3, 5 and 7 turn the output by 90 or 270 degrees.
+pub fn is_rotated(transform: i64) -> bool {
+    transform % 2 == 1
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{} {}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    fn from_str(geometry: &str) -> Result<Region> {
+        Region::parse(geometry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // proptest is not vendored, so properties run over a fixed-seed xorshift stream.
+    struct Cases(u64);
+
+    impl Cases {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn int(&mut self, min: i32, max: i32) -> i32 {
+            min + (self.next() % (max - min + 1) as u64) as i32
+        }
+
+        fn region(&mut self) -> Region {
+            Region::new(
+                self.int(-4000, 4000),
+                self.int(-4000, 4000),
+                self.int(1, 3000),
+                self.int(1, 3000),
+            )
+        }
+    }
+
+    fn check(property: impl Fn(&mut Cases)) {
+        let mut cases = Cases(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            property(&mut cases);
+        }
+    }
+
+    #[test]
+    fn display_round_trips_through_parse() {
+        check(|cases| {
+            let region = cases.region();
+            assert_eq!(region.to_string().parse::<Region>().unwrap(), region);
+        });
+    }
+
+    #[test]
+    fn snap_moves_edges_onto_the_grid() {
+        check(|cases| {
+            let (region, grid) = (cases.region(), cases.int(1, 64));
+            let snapped = region.snap(grid);
+            for value in [snapped.x, snapped.y, snapped.width, snapped.height] {
+                assert_eq!(value % grid, 0);
+            }
+            assert!((snapped.x - region.x).abs() * 2 <= grid);
+            assert!((snapped.y - region.y).abs() * 2 <= grid);
+            assert!(snapped.width >= grid && snapped.height >= grid);
+            if region.width >= grid {
+                assert!((snapped.right() - region.right()).abs() * 2 <= grid);
+            }
+        });
+        assert_eq!(
+            Region::new(13, -5, 301, 198).snap(8),
+            Region::new(16, -8, 296, 200)
+        );
+    }
+
+    #[test]
+    fn even_sizes_are_even_in_physical_pixels() {
+        check(|cases| {
+            let region = cases.region();
+            let scale = [1.0, 1.25, 1.5, 2.0][cases.int(0, 3) as usize];
+            let even = region.even(scale);
+            for (before, after) in [(region.width, even.width), (region.height, even.height)] {
+                assert_eq!((after as f64 * scale).round() as i64 % 2, 0);
+                assert!((after - before).abs() <= 2 && after > 0);
+            }
+            assert_eq!((even.x, even.y), (region.x, region.y));
+        });
+        assert_eq!(
+            Region::new(5, 5, 301, 1).even(1.0),
+            Region::new(5, 5, 300, 2)
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_input() {
+        assert_eq!(Region::parse("1,2 3x4").unwrap(), Region::new(1, 2, 3, 4));
+        assert_eq!(
+            Region::parse("-1920,-200 800x600").unwrap(),
+            Region::new(-1920, -200, 800, 600)
+        );
+        for bad in [
+            "",
+            "1,2",
+            "1,2 3",
+            "1 2 3x4",
+            "a,2 3x4",
+            "1,2 3x4x5",
+            "1,2,3 4x5",
+            "1,2 -3x4",
+        ] {
+            assert!(Region::parse(bad).is_err(), "{:?} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn intersection_is_commutative_and_inside_both() {
+        check(|cases| {
+            let (a, b) = (cases.region(), cases.region());
+            assert_eq!(a.intersect(&b), b.intersect(&a));
+            if let Some(both) = a.intersect(&b) {
+                assert!(!both.is_empty());
+                for region in [a, b] {
+                    assert_eq!(both.intersect(&region), Some(both));
+                }
+            }
+            assert_eq!(a.intersect(&a), Some(a));
+        });
+    }
+
+    #[test]
+    fn intersection_agrees_with_contains() {
+        check(|cases| {
+            let (a, b) = (cases.region(), cases.region());
+            let (x, y) = (cases.int(-4000, 7000), cases.int(-4000, 7000));
+            let inside = a.intersect(&b).is_some_and(|both| both.contains(x, y));
+            assert_eq!(inside, a.contains(x, y) && b.contains(x, y));
+        });
+    }
+
+    #[test]
+    fn union_contains_both() {
+        check(|cases| {
+            let (a, b) = (cases.region(), cases.region());
+            let both = a.union(&b);
+            assert_eq!(both, b.union(&a));
+            for region in [a, b] {
+                assert_eq!(region.intersect(&both), Some(region));
+            }
+        });
+    }
+
+    #[test]
+    fn clamp_stays_inside_bounds() {
+        check(|cases| {
+            let (region, bounds) = (cases.region(), cases.region());
+            match region.clamp_to(&bounds) {
+                Some(clamped) => {
+                    assert_eq!(clamped.clamp_to(&bounds), Some(clamped));
+                    assert!(clamped.width <= region.width && clamped.height <= region.height);
+                    assert!(bounds.contains(clamped.x, clamped.y));
+                    assert!(bounds.contains(clamped.right() - 1, clamped.bottom() - 1));
+                }
+                None => assert!(!region.intersects(&bounds)),
+            }
+        });
+    }
+
+    #[test]
+    fn grow_and_scale_invert() {
+        check(|cases| {
+            let region = cases.region();
+            let dx = cases.int(-4000, 4000);
+            assert_eq!(region.grow(dx).grow(-dx), region);
+            assert_eq!(region.scale(2.0).scale(0.5), region);
+        });
+    }
+
+    #[test]
+    fn monitor_bounds_are_logical() {
+        let monitor =
+            serde_json::json!({"x": 2048, "y": 0, "width": 2560, "height": 1440, "scale": 1.25});
+        assert_eq!(
+            Region::of_monitor(&monitor),
+            Region::new(2048, 0, 2048, 1152)
+        );
+        for (transform, size) in [(1, (1152, 2048)), (2, (2048, 1152)), (7, (1152, 2048))] {
+            let mut rotated = monitor.clone();
+            rotated["transform"] = transform.into();
+            let region = Region::of_monitor(&rotated);
+            assert_eq!(
+                (region.width, region.height),
+                size,
+                "transform {}",
+                transform
+            );
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::{Context, Result};
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    code[0] as u32 | (code[1] as u32) << 8 | (code[2] as u32) << 16 | (code[3] as u32) << 24
+}
+
+const ARGB8888: u32 = 0;
+const XRGB8888: u32 = 1;
+const ABGR8888: u32 = fourcc(b"AB24");
+const XBGR8888: u32 = fourcc(b"XB24");
+const ARGB2101010: u32 = fourcc(b"AR30");
+const XRGB2101010: u32 = fourcc(b"XR30");
+const ABGR2101010: u32 = fourcc(b"AB30");
+const XBGR2101010: u32 = fourcc(b"XB30");
+const ABGR16161616F: u32 = fourcc(b"AB4H");
+const XBGR16161616F: u32 = fourcc(b"XB4H");
+
+// scRGB 1.0 is taken as SDR reference white (ITU-R BT.2408).
+const SDR_WHITE_NITS: f32 = 203.0;
+const KNEE: f32 = 0.8;
+
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    // Non-linear sRGB values for integer formats, linear scRGB for float formats.
+    rgb: Vec<[f32; 3]>,
+    linear: bool,
+}
+
+fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f => sign * f32::INFINITY,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn pq_encode(nits: f32) -> f32 {
+    let (m1, m2) = (0.159_301_76, 78.84375);
+    let (c1, c2, c3) = (0.8359375, 18.851_563, 18.6875);
+    let l = (nits / 10000.0).clamp(0.0, 1.0).powf(m1);
+    ((c1 + c2 * l) / (1.0 + c3 * l)).powf(m2)
+}
+
+fn tone_map(v: f32) -> f32 {
+    // Leave SDR content alone and roll highlights off above the knee.
+    if v <= KNEE {
+        v.max(0.0)
+    } else {
+        KNEE + (1.0 - KNEE) * (1.0 - (-(v - KNEE) / (1.0 - KNEE)).exp())
+    }
+}
+
+fn bt709_to_bt2020([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [
+        0.6274 * r + 0.3293 * g + 0.0433 * b,
+        0.0691 * r + 0.9195 * g + 0.0114 * b,
+        0.0164 * r + 0.0880 * g + 0.8956 * b,
+    ]
+}
+
+pub fn format_name(format: u32) -> String {
+    match format {
+        ARGB8888 => "ARGB8888".to_string(),
+        XRGB8888 => "XRGB8888".to_string(),
+        _ => String::from_utf8_lossy(&format.to_le_bytes()).into_owned(),
+    }
+}
+
+pub fn decode(data: &[u8], width: u32, height: u32, stride: u32, format: u32) -> Result<Frame> {
+    let bytes_per_pixel = match format {
+        ABGR16161616F | XBGR16161616F => 8,
+        ARGB8888 | XRGB8888 | ABGR8888 | XBGR8888 | ARGB2101010 | XRGB2101010 | ABGR2101010
+        | XBGR2101010 => 4,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported screencopy pixel format {}",
+                format_name(format)
+            ));
+        }
+    };
+    if (stride as usize) < width as usize * bytes_per_pixel
+        || data.len() < stride as usize * height as usize
+    {
+        return Err(anyhow::anyhow!("Screencopy buffer is too small"));
+    }
+
+    let mut rgb = Vec::with_capacity(width as usize * height as usize);
+    for row in data.chunks_exact(stride as usize).take(height as usize) {
+        for px in row[..width as usize * bytes_per_pixel].chunks_exact(bytes_per_pixel) {
+            let word = u32::from_le_bytes([px[0], px[1], px[2], px[3]]);
+            let ten = |shift: u32| ((word >> shift) & 0x3ff) as f32 / 1023.0;
+            let eight = |i: usize| px[i] as f32 / 255.0;
+            rgb.push(match format {
+                ARGB8888 | XRGB8888 => [eight(2), eight(1), eight(0)],
+                ABGR8888 | XBGR8888 => [eight(0), eight(1), eight(2)],
+                ARGB2101010 | XRGB2101010 => [ten(20), ten(10), ten(0)],
+                ABGR2101010 | XBGR2101010 => [ten(0), ten(10), ten(20)],
+                _ => {
+                    let half = |i: usize| half_to_f32(u16::from_le_bytes([px[i], px[i + 1]]));
+                    [half(0), half(2), half(4)]
+                }
+            });
+        }
+    }
+    Ok(Frame {
+        width,
+        height,
+        rgb,
+        linear: matches!(format, ABGR16161616F | XBGR16161616F),
+    })
+}
+
+impl Frame {
+    pub fn is_hdr(&self) -> bool {
+        self.linear
+    }
+
+    // Tone-mapped, sRGB-encoded values in 0..1.
+    fn sdr(&self) -> impl Iterator<Item = f32> + '_ {
+        self.rgb.iter().flat_map(move |&pixel| {
+            pixel.map(|v| {
+                if self.linear {
+                    linear_to_srgb(tone_map(v))
+                } else {
+                    v.clamp(0.0, 1.0)
+                }
+            })
+        })
+    }
+
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.sdr().map(|v| (v * 255.0).round() as u8).collect()
+    }
+
+    pub fn to_rgb16(&self) -> Vec<u16> {
+        self.sdr().map(|v| (v * 65535.0).round() as u16).collect()
+    }
+
+    // BT.2020 primaries with the PQ transfer function, for AVIF's 9/16/9 CICP.
+    pub fn to_pq16(&self) -> Vec<u16> {
+        self.rgb
+            .iter()
+            .flat_map(|&pixel| {
+                let linear = if self.linear {
+                    pixel
+                } else {
+                    pixel.map(srgb_to_linear)
+                };
+                bt709_to_bt2020(linear)
+                    .map(|v| (pq_encode(v.max(0.0) * SDR_WHITE_NITS) * 65535.0).round() as u16)
+            })
+            .collect()
+    }
+}
+
+pub fn encode_avif(pq_png: &[u8]) -> Result<Vec<u8>> {
+    let input = crate::storage::temp_file(&format!("hdr-{}.png", std::process::id()))?;
+    let output = input.with_extension("avif");
+    std::fs::write(&input, pq_png).context("Failed to write temporary PNG for avifenc")?;
+    log::debug!("Encoding 10-bit PQ AVIF with avifenc");
+    let ran = crate::runner::checked(
+        "avifenc",
+        &[
+            "--depth",
+            "10",
+            "--cicp",
+            "9/16/9",
+            "--range",
+            "full",
+            &input.to_string_lossy(),
+            &output.to_string_lossy(),
+        ],
+        None,
+    );
+    let _ = std::fs::remove_file(&input);
+    if let Err(e) = ran {
+        let _ = std::fs::remove_file(&output);
+        return Err(e.context("avifenc failed to encode HDR screenshot"));
+    }
+    let encoded = std::fs::read(&output).context("Failed to read avifenc output");
+    let _ = std::fs::remove_file(&output);
+    encoded
+}
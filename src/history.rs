@@ -0,0 +1,113 @@
+use crate::i18n;
+use crate::picker::{Item, Picker};
+use crate::storage::history_file;
+use crate::thumbnail;
+use anyhow::{Context, Result};
+use std::fs::{OpenOptions, create_dir_all};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub fn record(path: &Path) -> Result<()> {
+    let file = history_file()?;
+    create_dir_all(file.parent().unwrap()).context("Failed to create history directory")?;
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    log::debug!(
+        "Recording in history ({}): {}",
+        file.display(),
+        path.display()
+    );
+    let mut history = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file)
+        .context(format!("Failed to open history file '{}'", file.display()))?;
+    writeln!(history, "{}", path.display()).context("Failed to write history entry")?;
+    Ok(())
+}
+
+pub fn replace(renames: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let file = history_file()?;
+    let contents = match std::fs::read_to_string(&file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(e).context(format!("Failed to read history file '{}'", file.display()));
+        }
+    };
+    let renames: Vec<(PathBuf, PathBuf)> = renames
+        .iter()
+        .map(|(old, new)| {
+            (
+                old.canonicalize().unwrap_or_else(|_| old.to_path_buf()),
+                new.canonicalize().unwrap_or_else(|_| new.to_path_buf()),
+            )
+        })
+        .collect();
+
+    let updated: String = contents
+        .lines()
+        .map(
+            |line| match renames.iter().find(|(old, _)| Path::new(line) == old) {
+                Some((old, new)) => {
+                    log::debug!(
+                        "Updating history entry: {} -> {}",
+                        old.display(),
+                        new.display()
+                    );
+                    format!("{}\n", new.display())
+                }
+                None => format!("{}\n", line),
+            },
+        )
+        .collect();
+    std::fs::write(&file, updated)
+        .context(format!("Failed to write history file '{}'", file.display()))
+}
+
+pub fn recent(count: usize) -> Result<Vec<PathBuf>> {
+    let file = history_file()?;
+    let contents = match std::fs::read_to_string(&file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(e).context(format!("Failed to read history file '{}'", file.display()));
+        }
+    };
+
+    let mut entries: Vec<PathBuf> = Vec::new();
+    for line in contents.lines().rev() {
+        let path = PathBuf::from(line);
+        if entries.len() == count {
+            break;
+        }
+        if !line.is_empty() && path.exists() && !entries.contains(&path) {
+            entries.push(path);
+        }
+    }
+    Ok(entries)
+}
+
+pub fn pick(count: usize, picker: &Picker) -> Result<Option<PathBuf>> {
+    let entries = recent(count)?;
+    if entries.is_empty() {
+        return Err(anyhow::anyhow!("No screenshots in history"));
+    }
+
+    let items: Vec<Item> = entries
+        .iter()
+        .map(|p| Item {
+            label: p
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| p.display().to_string()),
+            icon: Some(thumbnail::find(p).unwrap_or_else(|| p.clone())),
+        })
+        .collect();
+    let Some(index) = picker.choose(i18n::tr("History"), &items)? else {
+        log::debug!("History selection cancelled");
+        return Ok(None);
+    };
+    let selected = entries[index].clone();
+    log::debug!("Selected from history: {}", selected.display());
+    Ok(Some(selected))
+}
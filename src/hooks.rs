@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::process::Command;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    PreCapture,
+    PostCapture,
+    PreSave,
+    PostSave,
+}
+
+pub const STAGES: &[(&str, Stage)] = &[
+    ("pre_capture", Stage::PreCapture),
+    ("post_capture", Stage::PostCapture),
+    ("pre_save", Stage::PreSave),
+    ("post_save", Stage::PostSave),
+];
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::PreCapture => "pre-capture",
+            Stage::PostCapture => "post-capture",
+            Stage::PreSave => "pre-save",
+            Stage::PostSave => "post-save",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Hooks {
+    commands: Vec<(Stage, Vec<String>)>,
+    env: RefCell<Vec<(&'static str, String)>>,
+}
+
+impl Hooks {
+    pub fn new(commands: Vec<(Stage, Vec<String>)>) -> Hooks {
+        Hooks {
+            commands,
+            env: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn set(&self, key: &'static str, value: impl Into<String>) {
+        let mut env = self.env.borrow_mut();
+        env.retain(|(k, _)| *k != key);
+        env.push((key, value.into()));
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let env = self.env.borrow();
+        env.iter().find(|(k, _)| *k == key).map(|(_, v)| v.clone())
+    }
+
+    // A failing pre-* hook aborts the screenshot; post-* failures only warn.
+    pub fn run(&self, stage: Stage) -> Result<()> {
+        for (_, cmd) in self.commands.iter().filter(|(s, _)| *s == stage) {
+            log::debug!("Running {} hook: {}", stage.name(), cmd.join(" "));
+            let status = Command::new(&cmd[0])
+                .args(&cmd[1..])
+                .envs(self.env.borrow().iter().map(|(k, v)| (k, v)))
+                .env("HYPRSHOT_HOOK", stage.name())
+                .stdout(std::io::stderr())
+                .status()
+                .context(format!("Failed to run {} hook '{}'", stage.name(), cmd[0]));
+            let failure = match status {
+                Ok(status) if status.success() => continue,
+                Ok(status) => {
+                    anyhow::anyhow!("{} hook '{}' failed ({})", stage.name(), cmd[0], status)
+                }
+                Err(e) => e,
+            };
+            match stage {
+                Stage::PreCapture | Stage::PreSave => return Err(failure),
+                Stage::PostCapture | Stage::PostSave => log::warn!("{:#}", failure),
+            }
+        }
+        Ok(())
+    }
+}
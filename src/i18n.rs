@@ -0,0 +1,436 @@
+use std::sync::OnceLock;
+
+// Translations are keyed by the English text, gettext style, and fill in
+// {name} placeholders at runtime. The tests check every catalog against MESSAGES.
+type Catalog = &'static [(&'static str, &'static str)];
+
+#[cfg(test)]
+const MESSAGES: &[&str] = &[
+    "Screenshot saved",
+    "Screenshot copied",
+    "Screenshots copied",
+    "Screenshot uploaded",
+    "Recording saved",
+    "Color picked",
+    "Screenshot of {class} window saved",
+    "Screenshot of {class} window copied",
+    "Window",
+    "Image copied to {target}",
+    "Image saved in <i>{path}</i>.",
+    "Image saved in <i>{path}</i> and copied to {target}.",
+    "Image <i>{path}</i> copied to {target}.",
+    "{width}x{height} at {x},{y}",
+    "{size} ({percent}% saved)",
+    "Not enough disk space",
+    "Only {free} free in {folder}",
+    "{count} screenshot(s) copied to {target}.",
+    "<b>{color}</b> copied to {target}.",
+    "Recording saved in <i>{path}</i>.",
+    "the clipboard",
+    "the clipboard and primary selection",
+    "the primary selection",
+    "Output",
+    "Layer",
+    "History",
+    "Save as",
+    "Capture {geometry}",
+    "Save",
+    "Copy",
+    "Edit",
+    "Upload",
+    "Record",
+    "Cancel",
+    "Output selection cancelled",
+    "Layer selection cancelled",
+    "Filename prompt cancelled",
+    "Window selection cancelled",
+    "No menu program found (install rofi, wofi, fuzzel, bemenu or dmenu, or pass --picker)",
+    "Captured a black frame: a fullscreen application is probably using direct scanout. Disable it with `hyprctl keyword render:direct_scanout 0` or pass --allow-black to save anyway",
+];
+
+const RU: Catalog = &[
+    ("Screenshot saved", "Снимок экрана сохранён"),
+    ("Screenshot copied", "Снимок экрана скопирован"),
+    ("Screenshots copied", "Снимки экрана скопированы"),
+    ("Screenshot uploaded", "Снимок экрана загружен"),
+    ("Recording saved", "Запись сохранена"),
+    ("Color picked", "Цвет выбран"),
+    (
+        "Screenshot of {class} window saved",
+        "Снимок окна {class} сохранён",
+    ),
+    (
+        "Screenshot of {class} window copied",
+        "Снимок окна {class} скопирован",
+    ),
+    ("Window", "Окно"),
+    (
+        "Image copied to {target}",
+        "Изображение скопировано в {target}",
+    ),
+    (
+        "Image saved in <i>{path}</i>.",
+        "Изображение сохранено в <i>{path}</i>.",
+    ),
+    (
+        "Image saved in <i>{path}</i> and copied to {target}.",
+        "Изображение сохранено в <i>{path}</i> и скопировано в {target}.",
+    ),
+    (
+        "Image <i>{path}</i> copied to {target}.",
+        "Изображение <i>{path}</i> скопировано в {target}.",
+    ),
+    (
+        "{width}x{height} at {x},{y}",
+        "{width}x{height} в точке {x},{y}",
+    ),
+    (
+        "{size} ({percent}% saved)",
+        "{size} (сэкономлено {percent}%)",
+    ),
+    ("Not enough disk space", "Недостаточно места на диске"),
+    (
+        "Only {free} free in {folder}",
+        "В {folder} свободно только {free}",
+    ),
+    (
+        "{count} screenshot(s) copied to {target}.",
+        "Снимков экрана скопировано в {target}: {count}.",
+    ),
+    (
+        "<b>{color}</b> copied to {target}.",
+        "<b>{color}</b> скопирован в {target}.",
+    ),
+    (
+        "Recording saved in <i>{path}</i>.",
+        "Запись сохранена в <i>{path}</i>.",
+    ),
+    ("the clipboard", "буфер обмена"),
+    (
+        "the clipboard and primary selection",
+        "буфер обмена и первичное выделение",
+    ),
+    ("the primary selection", "первичное выделение"),
+    ("Output", "Монитор"),
+    ("Layer", "Слой"),
+    ("History", "История"),
+    ("Save as", "Сохранить как"),
+    ("Capture {geometry}", "Снимок {geometry}"),
+    ("Save", "Сохранить"),
+    ("Copy", "Копировать"),
+    ("Edit", "Редактировать"),
+    ("Upload", "Загрузить"),
+    ("Record", "Записать"),
+    ("Cancel", "Отмена"),
+    ("Output selection cancelled", "Выбор монитора отменён"),
+    ("Layer selection cancelled", "Выбор слоя отменён"),
+    ("Filename prompt cancelled", "Ввод имени файла отменён"),
+    ("Window selection cancelled", "Выбор окна отменён"),
+    (
+        "No menu program found (install rofi, wofi, fuzzel, bemenu or dmenu, or pass --picker)",
+        "Программа меню не найдена (установите rofi, wofi, fuzzel, bemenu или dmenu либо укажите --picker)",
+    ),
+    (
+        "Captured a black frame: a fullscreen application is probably using direct scanout. Disable it with `hyprctl keyword render:direct_scanout 0` or pass --allow-black to save anyway",
+        "Получен чёрный кадр: вероятно, полноэкранное приложение использует direct scanout. Отключите его командой `hyprctl keyword render:direct_scanout 0` или укажите --allow-black, чтобы всё равно сохранить",
+    ),
+];
+
+const DE: Catalog = &[
+    ("Screenshot saved", "Bildschirmfoto gespeichert"),
+    ("Screenshot copied", "Bildschirmfoto kopiert"),
+    ("Screenshots copied", "Bildschirmfotos kopiert"),
+    ("Screenshot uploaded", "Bildschirmfoto hochgeladen"),
+    ("Recording saved", "Aufnahme gespeichert"),
+    ("Color picked", "Farbe ausgewählt"),
+    (
+        "Screenshot of {class} window saved",
+        "Bildschirmfoto des Fensters {class} gespeichert",
+    ),
+    (
+        "Screenshot of {class} window copied",
+        "Bildschirmfoto des Fensters {class} kopiert",
+    ),
+    ("Window", "Fenster"),
+    ("Image copied to {target}", "Bild in {target} kopiert"),
+    (
+        "Image saved in <i>{path}</i>.",
+        "Bild unter <i>{path}</i> gespeichert.",
+    ),
+    (
+        "Image saved in <i>{path}</i> and copied to {target}.",
+        "Bild unter <i>{path}</i> gespeichert und in {target} kopiert.",
+    ),
+    (
+        "Image <i>{path}</i> copied to {target}.",
+        "Bild <i>{path}</i> in {target} kopiert.",
+    ),
+    (
+        "{width}x{height} at {x},{y}",
+        "{width}x{height} bei {x},{y}",
+    ),
+    ("{size} ({percent}% saved)", "{size} ({percent} % gespart)"),
+    ("Not enough disk space", "Nicht genug Speicherplatz"),
+    (
+        "Only {free} free in {folder}",
+        "Nur {free} frei in {folder}",
+    ),
+    (
+        "{count} screenshot(s) copied to {target}.",
+        "{count} Bildschirmfoto(s) in {target} kopiert.",
+    ),
+    (
+        "<b>{color}</b> copied to {target}.",
+        "<b>{color}</b> in {target} kopiert.",
+    ),
+    (
+        "Recording saved in <i>{path}</i>.",
+        "Aufnahme unter <i>{path}</i> gespeichert.",
+    ),
+    ("the clipboard", "die Zwischenablage"),
+    (
+        "the clipboard and primary selection",
+        "die Zwischenablage und die primäre Auswahl",
+    ),
+    ("the primary selection", "die primäre Auswahl"),
+    ("Output", "Bildschirm"),
+    ("Layer", "Ebene"),
+    ("History", "Verlauf"),
+    ("Save as", "Speichern unter"),
+    ("Capture {geometry}", "Aufnahme {geometry}"),
+    ("Save", "Speichern"),
+    ("Copy", "Kopieren"),
+    ("Edit", "Bearbeiten"),
+    ("Upload", "Hochladen"),
+    ("Record", "Aufnehmen"),
+    ("Cancel", "Abbrechen"),
+    (
+        "Output selection cancelled",
+        "Bildschirmauswahl abgebrochen",
+    ),
+    ("Layer selection cancelled", "Ebenenauswahl abgebrochen"),
+    (
+        "Filename prompt cancelled",
+        "Eingabe des Dateinamens abgebrochen",
+    ),
+    ("Window selection cancelled", "Fensterauswahl abgebrochen"),
+    (
+        "No menu program found (install rofi, wofi, fuzzel, bemenu or dmenu, or pass --picker)",
+        "Kein Menüprogramm gefunden (installiere rofi, wofi, fuzzel, bemenu oder dmenu oder gib --picker an)",
+    ),
+    (
+        "Captured a black frame: a fullscreen application is probably using direct scanout. Disable it with `hyprctl keyword render:direct_scanout 0` or pass --allow-black to save anyway",
+        "Schwarzes Bild aufgenommen: Eine Vollbildanwendung nutzt vermutlich Direct Scanout. Deaktiviere es mit `hyprctl keyword render:direct_scanout 0` oder gib --allow-black an, um trotzdem zu speichern",
+    ),
+];
+
+const ES: Catalog = &[
+    ("Screenshot saved", "Captura guardada"),
+    ("Screenshot copied", "Captura copiada"),
+    ("Screenshots copied", "Capturas copiadas"),
+    ("Screenshot uploaded", "Captura subida"),
+    ("Recording saved", "Grabación guardada"),
+    ("Color picked", "Color seleccionado"),
+    (
+        "Screenshot of {class} window saved",
+        "Captura de la ventana {class} guardada",
+    ),
+    (
+        "Screenshot of {class} window copied",
+        "Captura de la ventana {class} copiada",
+    ),
+    ("Window", "Ventana"),
+    ("Image copied to {target}", "Imagen copiada en {target}"),
+    (
+        "Image saved in <i>{path}</i>.",
+        "Imagen guardada en <i>{path}</i>.",
+    ),
+    (
+        "Image saved in <i>{path}</i> and copied to {target}.",
+        "Imagen guardada en <i>{path}</i> y copiada en {target}.",
+    ),
+    (
+        "Image <i>{path}</i> copied to {target}.",
+        "Imagen <i>{path}</i> copiada en {target}.",
+    ),
+    ("{width}x{height} at {x},{y}", "{width}x{height} en {x},{y}"),
+    ("{size} ({percent}% saved)", "{size} ({percent} % ahorrado)"),
+    (
+        "Not enough disk space",
+        "No hay suficiente espacio en disco",
+    ),
+    (
+        "Only {free} free in {folder}",
+        "Solo quedan {free} libres en {folder}",
+    ),
+    (
+        "{count} screenshot(s) copied to {target}.",
+        "{count} captura(s) copiada(s) en {target}.",
+    ),
+    (
+        "<b>{color}</b> copied to {target}.",
+        "<b>{color}</b> copiado en {target}.",
+    ),
+    (
+        "Recording saved in <i>{path}</i>.",
+        "Grabación guardada en <i>{path}</i>.",
+    ),
+    ("the clipboard", "el portapapeles"),
+    (
+        "the clipboard and primary selection",
+        "el portapapeles y la selección primaria",
+    ),
+    ("the primary selection", "la selección primaria"),
+    ("Output", "Pantalla"),
+    ("Layer", "Capa"),
+    ("History", "Historial"),
+    ("Save as", "Guardar como"),
+    ("Capture {geometry}", "Capturar {geometry}"),
+    ("Save", "Guardar"),
+    ("Copy", "Copiar"),
+    ("Edit", "Editar"),
+    ("Upload", "Subir"),
+    ("Record", "Grabar"),
+    ("Cancel", "Cancelar"),
+    (
+        "Output selection cancelled",
+        "Selección de pantalla cancelada",
+    ),
+    ("Layer selection cancelled", "Selección de capa cancelada"),
+    ("Filename prompt cancelled", "Nombre de archivo cancelado"),
+    (
+        "Window selection cancelled",
+        "Selección de ventana cancelada",
+    ),
+    (
+        "No menu program found (install rofi, wofi, fuzzel, bemenu or dmenu, or pass --picker)",
+        "No se encontró ningún programa de menú (instala rofi, wofi, fuzzel, bemenu o dmenu, o usa --picker)",
+    ),
+    (
+        "Captured a black frame: a fullscreen application is probably using direct scanout. Disable it with `hyprctl keyword render:direct_scanout 0` or pass --allow-black to save anyway",
+        "Se capturó un fotograma negro: probablemente una aplicación a pantalla completa usa direct scanout. Desactívalo con `hyprctl keyword render:direct_scanout 0` o usa --allow-black para guardarlo de todos modos",
+    ),
+];
+
+const CATALOGS: [(&str, Catalog); 3] = [("ru", RU), ("de", DE), ("es", ES)];
+
+// Follows gettext: LANGUAGE (a colon-separated list) unless the locale is C,
+// then the first of LC_ALL, LC_MESSAGES and LANG that is set.
+fn detect(var: impl Fn(&str) -> Option<String>) -> Option<Catalog> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()))?;
+    if locale == "C" || locale.starts_with("C.") || locale == "POSIX" {
+        return None;
+    }
+    let language = var("LANGUAGE").unwrap_or_default();
+    language
+        .split(':')
+        .chain(std::iter::once(locale.as_str()))
+        .filter_map(|tag| {
+            let code = tag.split(['_', '.', '@']).next()?;
+            CATALOGS
+                .iter()
+                .find(|(lang, _)| *lang == code)
+                .map(|(_, catalog)| *catalog)
+        })
+        .next()
+}
+
+fn catalog() -> Option<Catalog> {
+    static CATALOG: OnceLock<Option<Catalog>> = OnceLock::new();
+    // Tests compare against the English text whatever the developer's locale is.
+    if cfg!(test) {
+        return None;
+    }
+    *CATALOG.get_or_init(|| {
+        let catalog = detect(|name| std::env::var(name).ok());
+        log::debug!(
+            "Translations: {}",
+            if catalog.is_some() { "yes" } else { "none" }
+        );
+        catalog
+    })
+}
+
+fn lookup(catalog: Option<Catalog>, text: &'static str) -> &'static str {
+    catalog
+        .and_then(|catalog| catalog.iter().find(|(key, _)| *key == text))
+        .map_or(text, |(_, translated)| translated)
+}
+
+pub fn tr(text: &'static str) -> &'static str {
+    lookup(catalog(), text)
+}
+
+// Translates text and replaces its {name} placeholders.
+pub fn trf(text: &'static str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(tr(text).to_string(), |message, (name, value)| {
+            message.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = text
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn catalogs_cover_every_message() {
+        for (lang, catalog) in CATALOGS {
+            for message in MESSAGES {
+                let translated = lookup(Some(catalog), message);
+                assert_ne!(translated, *message, "{} is missing {:?}", lang, message);
+                assert_eq!(
+                    placeholders(translated),
+                    placeholders(message),
+                    "{} changes the placeholders of {:?}",
+                    lang,
+                    message
+                );
+            }
+            for (key, _) in catalog {
+                assert!(MESSAGES.contains(key), "{} has unknown key {:?}", lang, key);
+            }
+        }
+    }
+
+    #[test]
+    fn detects_language_from_the_environment() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        let detected = |vars| detect(env(vars)).map(|catalog| catalog[0].1);
+        assert_eq!(detected(&[("LANG", "ru_RU.UTF-8")]), Some(RU[0].1));
+        assert_eq!(
+            detected(&[("LANG", "en_US.UTF-8"), ("LC_MESSAGES", "de_AT")]),
+            Some(DE[0].1)
+        );
+        assert_eq!(
+            detected(&[("LANG", "es_ES.UTF-8"), ("LANGUAGE", "pt_BR:ru")]),
+            Some(RU[0].1)
+        );
+        assert_eq!(detected(&[("LANG", "C.UTF-8"), ("LANGUAGE", "ru")]), None);
+        assert_eq!(detected(&[("LANG", "fr_FR.UTF-8")]), None);
+        assert_eq!(detected(&[]), None);
+        assert_eq!(
+            trf("Image copied to {target}", &[("target", "the clipboard")]),
+            "Image copied to the clipboard"
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use crate::geometry::Region;
+use crate::metadata;
+use crate::paths;
+use crate::runner;
+use crate::save::SaveOptions;
+use crate::storage;
+use crate::utils;
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+const SRGB_PROFILES: &[&str] = &[
+    "/usr/share/color/icc/colord/sRGB.icc",
+    "/usr/share/color/icc/sRGB.icc",
+    "/usr/share/color/icc/ghostscript/srgb.icc",
+    "/usr/share/ghostscript/iccprofiles/srgb.icc",
+];
+
+fn hyprland_profiles() -> Vec<(String, PathBuf)> {
+    let Some(path) = dirs::config_dir().map(|dir| dir.join("hypr").join("hyprland.conf")) else {
+        return Vec::new();
+    };
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    let mut profiles = Vec::new();
+    let (mut in_block, mut output, mut icc) = (false, None, None);
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.starts_with("monitorv2") && line.ends_with('{') {
+            (in_block, output, icc) = (true, None, None);
+        } else if in_block && line == "}" {
+            if let (Some(output), Some(icc)) = (output.take(), icc.take()) {
+                profiles.push((output, paths::expand(Path::new(&icc))));
+            }
+            in_block = false;
+        } else if in_block && let Some((key, value)) = line.split_once('=') {
+            match key.trim() {
+                "output" => output = Some(value.trim().to_string()),
+                "icc" => icc = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+    profiles
+}
+
+fn profile_for(geometry: &str, opts: &SaveOptions) -> Result<Option<PathBuf>> {
+    let profiles = if opts.icc_profiles.is_empty() {
+        hyprland_profiles()
+    } else {
+        opts.icc_profiles.clone()
+    };
+    if profiles.is_empty() {
+        return Ok(None);
+    }
+
+    let outputs = utils::intersecting_monitors(&Region::parse(geometry)?)?;
+    let found = outputs
+        .iter()
+        .chain(std::iter::once(&"default".to_string()))
+        .find_map(|name| {
+            profiles
+                .iter()
+                .find(|(output, _)| output == name)
+                .map(|(output, path)| (output.clone(), path.clone()))
+        });
+    match &found {
+        Some((output, path)) => log::debug!("ICC profile for {}: {}", output, path.display()),
+        None => log::debug!("No ICC profile configured for {}", outputs.join(", ")),
+    }
+    Ok(found.map(|(_, path)| path))
+}
+
+fn convert_to_srgb(tagged: &[u8]) -> Result<Vec<u8>> {
+    let srgb = SRGB_PROFILES
+        .iter()
+        .map(Path::new)
+        .find(|path| path.exists())
+        .context("No sRGB ICC profile found for --srgb-convert (install colord or ghostscript)")?;
+    let input = storage::temp_file(&format!("icc-{}.png", std::process::id()))?;
+    std::fs::write(&input, tagged).context("Failed to write temporary PNG for magick")?;
+    log::debug!("Converting to sRGB with {}", srgb.display());
+    let output = runner::checked(
+        "magick",
+        &[
+            &input.to_string_lossy(),
+            "-intent",
+            "relative",
+            "-profile",
+            &srgb.to_string_lossy(),
+            "png:-",
+        ],
+        None,
+    )
+    .context(
+        "magick failed to convert screenshot to sRGB (ImageMagick is needed for --srgb-convert)",
+    );
+    let _ = std::fs::remove_file(&input);
+    Ok(output?.stdout)
+}
+
+pub fn apply<'a>(png: &'a [u8], geometry: &str, opts: &SaveOptions) -> Result<Cow<'a, [u8]>> {
+    let Some(path) = profile_for(geometry, opts)? else {
+        return Ok(Cow::Borrowed(png));
+    };
+    let profile =
+        std::fs::read(&path).context(format!("Failed to read ICC profile '{}'", path.display()))?;
+    let tagged = metadata::embed_icc(png, &profile)?;
+    if opts.srgb_convert {
+        return Ok(Cow::Owned(convert_to_srgb(&tagged)?));
+    }
+    Ok(Cow::Owned(tagged))
+}
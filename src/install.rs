@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const IMAGE_MIME_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/webp",
+    "image/gif",
+    "image/bmp",
+    "image/jxl",
+];
+
+fn applications_dir() -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .context("Could not determine data directory")?
+        .join("applications"))
+}
+
+fn current_exe() -> Result<String> {
+    let exe = std::env::current_exe().context("Failed to locate the hyprshot-rs executable")?;
+    Ok(exe.to_string_lossy().into_owned())
+}
+
+fn desktop_entry(exe: &str) -> String {
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=Hyprshot-rs\n\
+         GenericName=Screenshot processor\n\
+         Comment=Copy images to the clipboard and run the hyprshot-rs pipeline\n\
+         Exec={} process %F\n\
+         Icon=applets-screenshooter\n\
+         Terminal=false\n\
+         NoDisplay=true\n\
+         Categories=Graphics;Utility;\n\
+         MimeType={};\n",
+        exe,
+        IMAGE_MIME_TYPES.join(";")
+    )
+}
+
+pub fn install_desktop(print: bool) -> Result<()> {
+    let entry = desktop_entry(&current_exe()?);
+    if print {
+        print!("{}", entry);
+        return Ok(());
+    }
+
+    let dir = applications_dir()?;
+    std::fs::create_dir_all(&dir).context(format!(
+        "Failed to create applications directory '{}'",
+        dir.display()
+    ))?;
+    let path = dir.join("hyprshot-rs-process.desktop");
+    std::fs::write(&path, entry).context(format!(
+        "Failed to write desktop entry '{}'",
+        path.display()
+    ))?;
+    println!("Installed {}", path.display());
+
+    match Command::new("update-desktop-database").arg(&dir).status() {
+        Ok(status) if !status.success() => {
+            log::debug!("update-desktop-database exited with {}", status)
+        }
+        Err(e) => log::debug!("Skipping update-desktop-database: {}", e),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn dbus_services_dir() -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .context("Could not determine data directory")?
+        .join("dbus-1/services"))
+}
+
+pub fn install_dbus_service() -> Result<()> {
+    let dir = dbus_services_dir()?;
+    std::fs::create_dir_all(&dir).context(format!(
+        "Failed to create D-Bus services directory '{}'",
+        dir.display()
+    ))?;
+    let path = dir.join(format!("{}.service", crate::service::BUS_NAME));
+    let entry = format!(
+        "[D-BUS Service]\nName={}\nExec={} dbus-service\n",
+        crate::service::BUS_NAME,
+        current_exe()?
+    );
+    log::debug!("{}", entry.trim_end());
+    std::fs::write(&path, entry).context(format!(
+        "Failed to write D-Bus service file '{}'",
+        path.display()
+    ))?;
+    println!("Installed {}", path.display());
+    Ok(())
+}
+
+const KEYBINDS: &[(&str, &str, &str)] = &[
+    ("", "Print", "-m region"),
+    ("SHIFT", "Print", "-m window"),
+    ("CTRL", "Print", "-m output"),
+    ("ALT", "Print", "-m window -m active"),
+    ("SUPER", "Print", "-m output -m active"),
+];
+
+fn hypr_dir() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .context("Could not determine config directory")?
+        .join("hypr"))
+}
+
+fn chord(line: &str) -> Option<(String, String)> {
+    let (keyword, value) = line.split_once('=')?;
+    if !keyword.trim().starts_with("bind") {
+        return None;
+    }
+    let mut fields = value.split(',');
+    let mut mods: Vec<String> = fields
+        .next()?
+        .split(|c: char| c.is_whitespace() || c == '_')
+        .filter(|m| !m.is_empty())
+        .map(str::to_ascii_uppercase)
+        .collect();
+    mods.sort();
+    let key = fields.next()?.trim().to_ascii_lowercase();
+    Some((mods.join(" "), key))
+}
+
+fn existing_chords(paths: &[PathBuf]) -> Vec<(String, String)> {
+    paths
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| chord(line.split('#').next().unwrap_or("")))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+pub fn install_keybinds(dry_run: bool, append: bool) -> Result<()> {
+    let exe = current_exe()?;
+    let dir = hypr_dir()?;
+    let hyprland_conf = dir.join("hyprland.conf");
+    let snippet = dir.join("hyprshot-rs.conf");
+    let target = if append { &hyprland_conf } else { &snippet };
+
+    let existing = existing_chords(&[hyprland_conf.clone(), snippet.clone()]);
+    let mut binds = String::new();
+    for (mods, key, args) in KEYBINDS {
+        let line = format!("bind = {}, {}, exec, {} {}", mods, key, exe, args);
+        if existing.contains(&chord(&line).unwrap()) {
+            let label = if mods.is_empty() {
+                key.to_string()
+            } else {
+                format!("{} + {}", mods, key)
+            };
+            eprintln!("Skipping {}: already bound", label);
+            continue;
+        }
+        binds.push_str(&line);
+        binds.push('\n');
+    }
+
+    let source_line = format!("source = {}", snippet.display());
+    let needs_source = !append
+        && !std::fs::read_to_string(&hyprland_conf)
+            .map(|conf| conf.lines().any(|line| line.trim() == source_line))
+            .unwrap_or(false);
+
+    if dry_run {
+        println!("# {}", target.display());
+        print!("{}", binds);
+        if needs_source && !binds.is_empty() {
+            println!("# {}", hyprland_conf.display());
+            println!("{}", source_line);
+        }
+        return Ok(());
+    }
+    if binds.is_empty() {
+        println!("All keybindings are already configured");
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&dir).context(format!(
+        "Failed to create Hyprland config directory '{}'",
+        dir.display()
+    ))?;
+    append_lines(target, &binds)?;
+    println!("Added keybindings to {}", target.display());
+    if needs_source {
+        append_lines(&hyprland_conf, &format!("{}\n", source_line))?;
+        println!(
+            "Sourced {} from {}",
+            snippet.display(),
+            hyprland_conf.display()
+        );
+    }
+    log::debug!("{}", binds.trim_end());
+    Ok(())
+}
+
+fn append_lines(path: &Path, lines: &str) -> Result<()> {
+    use std::io::Write;
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(format!("Failed to open '{}'", path.display()))?;
+    let needs_newline = std::fs::read(path)
+        .map(|data| data.last().is_some_and(|&c| c != b'\n'))
+        .unwrap_or(false);
+    if needs_newline {
+        writeln!(file)?;
+    }
+    file.write_all(lines.as_bytes())
+        .context(format!("Failed to write '{}'", path.display()))
+}
@@ -0,0 +1,90 @@
+use crate::environment;
+use crate::storage;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::os::fd::AsRawFd;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Concurrent {
+    #[default]
+    Deny,
+    Queue,
+    CancelPrevious,
+}
+
+// Programs a capture waits on while the user picks something.
+const SELECTORS: [&str; 7] = ["slurp", "rofi", "wofi", "fuzzel", "bemenu", "dmenu", "fzf"];
+
+// Held until the capture is delivered; the kernel drops the flock with the file.
+#[derive(Debug)]
+pub struct Lock {
+    _file: File,
+}
+
+fn flock(file: &File, blocking: bool) -> std::io::Result<bool> {
+    let operation = if blocking {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_EX | libc::LOCK_NB
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+        return Ok(true);
+    }
+    let error = std::io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::EWOULDBLOCK) => Ok(false),
+        _ => Err(error),
+    }
+}
+
+fn cancel_selection(previous: i32) {
+    for (pid, comm) in environment::children(previous) {
+        if SELECTORS.contains(&comm.as_str()) {
+            log::debug!("Cancelling {} ({}) of the previous capture", comm, pid);
+            unsafe { libc::kill(pid, libc::SIGTERM) };
+        }
+    }
+}
+
+// One capture at a time: a second keypress while slurp is open would
+// otherwise stack another selection on top.
+pub fn acquire(policy: Concurrent) -> Result<Lock> {
+    let path = storage::runtime_file("instance.lock")?;
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .context(format!("Failed to open lock file '{}'", path.display()))?;
+
+    let failed = || format!("Failed to lock '{}'", path.display());
+    if !flock(&file, false).context(failed())? {
+        let mut previous = String::new();
+        let _ = file.read_to_string(&mut previous);
+        let previous = previous.trim().parse::<i32>().ok();
+        match policy {
+            Concurrent::Deny => {
+                return Err(anyhow::anyhow!(
+                    "Another capture is still running{}; pass --concurrent queue or cancel-previous to run anyway",
+                    previous
+                        .map(|pid| format!(" (pid {})", pid))
+                        .unwrap_or_default()
+                ));
+            }
+            Concurrent::Queue => log::debug!("Waiting for the previous capture to finish"),
+            Concurrent::CancelPrevious => {
+                if let Some(pid) = previous {
+                    cancel_selection(pid);
+                }
+            }
+        }
+        flock(&file, true).context(failed())?;
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    Ok(Lock { _file: file })
+}
@@ -0,0 +1,135 @@
+use crate::storage;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+struct Logger {
+    format: LogFormat,
+    file: Option<Mutex<File>>,
+    level: log::LevelFilter,
+    last_run: Option<Mutex<File>>,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let module = match record.target() {
+            "hyprshot_rs" => "main",
+            target => target.strip_prefix("hyprshot_rs::").unwrap_or(target),
+        };
+        if let Some(file) = &self.last_run
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = writeln!(
+                file,
+                "{} {:<5} {}: {}",
+                chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"),
+                record.level(),
+                module,
+                record.args()
+            );
+        }
+        if record.level() > self.level {
+            return;
+        }
+        let line = match self.format {
+            LogFormat::Json => serde_json::json!({
+                "time": chrono::Local::now().to_rfc3339(),
+                "level": record.level().as_str().to_lowercase(),
+                "module": module,
+                "message": record.args().to_string(),
+            })
+            .to_string(),
+            LogFormat::Text if self.file.is_some() => format!(
+                "{} {:<5} {}: {}",
+                chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"),
+                record.level(),
+                module,
+                record.args()
+            ),
+            // Plain stderr keeps the prefix warnings have always had.
+            LogFormat::Text if record.level() == log::Level::Warn => {
+                format!("warning: {}", record.args())
+            }
+            LogFormat::Text => record.args().to_string(),
+        };
+        // Logs never go to stdout, which carries --raw images and --json reports.
+        match &self.file {
+            Some(file) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(file, "{}", line);
+                }
+            }
+            None => eprintln!("{}", line),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+// The debug log of the latest run, kept for debug-bundle whatever --debug says.
+pub fn last_run_file() -> Result<PathBuf> {
+    Ok(storage::state_dir()?.join("last-run.log"))
+}
+
+fn open_last_run() -> Option<Mutex<File>> {
+    let path = last_run_file().ok()?;
+    std::fs::create_dir_all(path.parent()?).ok()?;
+    File::create(&path).ok().map(Mutex::new)
+}
+
+pub fn init(
+    debug: bool,
+    log_file: Option<&Path>,
+    format: LogFormat,
+    keep_last_run: bool,
+) -> Result<()> {
+    let file = match log_file {
+        Some(path) => Some(Mutex::new(
+            File::options()
+                .create(true)
+                .append(true)
+                .open(path)
+                .context(format!("Failed to open log file '{}'", path.display()))?,
+        )),
+        None => None,
+    };
+    let level = if debug || file.is_some() {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Warn
+    };
+    let last_run = keep_last_run.then(open_last_run).flatten();
+    log::set_max_level(match last_run {
+        Some(_) => log::LevelFilter::Debug,
+        None => level,
+    });
+    log::set_boxed_logger(Box::new(Logger {
+        format,
+        file,
+        level,
+        last_run,
+    }))
+    .map_err(|e| anyhow::anyhow!("Failed to set up logging: {}", e))
+}
@@ -1,19 +1,69 @@
 use anyhow::{Context, Result};
 use chrono::Local;
-use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::thread::sleep;
 use std::time::Duration;
 
+mod a11y;
+mod bundle;
+mod capabilities;
 mod capture;
+mod cleanup;
+mod clipboard;
+mod color;
+mod config;
+mod convert;
+mod crash;
+mod desktop;
+mod diff;
+mod encode;
+mod environment;
+mod events;
+mod fault;
+mod flash;
+mod frame;
+mod geometry;
+#[cfg(feature = "native")]
+mod hdr;
+mod history;
+mod hooks;
+mod i18n;
+mod icc;
+mod install;
+mod instance;
+mod logging;
+mod metadata;
+mod notifications;
+mod paths;
+mod picker;
+mod portal;
+mod post;
+mod protect;
+mod raster;
+mod record;
+mod remote;
+mod resize;
+mod runner;
 mod save;
+mod screencast;
+mod service;
+mod sign;
+mod storage;
+mod template;
+mod thumbnail;
+mod timing;
+mod toolbar;
+mod tui;
 mod utils;
 
 #[derive(Parser)]
 #[command(
     name = "hyprshot-rs",
-    about = "Utility to easily take screenshots in Hyprland"
+    about = "Utility to easily take screenshots in Hyprland",
+    disable_help_flag = true
 )]
 struct Args {
     #[arg(short, long, help = "Show help message")]
@@ -22,7 +72,8 @@ struct Args {
     #[arg(
         short = 'm',
         long,
-        help = "Mode: output, window, region, active, or OUTPUT_NAME"
+        value_parser = parse_mode,
+        help = "Mode: output, window, region, pixel, layer, active, or OUTPUT_NAME"
     )]
     mode: Vec<Mode>,
 
@@ -32,21 +83,339 @@ struct Args {
     #[arg(short, long, help = "Filename of the screenshot")]
     filename: Option<String>,
 
+    #[arg(
+        long,
+        value_enum,
+        help = "Image format: png, webp (needs cwebp) or jxl (needs cjxl) (default: from filename, or png)"
+    )]
+    format: Option<encode::Format>,
+
+    #[arg(long, help = "Use lossy compression for WebP and JPEG XL output")]
+    lossy: bool,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(1..=100),
+        help = "Encoding quality for WebP and JPEG XL output (1-100, 100 is lossless)"
+    )]
+    quality: Option<u8>,
+
+    #[arg(long, value_enum, help = "PNG compression level")]
+    png_compression: Option<encode::PngCompression>,
+
+    #[arg(
+        long,
+        help = "File name template: strftime plus {mode}, {class} and {title} (window mode)"
+    )]
+    filename_template: Option<String>,
+
+    #[arg(
+        long,
+        help = "Ask for the file name in the --picker menu, pre-filled with the generated one"
+    )]
+    ask_filename: bool,
+
+    #[arg(
+        long,
+        help = "Select and resolve the geometry, but only print what would be captured and saved"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Save into dated subfolders (daily: YYYY/MM/DD, monthly: YYYY/MM)"
+    )]
+    organize: Option<paths::Organize>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "What to do when the file exists (default: rename, or overwrite with --filename)"
+    )]
+    on_conflict: Option<paths::OnConflict>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Keep 10/16-bit depth instead of tone-mapping to 8-bit SDR (native backend)"
+    )]
+    hdr: Option<encode::HdrOutput>,
+
+    #[arg(
+        long,
+        help = "Convert to sRGB using the output's ICC profile instead of embedding the profile"
+    )]
+    srgb_convert: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Draw a title bar above the capture (custom: style from the [frame] config section)"
+    )]
+    frame: Option<frame::Frame>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Post-processing steps, e.g. \"resize=50%,shadow\" (overrides the config; none disables)"
+    )]
+    post: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with = "raw",
+        help = "Annotate the capture in an editor and deliver what it saves"
+    )]
+    edit_in: Option<post::Editor>,
+
+    #[arg(
+        long,
+        value_name = "TOLERANCE",
+        num_args = 0..=1,
+        default_missing_value = "0",
+        help = "Trim uniform-color borders, with an optional per-channel color tolerance"
+    )]
+    autocrop: Option<u8>,
+
+    #[arg(
+        long,
+        value_name = "PX",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Round the selection's position and size to a grid of PX pixels"
+    )]
+    snap: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "COLOR",
+        num_args = 0..=1,
+        default_missing_value = capture::DEFAULT_DIM,
+        help = "Tint the screen outside the selection while selecting (default #00000066)"
+    )]
+    dim: Option<String>,
+
+    #[arg(
+        long,
+        help = "Trim a pixel off odd widths and heights so both are even (for video encoders)"
+    )]
+    even_dims: bool,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        value_parser = resize::parse_percent,
+        help = "Downscale the image to PERCENT of its native size, e.g. 50%"
+    )]
+    resize: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "PX",
+        help = "Downscale the image to at most PX pixels wide"
+    )]
+    max_width: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Save even if a fullscreen capture comes out completely black"
+    )]
+    allow_black: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "hex",
+        help = "How pixel mode formats the picked color"
+    )]
+    color_format: color::ColorFormat,
+
+    #[arg(
+        long,
+        help = "Also offer bars, panels and other layer surfaces in window mode"
+    )]
+    include_layers: bool,
+
+    #[arg(
+        long,
+        help = "Offer mirrored outputs in the output picker instead of only their source"
+    )]
+    allow_mirrors: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "PICKER",
+        help = "How -m output and -m window pick: slurp (click) or list (choose in the --picker menu)"
+    )]
+    interactive: Option<capture::OutputPicker>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Menu used for lists and prompts (default: fzf in a terminal, then the first of rofi, wofi, fuzzel, bemenu and dmenu)"
+    )]
+    picker: Option<picker::Kind>,
+
+    #[arg(
+        long,
+        value_name = "THEME",
+        help = "Theme for the menu: rofi -theme, wofi --style or fuzzel --config"
+    )]
+    picker_theme: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        help = "Extra argument passed to the menu (repeatable)"
+    )]
+    picker_arg: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "PX",
+        conflicts_with = "shrink",
+        help = "Pad the captured window by PX pixels on every side, e.g. to include its border and shadow"
+    )]
+    grow: Option<u16>,
+
+    #[arg(
+        long,
+        value_name = "PX",
+        help = "Inset the captured window by PX pixels on every side, e.g. to cut off its border"
+    )]
+    shrink: Option<u16>,
+
+    #[arg(
+        long,
+        help = "In window mode, keep picking windows (click again to deselect, Escape to finish) and capture their bounding box"
+    )]
+    multi: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Capture the region saved as NAME under [regions] in the config file (implies -m region)"
+    )]
+    region_name: Option<String>,
+
+    #[arg(
+        long,
+        requires = "multi",
+        help = "With --multi, save each selected window as its own file"
+    )]
+    separate: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["separate", "raw"],
+        help = "After selecting, choose Save, Copy, Edit, Upload, Record or Cancel from a toolbar"
+    )]
+    toolbar: bool,
+
+    #[arg(
+        long,
+        help = "Capture even when a window from protected_classes is in the picture"
+    )]
+    force: bool,
+
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        help = "Only offer windows whose class matches PATTERN (globs separated by |)"
+    )]
+    include_class: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        help = "Hide windows whose class matches PATTERN (globs separated by |)"
+    )]
+    exclude_class: Option<String>,
+
     #[arg(short = 'D', long, help = "Delay before taking screenshot (seconds)")]
     delay: Option<u64>,
 
+    #[arg(
+        long,
+        value_name = "MS",
+        num_args = 0..=1,
+        default_missing_value = "150",
+        help = "Wait until the active window has stopped moving for MS milliseconds"
+    )]
+    wait_idle: Option<u64>,
+
+    #[arg(long, help = "Close notification popups before capturing")]
+    dismiss_notifications: bool,
+
     #[arg(long, help = "Freeze the screen on initialization")]
     freeze: bool,
 
+    #[arg(long, help = "Flash the screen when the capture is taken")]
+    flash: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Play a shutter sound file when the capture is taken"
+    )]
+    shutter_sound: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "auto",
+        help = "Capture backend (screencast: xdg-desktop-portal ScreenCast + PipeWire)"
+    )]
+    backend: Backend,
+
+    #[cfg(feature = "dev")]
+    #[arg(long, value_enum, hide = true)]
+    inject_failure: Vec<fault::Point>,
+
     #[arg(short, long, help = "Print debug information")]
     debug: bool,
 
+    #[arg(long, help = "Write debug information to this file instead of stderr")]
+    log_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "Format of debug information: text or json (one object per line)"
+    )]
+    log_format: logging::LogFormat,
+
+    #[arg(
+        long,
+        value_name = "STAGE=MS",
+        value_parser = timing::parse_budget,
+        help = "Warn when a stage (capture, encode, save, clipboard) exceeds MS milliseconds; 0 disables"
+    )]
+    stage_budget: Vec<(timing::Stage, u64)>,
+
     #[arg(short, long, help = "Don't send notification")]
     silent: bool,
 
+    #[arg(
+        long,
+        help = "Announce the finished capture to screen reader users through speech-dispatcher"
+    )]
+    a11y_announce: bool,
+
     #[arg(short, long, help = "Output raw image data to stdout")]
     raw: bool,
 
+    #[arg(
+        long,
+        conflicts_with = "raw",
+        help = "Print the path and geometry of each capture as a JSON object"
+    )]
+    json: bool,
+
     #[arg(
         short,
         long,
@@ -55,96 +424,922 @@ struct Args {
     )]
     notif_timeout: u32,
 
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Play a sound file with each notification"
+    )]
+    notify_sound: Option<PathBuf>,
+
+    #[arg(long, help = "Path to the config file")]
+    config: Option<PathBuf>,
+
+    #[arg(
+        short = 'P',
+        long,
+        help = "Use the [profile.NAME] section of the config file"
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        long,
+        help = "Save with private permissions (files 0600, directories 0700)"
+    )]
+    private: bool,
+
     #[arg(long, help = "Copy to clipboard and don't save to disk")]
     clipboard_only: bool,
 
+    #[arg(
+        long,
+        conflicts_with_all = ["clipboard_only", "primary", "clipboard_bundle"],
+        help = "Save to disk without touching the clipboard"
+    )]
+    no_clipboard: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "both",
+        help = "Also (or only) set the primary selection"
+    )]
+    primary: Option<clipboard::PrimarySelection>,
+
+    #[arg(
+        long,
+        conflicts_with = "no_clipboard",
+        help = "Clear the clipboard copy after it has been pasted once"
+    )]
+    clipboard_paste_once: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHERE",
+        num_args = 0..=1,
+        default_missing_value = "clipboard",
+        help = "Remove text, EXIF and other metadata chunks from clipboard copies (or from saved files too with 'all')"
+    )]
+    strip_metadata: Option<metadata::Strip>,
+
+    #[arg(
+        long,
+        value_name = "KEYFILE",
+        conflicts_with_all = ["clipboard_only", "raw"],
+        help = "Timestamp the saved file and write a detached signature made with KEYFILE to FILE.sig"
+    )]
+    sign: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["clipboard_only", "raw"],
+        help = "Also write a thumbnail at most SIZE pixels wide and high as NAME.thumb.png"
+    )]
+    thumbnail: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Put --thumbnail in the freedesktop thumbnail cache instead of next to the file"
+    )]
+    thumbnail_cache: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "What to do when another capture is still running (default: deny)"
+    )]
+    concurrent: Option<instance::Concurrent>,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Abort the whole run, stopping helper processes, after SECS seconds"
+    )]
+    timeout: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Copy the last N screenshots to the clipboard as a file list"
+    )]
+    clipboard_bundle: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Start the command in its own session and return without waiting for it"
+    )]
+    detach: bool,
+
     #[arg(last = true, help = "Command to open screenshot (e.g., 'mirage')")]
     command: Vec<String>,
+
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Action {
+    #[command(about = "Browse previously saved screenshots")]
+    History {
+        #[command(subcommand)]
+        action: HistoryAction,
+    },
+
+    #[command(
+        about = "Run existing images through the post steps, save, copy and command of a capture"
+    )]
+    Process {
+        #[arg(required = true, help = "Image files to process")]
+        files: Vec<PathBuf>,
+    },
+
+    #[command(about = "Re-encode existing screenshots to another format")]
+    Convert {
+        #[arg(long, value_enum, help = "Target image format")]
+        to: encode::Format,
+
+        #[arg(long, help = "Use lossy compression for WebP and JPEG XL output")]
+        lossy: bool,
+
+        #[arg(
+            long,
+            value_parser = clap::value_parser!(u8).range(1..=100),
+            help = "Encoding quality (1-100, 100 is lossless; default: --quality)"
+        )]
+        quality: Option<u8>,
+
+        #[arg(
+            short = 'j',
+            long,
+            help = "Number of files to convert in parallel (default: CPU count)"
+        )]
+        jobs: Option<usize>,
+
+        #[arg(long, help = "Keep the original files")]
+        keep: bool,
+
+        #[arg(help = "Files to convert (default: every screenshot in the output folder)")]
+        files: Vec<PathBuf>,
+    },
+
+    #[command(about = "Compare two captures and report the regions that changed")]
+    Diff {
+        #[arg(help = "Earlier capture")]
+        before: PathBuf,
+
+        #[arg(help = "Later capture")]
+        after: PathBuf,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Write a heatmap of the changes to PATH"
+        )]
+        heatmap: Option<PathBuf>,
+
+        #[arg(
+            long,
+            default_value = "16",
+            help = "Per-channel difference below which a pixel counts as unchanged"
+        )]
+        threshold: u8,
+
+        #[arg(long, help = "Print the changed regions as JSON")]
+        json: bool,
+    },
+
+    #[command(about = "Check a screenshot against the detached signature made by --sign")]
+    Verify {
+        #[arg(help = "Signed screenshot")]
+        file: PathBuf,
+
+        #[arg(help = "Signature file (default: FILE.sig)")]
+        signature: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "KEYFILE",
+            help = "Public (or private) key to check with (default: 'sign' from the config)"
+        )]
+        key: Option<PathBuf>,
+    },
+
+    #[command(about = "Record a selected region as an animated GIF, WebP or AVIF")]
+    Record {
+        #[arg(
+            value_name = "PATH",
+            required_unless_present_any = ["gif", "stop", "pause"],
+            help = "Write the recording to PATH (format from the extension)"
+        )]
+        path: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with = "path",
+            help = "Write the recording to PATH as a GIF"
+        )]
+        gif: Option<PathBuf>,
+
+        #[arg(long, help = "Animation format (default: from the extension)")]
+        record_format: Option<record::RecordFormat>,
+
+        #[arg(
+            long,
+            help = "Record a picked window and follow it when it moves or resizes"
+        )]
+        window: bool,
+
+        #[arg(
+            long,
+            default_value = "none",
+            help = "Record desktop or microphone audio (mp4 and webm only)"
+        )]
+        audio: record::AudioSource,
+
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "PulseAudio/PipeWire source to record instead of the default"
+        )]
+        audio_device: Option<String>,
+
+        #[arg(long, default_value = "15", help = "Frames per second")]
+        fps: u32,
+
+        #[arg(
+            long,
+            default_value = "30s",
+            value_parser = record::parse_duration,
+            help = "Stop recording after this long (e.g. 30s, 2m)"
+        )]
+        max_duration: Duration,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["path", "gif", "pause"],
+            help = "Stop the recording in progress and save it"
+        )]
+        stop: bool,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["path", "gif"],
+            help = "Pause or resume the recording in progress"
+        )]
+        pause: bool,
+    },
+
+    #[command(about = "Print the config, state, cache and screenshot locations in use")]
+    Paths,
+
+    #[command(about = "List the modes, backends, formats and helper programs available here")]
+    Capabilities {
+        #[arg(long, help = "Print the report as JSON")]
+        json: bool,
+    },
+
+    #[command(about = "Collect logs, config and system details into a tarball for bug reports")]
+    DebugBundle {
+        #[arg(
+            short = 'o',
+            long,
+            value_name = "PATH",
+            help = "Where to write the bundle (default: ./hyprshot-debug.tar.gz)"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[command(about = "Remove temporary files left behind by hyprshot-rs runs that crashed")]
+    CleanTemp {
+        #[arg(long, help = "List the files without removing them")]
+        dry_run: bool,
+    },
+
+    #[command(about = "Register hyprshot-rs as an \"Open With\" handler for images")]
+    InstallDesktop {
+        #[arg(long, help = "Print the desktop entry instead of installing it")]
+        print: bool,
+    },
+
+    #[command(about = "Add recommended Print-key bindings to the Hyprland config")]
+    InstallKeybinds {
+        #[arg(long, help = "Print the bindings instead of installing them")]
+        dry_run: bool,
+
+        #[arg(
+            long,
+            help = "Append to hyprland.conf instead of a sourced hyprshot-rs.conf snippet"
+        )]
+        append: bool,
+    },
+
+    #[command(about = "Pick outputs or windows, set options and capture from a terminal UI")]
+    Tui,
+
+    #[command(about = "Serve captures over D-Bus as org.hyprshot.Capture")]
+    DbusService {
+        #[arg(long, help = "Install a D-Bus activation file instead of serving")]
+        install: bool,
+    },
+
+    #[command(about = "Serve org.freedesktop.impl.portal.Screenshot for xdg-desktop-portal")]
+    PortalBackend {
+        #[arg(long, help = "Print the hyprshot.portal file instead of serving")]
+        print_portal: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum HistoryAction {
+    #[command(about = "Pick a recent screenshot with thumbnails and copy or open it")]
+    Pick {
+        #[arg(
+            short = 'n',
+            long,
+            default_value = "20",
+            help = "Number of recent screenshots to show"
+        )]
+        count: usize,
+
+        #[arg(long, help = "Open the screenshot instead of copying it")]
+        open: bool,
+    },
 }
 
 impl std::fmt::Debug for Args {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Args")
+        let mut debug = f.debug_struct("Args");
+        debug
             .field("help", &self.help)
             .field("mode", &self.mode)
             .field("output_folder", &self.output_folder)
             .field("filename", &self.filename)
+            .field("format", &self.format)
+            .field("lossy", &self.lossy)
+            .field("quality", &self.quality)
+            .field("png_compression", &self.png_compression)
+            .field("filename_template", &self.filename_template)
+            .field("ask_filename", &self.ask_filename)
+            .field("dry_run", &self.dry_run)
+            .field("organize", &self.organize)
+            .field("on_conflict", &self.on_conflict)
+            .field("hdr", &self.hdr)
+            .field("srgb_convert", &self.srgb_convert)
+            .field("frame", &self.frame)
+            .field("post", &self.post)
+            .field("edit_in", &self.edit_in)
+            .field("autocrop", &self.autocrop)
+            .field("snap", &self.snap)
+            .field("dim", &self.dim)
+            .field("even_dims", &self.even_dims)
+            .field("resize", &self.resize)
+            .field("max_width", &self.max_width)
+            .field("allow_black", &self.allow_black)
+            .field("color_format", &self.color_format)
+            .field("include_layers", &self.include_layers)
+            .field("allow_mirrors", &self.allow_mirrors)
+            .field("interactive", &self.interactive)
+            .field("picker", &self.picker)
+            .field("picker_theme", &self.picker_theme)
+            .field("picker_arg", &self.picker_arg)
+            .field("grow", &self.grow)
+            .field("shrink", &self.shrink)
+            .field("multi", &self.multi)
+            .field("separate", &self.separate)
+            .field("toolbar", &self.toolbar)
+            .field("force", &self.force)
+            .field("region_name", &self.region_name)
+            .field("include_class", &self.include_class)
+            .field("exclude_class", &self.exclude_class)
             .field("delay", &self.delay)
+            .field("wait_idle", &self.wait_idle)
+            .field("dismiss_notifications", &self.dismiss_notifications)
             .field("freeze", &self.freeze)
+            .field("flash", &self.flash)
+            .field("shutter_sound", &self.shutter_sound)
+            .field("backend", &self.backend);
+        #[cfg(feature = "dev")]
+        debug.field("inject_failure", &self.inject_failure);
+        debug
             .field("debug", &self.debug)
+            .field("log_file", &self.log_file)
+            .field("log_format", &self.log_format)
+            .field("stage_budget", &self.stage_budget)
             .field("silent", &self.silent)
+            .field("a11y_announce", &self.a11y_announce)
             .field("raw", &self.raw)
+            .field("json", &self.json)
             .field("notif_timeout", &self.notif_timeout)
+            .field("notify_sound", &self.notify_sound)
+            .field("config", &self.config)
+            .field("profile", &self.profile)
+            .field("private", &self.private)
             .field("clipboard_only", &self.clipboard_only)
+            .field("no_clipboard", &self.no_clipboard)
+            .field("primary", &self.primary)
+            .field("clipboard_paste_once", &self.clipboard_paste_once)
+            .field("strip_metadata", &self.strip_metadata)
+            .field("sign", &self.sign)
+            .field("thumbnail", &self.thumbnail)
+            .field("thumbnail_cache", &self.thumbnail_cache)
+            .field("concurrent", &self.concurrent)
+            .field("timeout", &self.timeout)
+            .field("clipboard_bundle", &self.clipboard_bundle)
             .field("command", &self.command)
+            .field("detach", &self.detach)
+            .field("action", &self.action)
             .finish()
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Backend {
+    Auto,
+    Screencast,
+}
+
 #[derive(Clone, Debug, ValueEnum)]
 enum Mode {
     Output,
     Window,
     Region,
+    Pixel,
+    Layer,
     Active,
     #[clap(skip)]
     OutputName(String),
 }
 
+impl Mode {
+    fn config_section(&self) -> Option<&'static str> {
+        match self {
+            Mode::Output => Some("output"),
+            Mode::Window => Some("window"),
+            Mode::Region => Some("region"),
+            Mode::Pixel => Some("pixel"),
+            Mode::Layer => Some("layer"),
+            Mode::Active | Mode::OutputName(_) => None,
+        }
+    }
+}
+
+fn parse_mode(s: &str) -> Result<Mode, String> {
+    Ok(Mode::from_str(s, true).unwrap_or_else(|_| Mode::OutputName(s.to_string())))
+}
+
 fn main() -> Result<()> {
+    crash::install();
     let args = Args::parse();
+    #[cfg(feature = "dev")]
+    fault::inject(args.inject_failure.clone());
 
-    if args.help || args.mode.is_empty() {
+    if args.help
+        || (args.mode.is_empty()
+            && args.region_name.is_none()
+            && args.clipboard_bundle.is_none()
+            && args.action.is_none())
+    {
         print_help();
         return Ok(());
     }
 
     let debug = args.debug;
-    let clipboard_only = args.clipboard_only;
-    let silent = args.silent;
-    let raw = args.raw;
-    let notif_timeout = args.notif_timeout;
+    // debug-bundle reports on the run before it, so it keeps that run's log.
+    let keep_last_run = !matches!(args.action, Some(Action::DebugBundle { .. }));
+    logging::init(
+        debug,
+        args.log_file.as_deref(),
+        args.log_format,
+        keep_last_run,
+    )?;
+    if !matches!(args.action, Some(Action::CleanTemp { .. })) {
+        cleanup::remove_stale();
+    }
     let freeze = args.freeze;
     let delay = args.delay.unwrap_or(0);
-    let command = if args.command.is_empty() {
-        None
-    } else {
-        Some(args.command)
-    };
 
+    let no_mode = args.mode.is_empty() && args.region_name.is_none();
     let mut option: Option<Mode> = None;
     let mut current = false;
     let mut selected_monitor: Option<String> = None;
 
     for mode in args.mode {
         match mode {
-            Mode::Output | Mode::Window | Mode::Region => option = Some(mode),
+            Mode::Output | Mode::Window | Mode::Region | Mode::Pixel | Mode::Layer => {
+                option = Some(mode)
+            }
             Mode::Active => current = true,
-            Mode::OutputName(name) => {
-                if utils::is_valid_monitor(&name)? {
-                    selected_monitor = Some(name);
-                }
+            Mode::OutputName(name) => selected_monitor = Some(name),
+        }
+    }
+    if args.region_name.is_some() {
+        match option {
+            None | Some(Mode::Region) => option = Some(Mode::Region),
+            Some(_) => {
+                return Err(anyhow::anyhow!(
+                    "--region-name can only be combined with region mode"
+                ));
             }
         }
     }
 
-    let option = option.context("A mode is required (output, region, window)")?;
+    let config = config::Config::load(args.config.as_deref())?;
+    let named_region = args
+        .region_name
+        .as_deref()
+        .map(|name| config.region(name))
+        .transpose()?;
+    let settings = config.resolve(
+        option.as_ref().and_then(Mode::config_section),
+        args.profile.as_deref(),
+    )?;
+    // Services and recordings are meant to run for a long time.
+    let long_running = matches!(
+        args.action,
+        Some(
+            Action::Record { .. }
+                | Action::Tui
+                | Action::DbusService { .. }
+                | Action::PortalBackend { .. }
+        )
+    );
+    if let Some(secs) = args.timeout.or(settings.timeout)
+        && !long_running
+    {
+        crash::watchdog(Duration::from_secs(u64::from(secs)));
+    }
+    if let Some(color) = args.dim.as_deref().or(settings.dim.as_deref()) {
+        capture::set_dim(capture::parse_dim(color)?);
+    }
+
+    let format = args
+        .format
+        .or_else(|| {
+            args.filename
+                .as_deref()
+                .and_then(|f| encode::Format::from_path(Path::new(f)))
+        })
+        .or(settings.format)
+        .unwrap_or(encode::Format::Png);
+    let (file_mode, dir_mode) = if args.private {
+        (Some(0o600), Some(0o700))
+    } else {
+        (settings.file_mode, settings.dir_mode)
+    };
+    let clipboard_only =
+        args.clipboard_only || (!args.no_clipboard && settings.clipboard_only.unwrap_or(false));
+    let command = if args.command.is_empty() {
+        settings.command
+    } else {
+        Some(args.command)
+    };
+    let output_folder = args.output_folder.or(settings.output_folder);
+    let remote = match output_folder.as_deref() {
+        Some(folder)
+            if !args
+                .filename
+                .as_deref()
+                .is_some_and(|f| Path::new(f).is_absolute()) =>
+        {
+            remote::Destination::parse(folder)?
+        }
+        _ => None,
+    };
+
+    if remote.is_some() && (args.sign.is_some() || settings.sign.is_some()) {
+        return Err(anyhow::anyhow!("--sign needs a local output folder"));
+    }
+    // The capture time is a PNG text chunk, which the other encoders drop.
+    if (args.sign.is_some() || settings.sign.is_some())
+        && (format != encode::Format::Png || args.hdr.is_some())
+        && !matches!(args.action, Some(Action::Verify { .. }))
+    {
+        return Err(anyhow::anyhow!(
+            "--sign is only supported with PNG output (no --format webp, jxl or --hdr)"
+        ));
+    }
+    // Uploads can't see what is already there, so they always replace it.
+    if remote.is_some()
+        && matches!(
+            args.on_conflict,
+            Some(paths::OnConflict::Rename | paths::OnConflict::Fail)
+        )
+    {
+        return Err(anyhow::anyhow!(
+            "--on-conflict rename and fail need a local output folder"
+        ));
+    }
+
+    let frame = match args.frame.or(config.frame) {
+        Some(frame::Frame::Macos) => Some(frame::FrameStyle::macos()),
+        Some(frame::Frame::Custom) => Some(
+            config
+                .frame_style
+                .clone()
+                .context("--frame custom needs a [frame] section in the config file")?,
+        ),
+        Some(frame::Frame::None) | None => None,
+    };
 
-    let save_dir = args
-        .output_folder
-        .unwrap_or_else(|| dirs::picture_dir().unwrap_or_else(|| PathBuf::from("~")));
-    let filename = args.filename.unwrap_or_else(|| {
-        Local::now()
-            .format("%Y-%m-%d-%H%M%S_hyprshot.png")
-            .to_string()
+    // --separate saves several files under one --filename.
+    let on_conflict = args.on_conflict.unwrap_or(match args.filename {
+        Some(_) if !args.separate => paths::OnConflict::Overwrite,
+        _ => paths::OnConflict::Rename,
     });
-    let save_fullpath = save_dir.join(&filename);
 
-    if debug && !clipboard_only {
-        eprintln!("Saving in: {}", save_fullpath.display());
+    let mut save_opts = save::SaveOptions {
+        clipboard_only,
+        no_clipboard: args.no_clipboard,
+        raw: args.raw,
+        command,
+        detach: args.detach || settings.detach.unwrap_or(false),
+        silent: args.silent || settings.silent.unwrap_or(false),
+        notif_timeout: args.notif_timeout,
+        notify_sound: args.notify_sound.as_deref().map(paths::expand),
+        primary: args.primary,
+        paste_once: args.clipboard_paste_once || settings.clipboard_paste_once.unwrap_or(false),
+        strip_metadata: args.strip_metadata.or(settings.strip_metadata),
+        sign: args.sign.or(settings.sign).map(|key| paths::expand(&key)),
+        thumbnail: args.thumbnail.or(settings.thumbnail),
+        thumbnail_cache: args.thumbnail_cache || settings.thumbnail_cache.unwrap_or(false),
+        min_free_space: settings
+            .min_free_space
+            .unwrap_or(storage::DEFAULT_MIN_FREE_SPACE),
+        low_space: settings.low_space.unwrap_or_default(),
+        flash: args.flash || settings.flash.unwrap_or(false),
+        format,
+        lossy: args.lossy,
+        quality: args.quality.or(match format {
+            encode::Format::Webp => config.webp_quality,
+            _ => config.jxl_quality,
+        }),
+        png_compression: args.png_compression.or(config.png_compression),
+        hdr: args.hdr,
+        icc_profiles: config.icc_profiles,
+        srgb_convert: args.srgb_convert,
+        frame,
+        blur: Vec::new(),
+        autocrop: args.autocrop,
+        selection: None,
+        post: post::parse(&args.post.or(settings.post).unwrap_or_default())?,
+        hooks: config.hooks,
+        remote,
+        resize: resize::Resize {
+            factor: args.resize.or(settings.resize),
+            max_width: args.max_width.or(settings.max_width),
+        },
+        budgets: timing::Budgets::new(args.stage_budget),
+        lock: RefCell::new(None),
+        file_mode,
+        dir_mode,
+        on_conflict,
+        debug,
+    };
+    // The editor comes last, so it shows the finished image.
+    if let Some(editor) = args.edit_in {
+        save_opts.post.push(Box::new(post::Edit(editor)));
+    }
+
+    let picker = picker::Picker {
+        kind: args.picker.or(settings.picker),
+        theme: args.picker_theme.or(settings.picker_theme),
+        args: if args.picker_arg.is_empty() {
+            settings.picker_args.unwrap_or_default()
+        } else {
+            args.picker_arg
+        },
+    };
+
+    if let Some(action) = args.action {
+        return run_action(
+            action,
+            output_folder.as_deref(),
+            args.config.as_deref(),
+            &save_opts,
+            &picker,
+        );
+    }
+
+    if no_mode {
+        if let Some(count) = args.clipboard_bundle {
+            save::copy_bundle(&history::recent(count)?, &save_opts)?;
+        }
+        return Ok(());
+    }
+
+    let option = option.context("A mode is required (output, region, window, pixel, layer)")?;
+    if let Some(mode) = option.config_section() {
+        save_opts.hooks.set("HYPRSHOT_MODE", mode);
+    }
+
+    let desktop_environment = environment::DesktopEnvironment::detect();
+    let screencast = args.backend == Backend::Screencast;
+    let desktop_backend = screencast || desktop_environment.has_desktop_backend();
+    if !desktop_environment.uses_hyprctl()
+        && !desktop_backend
+        && !matches!(option, Mode::Region | Mode::Pixel)
+    {
+        return Err(anyhow::anyhow!(
+            "Only region mode is supported on {:?}",
+            desktop_environment
+        ));
+    }
+
+    if desktop_backend && matches!(option, Mode::Pixel | Mode::Layer) {
+        return Err(anyhow::anyhow!(
+            "pixel and layer modes are only supported with the grim backend"
+        ));
+    }
+    if desktop_backend && args.autocrop.is_some() {
+        return Err(anyhow::anyhow!(
+            "--autocrop is only supported with the grim and native backends"
+        ));
+    }
+    let snap = args.snap.or(settings.snap);
+    if desktop_backend && (snap.is_some() || args.even_dims) {
+        return Err(anyhow::anyhow!(
+            "--snap and --even-dims are only supported with the grim and native backends"
+        ));
+    }
+    if desktop_backend && (args.resize.is_some() || args.max_width.is_some()) {
+        return Err(anyhow::anyhow!(
+            "--resize and --max-width are only supported with the grim backend"
+        ));
+    }
+
+    // The desktop backends pick the area themselves and never report its geometry.
+    if desktop_backend && args.json {
+        return Err(anyhow::anyhow!(
+            "--json is only supported with the grim and native backends"
+        ));
     }
 
-    let hyprpicker_pid = if freeze && Command::new("hyprpicker").output().is_ok() {
+    if desktop_backend && named_region.is_some() {
+        return Err(anyhow::anyhow!(
+            "--region-name is only supported with the grim and native backends"
+        ));
+    }
+
+    if desktop_backend && args.toolbar {
+        return Err(anyhow::anyhow!(
+            "--toolbar is only supported with the grim and native backends"
+        ));
+    }
+    // A toolbar from the config steps aside for the backends and flags it can't work with.
+    let toolbar = (args.toolbar || settings.toolbar.unwrap_or(false))
+        && !desktop_backend
+        && !args.raw
+        && !args.separate
+        && !args.dry_run;
+
+    let protected_classes = match settings.protected_classes {
+        Some(_) if args.force => Vec::new(),
+        Some(classes) => classes,
+        None => Vec::new(),
+    };
+    let protected_action = settings.protected_action.unwrap_or_default();
+    if desktop_backend && !protected_classes.is_empty() {
+        log::warn!(
+            "protected_classes can't be checked with the desktop backends; pass --force to silence this"
+        );
+    }
+
+    let flash_color = settings
+        .flash_color
+        .clone()
+        .unwrap_or_else(|| flash::DEFAULT_COLOR.to_string());
+    let shutter_sound = args
+        .shutter_sound
+        .or(settings.shutter_sound.clone())
+        .map(|sound| paths::expand(&sound));
+
+    if desktop_backend && save_opts.frame.is_some() {
+        return Err(anyhow::anyhow!(
+            "--frame is only supported with the grim and native backends"
+        ));
+    }
+    if (save_opts.frame.is_some() || !save_opts.post.is_empty())
+        && save_opts.hdr == Some(encode::HdrOutput::Avif)
+    {
+        return Err(anyhow::anyhow!(
+            "--frame and post steps cannot be combined with --hdr avif"
+        ));
+    }
+    if save_opts.hdr.is_some() && (desktop_backend || !cfg!(feature = "native")) {
+        return Err(anyhow::anyhow!(
+            "--hdr is only supported with the native backend"
+        ));
+    }
+
+    let extension = args
+        .hdr
+        .map_or(format.extension(), encode::HdrOutput::extension);
+    let now = Local::now();
+    let subfolder = args
+        .organize
+        .or(settings.organize)
+        .and_then(|organize| organize.subfolder(&now));
+    let filename_template = args.filename_template.or(settings.filename_template);
+    let mode_name = option.config_section().unwrap_or_default();
+    // The toolbar changes save_opts after the selection, so the path doesn't borrow it.
+    let remote = save_opts.remote.clone();
+    // Where a capture goes before checking for an existing file, so a
+    // recording can swap the extension first.
+    let plan_path =
+        |window: Option<&capture::WindowInfo>, clipboard_only: bool| -> Result<PathBuf> {
+            let template = match (&filename_template, window) {
+                (Some(template), _) => template.as_str(),
+                (None, Some(_)) => template::WINDOW_DEFAULT,
+                (None, None) => template::DEFAULT,
+            };
+            let values = template::Values {
+                mode: mode_name,
+                class: window.map(|w| w.class.as_str()),
+                title: window.map(|w| w.title.as_str()),
+            };
+            let default_filename = format!(
+                "{}.{}",
+                template::render(template, &now, &values)?,
+                extension
+            );
+            let filename = if args.ask_filename && !clipboard_only {
+                let answer = paths::ask_filename(
+                    args.filename.as_deref().unwrap_or(&default_filename),
+                    &picker,
+                )?;
+                Some(match Path::new(&answer).extension() {
+                    Some(_) => answer,
+                    None => format!("{}.{}", answer, extension),
+                })
+            } else {
+                args.filename.clone()
+            };
+            Ok(match &remote {
+                Some(remote) => {
+                    let filename = filename.as_deref().unwrap_or(&default_filename);
+                    PathBuf::from(remote.url(&match &subfolder {
+                        Some(subfolder) => format!("{}/{}", subfolder, filename),
+                        None => filename.to_string(),
+                    }))
+                }
+                None => {
+                    let organized_folder = subfolder.as_ref().map(|subfolder| {
+                        output_folder
+                            .as_deref()
+                            .map(paths::expand)
+                            .unwrap_or_else(paths::default_save_dir)
+                            .join(subfolder)
+                    });
+                    paths::resolve_save_path(
+                        organized_folder.as_deref().or(output_folder.as_deref()),
+                        filename.as_deref().map(Path::new),
+                        &default_filename,
+                    )
+                }
+            })
+        };
+    let settle_path = |save_fullpath: PathBuf, clipboard_only: bool| -> Result<PathBuf> {
+        let save_fullpath = if clipboard_only || remote.is_some() {
+            save_fullpath
+        } else {
+            paths::resolve_conflict(save_fullpath, on_conflict)?
+        };
+        if !clipboard_only {
+            log::debug!("Saving in: {}", save_fullpath.display());
+        }
+        Ok(save_fullpath)
+    };
+    let resolve_path =
+        |window: Option<&capture::WindowInfo>, clipboard_only: bool| -> Result<PathBuf> {
+            settle_path(plan_path(window, clipboard_only)?, clipboard_only)
+        };
+
+    if let Some(idle_ms) = args.wait_idle {
+        if !desktop_environment.uses_hyprctl() {
+            return Err(anyhow::anyhow!("--wait-idle is only supported on Hyprland"));
+        }
+        capture::wait_idle(idle_ms)?;
+    }
+
+    let lock = (!args.dry_run)
+        .then(|| instance::acquire(args.concurrent.or(settings.concurrent).unwrap_or_default()))
+        .transpose()?;
+    save_opts.lock.replace(lock);
+
+    // Leftover notifications are not worth losing the capture over.
+    if args.dismiss_notifications
+        && !args.dry_run
+        && let Err(e) = notifications::dismiss_all()
+    {
+        log::warn!("{:#}", e);
+    }
+
+    let hyprpicker_pid = if freeze && !args.dry_run && Command::new("hyprpicker").output().is_ok() {
         let pid = Command::new("hyprpicker")
             .args(["-r", "-z"])
             .spawn()
@@ -156,43 +1351,308 @@ fn main() -> Result<()> {
         None
     };
 
-    if delay > 0 {
+    if delay > 0 && !args.dry_run {
         sleep(Duration::from_secs(delay));
     }
 
-    let geometry = match option {
+    if matches!(option, Mode::Pixel) {
+        if args.dry_run {
+            println!("Target: colour picker ({:?})", args.color_format);
+            return Ok(());
+        }
+        color::pick(args.color_format, &save_opts)?;
+        return kill_hyprpicker(hyprpicker_pid);
+    }
+
+    if desktop_backend {
+        let target = match option {
+            Mode::Output if current => desktop::Target::Output { active: true },
+            Mode::Output => match selected_monitor {
+                Some(name) => desktop::Target::NamedOutput(name),
+                None => desktop::Target::Output { active: false },
+            },
+            Mode::Window => desktop::Target::Window { active: current },
+            Mode::Region => desktop::Target::Region,
+            _ => unreachable!(),
+        };
+        let save_fullpath = resolve_path(None, clipboard_only)?;
+        if args.dry_run {
+            let backend = match desktop_environment {
+                _ if screencast => "screencast portal",
+                environment::DesktopEnvironment::Kde => "spectacle",
+                environment::DesktopEnvironment::Gnome => "gnome-shell",
+                _ => "maim",
+            };
+            save::dry_run(
+                backend,
+                &format!("{:?}", target),
+                &save_fullpath,
+                &save_opts,
+            );
+            return Ok(());
+        }
+        save_opts.hooks.run(hooks::Stage::PreCapture)?;
+        let delivery = match desktop_environment {
+            _ if screencast => {
+                screencast::save_geometry_with_screencast(&target, &save_fullpath, &save_opts)?
+            }
+            environment::DesktopEnvironment::Kde => {
+                desktop::save_geometry_with_kde(&target, &save_fullpath, &save_opts)?
+            }
+            environment::DesktopEnvironment::Gnome => {
+                desktop::save_geometry_with_gnome(&target, &save_fullpath, &save_opts)?
+            }
+            _ => desktop::save_geometry_with_x11(&target, &save_fullpath, &save_opts)?,
+        };
+        log::debug!("Delivered {:?}", delivery);
+        let save_fullpath = delivery.path;
+        // GNOME flashes by itself; the screencast portal on Hyprland can use the shader.
+        let flash = save_opts.flash && desktop_environment.uses_hyprctl();
+        feedback(
+            flash.then_some(flash_color.as_str()),
+            shutter_sound.as_deref(),
+        );
+        finish(
+            &save_fullpath,
+            &save_opts,
+            args.clipboard_bundle,
+            hyprpicker_pid,
+        )?;
+        if args.a11y_announce {
+            a11y::announce(&a11y::capture_message(None, save_opts.clipboard_only));
+        }
+        return Ok(());
+    }
+
+    let selected_monitor = match selected_monitor {
+        Some(name) if utils::is_valid_monitor(&name)? => Some(name),
+        _ => None,
+    };
+
+    let window_filter = utils::WindowFilter {
+        include_class: args.include_class.or(settings.include_class),
+        exclude_class: args.exclude_class.or(settings.exclude_class),
+    };
+
+    fault::check(fault::Point::Selection)?;
+    let interactive = args
+        .interactive
+        .or(settings.interactive)
+        .unwrap_or_default();
+    let targets = match option {
         Mode::Output => {
-            if current {
-                capture::grab_active_output(debug)?
+            let geometry = if current {
+                capture::grab_active_output()?
             } else if let Some(monitor) = selected_monitor {
-                capture::grab_selected_output(&monitor, debug)?
+                capture::grab_selected_output(&monitor)?
             } else {
-                capture::grab_output(debug)?
-            }
+                capture::grab_output(interactive, args.allow_mirrors, &picker)?
+            };
+            vec![(geometry, None)]
         }
-        Mode::Region => capture::grab_region(debug)?,
+        Mode::Region => match named_region {
+            Some(region) => vec![(region.to_string(), None)],
+            None => vec![(capture::grab_region()?, None)],
+        },
+        Mode::Layer => vec![(capture::grab_layer(&window_filter, &picker)?, None)],
         Mode::Window => {
-            let geo = if current {
-                capture::grab_active_window(debug)?
+            let picked = if current {
+                vec![capture::grab_active_window()?]
+            } else if args.multi {
+                capture::grab_windows(args.include_layers, &window_filter)?
+            } else if interactive == capture::OutputPicker::List {
+                vec![capture::pick_window_from_list(
+                    args.include_layers,
+                    &window_filter,
+                    &picker,
+                )?]
             } else {
-                capture::grab_window(debug)?
+                vec![capture::grab_window(args.include_layers, &window_filter)?]
             };
-            utils::trim(&geo, debug)?
+            let padding = match (args.grow, args.shrink) {
+                (Some(grow), _) => grow as i32,
+                (_, Some(shrink)) => -(shrink as i32),
+                _ => 0,
+            };
+            let mut targets = Vec::new();
+            for geo in picked {
+                let window = capture::window_info(&geo)?;
+                targets.push((utils::trim(&geo, padding)?, window));
+            }
+            if targets.len() > 1 && !args.separate {
+                let geometries: Vec<String> = targets.into_iter().map(|(geo, _)| geo).collect();
+                vec![(capture::bounding_box(&geometries)?, None)]
+            } else {
+                targets
+            }
         }
         _ => unreachable!(),
     };
 
-    save::save_geometry(
-        &geometry,
-        &save_fullpath,
-        clipboard_only,
-        raw,
-        command,
-        silent,
-        notif_timeout,
-        debug,
-    )?;
+    for (geometry, window) in targets {
+        let geometry = match snap {
+            Some(grid) => {
+                let snapped = geometry::Region::parse(&geometry)?.snap(grid as i32);
+                log::debug!("Snapped {} to {} (grid {})", geometry, snapped, grid);
+                snapped.to_string()
+            }
+            None => geometry,
+        };
+        let geometry = if args.even_dims {
+            utils::even_dims(&geometry)?
+        } else {
+            geometry
+        };
+        let protected = protect::find(&geometry, &protected_classes)?;
+        if let (Some(window), protect::Action::Refuse) = (protected.first(), protected_action) {
+            return Err(anyhow::anyhow!(
+                "Capture includes protected window {} ({}); pass --force to capture anyway",
+                window.class,
+                window.title
+            ));
+        }
+        save_opts.blur = protected.into_iter().map(|window| window.region).collect();
+        let selection = geometry::Region::parse(&geometry)?;
+        log::debug!("Selection: {}", save::describe_selection(&selection));
+        save_opts.selection = Some(selection);
+        let action = toolbar
+            .then(|| toolbar::ask(&geometry, &picker))
+            .transpose()?;
+        match action {
+            Some(toolbar::Action::Cancel) => {
+                log::debug!("Toolbar cancelled");
+                break;
+            }
+            Some(toolbar::Action::Record) if !save_opts.blur.is_empty() => {
+                return Err(anyhow::anyhow!(
+                    "Protected windows can't be blurred in recordings; pass --force to record anyway"
+                ));
+            }
+            Some(toolbar::Action::Record) => {
+                kill_hyprpicker(hyprpicker_pid)?;
+                let output = settle_path(
+                    plan_path(window.as_ref(), false)?.with_extension("mp4"),
+                    false,
+                )?;
+                let opts = record::RecordOptions {
+                    format: record::RecordFormat::Mp4,
+                    window: false,
+                    fps: 15,
+                    max_duration: Duration::from_secs(30),
+                    audio: record::AudioSource::None,
+                    audio_device: None,
+                    region: Some(geometry),
+                };
+                // Recordings are exempt from --timeout, wherever they start.
+                crash::disarm();
+                return record::record(&output, &opts, &save_opts);
+            }
+            Some(action) => toolbar::apply(action, settings.editor.as_deref(), &mut save_opts),
+            None => {}
+        }
+        let upload = match action {
+            Some(toolbar::Action::Upload) => {
+                Some(toolbar::destination(settings.upload.as_deref())?)
+            }
+            _ => None,
+        };
+        let save_fullpath = resolve_path(window.as_ref(), save_opts.clipboard_only)?;
+        save_opts.hooks.set("HYPRSHOT_GEOMETRY", geometry.as_str());
+        if args.dry_run {
+            save::dry_run(save::backend(), &geometry, &save_fullpath, &save_opts);
+            continue;
+        }
+        save_opts.hooks.run(hooks::Stage::PreCapture)?;
+        if !args.allow_black {
+            capture::ensure_not_black(&geometry)?;
+        }
+
+        let delivery = save::save_geometry(&geometry, &save_fullpath, &save_opts)?;
+        let save_fullpath = delivery.path.clone();
+        feedback(
+            save_opts.flash.then_some(flash_color.as_str()),
+            shutter_sound.as_deref(),
+        );
+
+        finish(&save_fullpath, &save_opts, args.clipboard_bundle, None)?;
+        if args.json {
+            print_capture(
+                &save_fullpath,
+                &selection,
+                window.as_ref(),
+                &delivery,
+                &save_opts,
+            );
+        }
+        if args.a11y_announce {
+            a11y::announce(&a11y::capture_message(
+                window.as_ref(),
+                save_opts.clipboard_only,
+            ));
+        }
+        if let Some(destination) = &upload {
+            toolbar::upload(&save_fullpath, destination, &save_opts)?;
+        }
+    }
+
+    kill_hyprpicker(hyprpicker_pid)
+}
+
+fn print_capture(
+    save_fullpath: &Path,
+    selection: &geometry::Region,
+    window: Option<&capture::WindowInfo>,
+    delivery: &save::Delivery,
+    save_opts: &save::SaveOptions,
+) {
+    let path = (!save_opts.clipboard_only).then(|| save_fullpath.display().to_string());
+    println!(
+        "{}",
+        serde_json::json!({
+            "path": path,
+            "geometry": selection.to_string(),
+            "x": selection.x,
+            "y": selection.y,
+            "width": selection.width,
+            "height": selection.height,
+            "window": window.map(|window| &window.class),
+            "clipboard": !save_opts.no_clipboard,
+            "size": delivery.saved,
+            "captured_size": delivery.captured,
+            "saved_percent": delivery.saved_percent(),
+        })
+    );
+}
+
+// Runs once the picture is taken, so the flash can't end up in it.
+fn feedback(flash_color: Option<&str>, shutter_sound: Option<&Path>) {
+    if let Some(sound) = shutter_sound {
+        notifications::play_sound(sound);
+    }
+    if let Some(color) = flash_color
+        && let Err(e) = flash::show(color)
+    {
+        log::warn!("{:#}", e);
+    }
+}
+
+fn finish(
+    save_fullpath: &Path,
+    save_opts: &save::SaveOptions,
+    clipboard_bundle: Option<usize>,
+    hyprpicker_pid: Option<u32>,
+) -> Result<()> {
+    if !save_opts.clipboard_only && !save_opts.raw && save_opts.remote.is_none() {
+        history::record(save_fullpath)?;
+    }
+    if let Some(count) = clipboard_bundle {
+        save::copy_bundle(&history::recent(count)?, save_opts)?;
+    }
+
+    kill_hyprpicker(hyprpicker_pid)
+}
 
+fn kill_hyprpicker(hyprpicker_pid: Option<u32>) -> Result<()> {
     if let Some(pid) = hyprpicker_pid {
         Command::new("kill")
             .arg(pid.to_string())
@@ -203,10 +1663,207 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+fn run_action(
+    action: Action,
+    output_folder: Option<&Path>,
+    config: Option<&Path>,
+    save_opts: &save::SaveOptions,
+    picker: &picker::Picker,
+) -> Result<()> {
+    match action {
+        Action::History {
+            action: HistoryAction::Pick { count, open },
+        } => {
+            let Some(selected) = history::pick(count, picker)? else {
+                return Ok(());
+            };
+            if open {
+                Command::new("xdg-open")
+                    .arg(&selected)
+                    .spawn()
+                    .context("Failed to run xdg-open")?;
+                return Ok(());
+            }
+            copy_existing(&selected, save_opts)
+        }
+        Action::Process { files } => {
+            for file in &files {
+                let filename = format!(
+                    "{}.{}",
+                    file.file_stem().unwrap_or_default().to_string_lossy(),
+                    save_opts.format.extension()
+                );
+                // The original is left alone; the result is saved like a new capture.
+                let save_fullpath = match &save_opts.remote {
+                    Some(remote) => PathBuf::from(remote.url(&filename)),
+                    None => paths::resolve_conflict(
+                        paths::resolve_save_path(output_folder, None, &filename),
+                        paths::OnConflict::Rename,
+                    )?,
+                };
+                let delivery = save::deliver_file(file, &save_fullpath, save_opts)?;
+                if !save_opts.clipboard_only && !save_opts.raw && save_opts.remote.is_none() {
+                    history::record(&delivery.path)?;
+                }
+            }
+            Ok(())
+        }
+        Action::Convert {
+            to,
+            lossy,
+            quality,
+            jobs,
+            keep,
+            files,
+        } => {
+            let folder = output_folder
+                .map(paths::expand)
+                .unwrap_or_else(paths::default_save_dir);
+            let opts = convert::ConvertOptions {
+                format: to,
+                lossy,
+                quality: quality.or(save_opts.quality),
+                jobs: jobs.unwrap_or_else(|| {
+                    std::thread::available_parallelism()
+                        .map(|n| n.get())
+                        .unwrap_or(1)
+                }),
+                keep,
+                file_mode: save_opts.file_mode,
+            };
+            convert::run(files, &folder, &opts)
+        }
+        Action::Diff {
+            before,
+            after,
+            heatmap,
+            threshold,
+            json,
+        } => diff::run(&before, &after, heatmap.as_deref(), threshold, json),
+        Action::Verify {
+            file,
+            signature,
+            key,
+        } => {
+            let key = key
+                .map(|key| paths::expand(&key))
+                .or(save_opts.sign.clone())
+                .context("verify needs --key or 'sign' set to a key file in the config")?;
+            sign::verify(&file, signature.as_deref(), &key)
+        }
+        Action::Record {
+            path,
+            gif,
+            record_format,
+            window,
+            audio,
+            audio_device,
+            fps,
+            max_duration,
+            stop,
+            pause,
+        } => {
+            if stop || pause {
+                return record::signal_recording(stop);
+            }
+            let (output, format) = match (gif, path) {
+                (Some(gif), _) => (gif, record_format.unwrap_or(record::RecordFormat::Gif)),
+                (None, Some(path)) => {
+                    let format = record_format
+                        .or_else(|| record::RecordFormat::from_path(&path))
+                        .context(format!(
+                            "Can't tell the animation format of '{}'; use a .gif, .webp, .avif, .mp4 or .webm extension or --record-format",
+                            path.display()
+                        ))?;
+                    (path, format)
+                }
+                (None, None) => unreachable!("clap requires PATH, --gif, --stop or --pause"),
+            };
+            let opts = record::RecordOptions {
+                format,
+                window,
+                fps,
+                max_duration,
+                audio,
+                audio_device,
+                region: None,
+            };
+            record::record(&output, &opts, save_opts)
+        }
+        Action::Paths => {
+            let screenshots = output_folder
+                .map(|folder| paths::absolute(paths::expand(folder)))
+                .unwrap_or_else(paths::default_save_dir);
+            storage::print_paths(config, &screenshots)
+        }
+        Action::Capabilities { json } => {
+            let modes = Mode::value_variants()
+                .iter()
+                .filter_map(|mode| mode.to_possible_value())
+                .map(|mode| mode.get_name().to_string())
+                .collect();
+            capabilities::print(modes, json)
+        }
+        Action::DebugBundle { output } => bundle::create(output.as_deref(), config).map(|_| ()),
+        Action::CleanTemp { dry_run } => cleanup::clean_temp(dry_run),
+        Action::Tui => tui::run(output_folder, config),
+        Action::InstallDesktop { print } => install::install_desktop(print),
+        Action::InstallKeybinds { dry_run, append } => install::install_keybinds(dry_run, append),
+        Action::DbusService { install } => {
+            if install {
+                install::install_dbus_service()
+            } else {
+                service::run(output_folder, config, save_opts.format)
+            }
+        }
+        Action::PortalBackend { print_portal } => {
+            if print_portal {
+                print!("{}", portal::portal_file());
+                Ok(())
+            } else {
+                portal::run(output_folder, config, save_opts.format)
+            }
+        }
+    }
+}
+
+fn copy_existing(path: &Path, save_opts: &save::SaveOptions) -> Result<()> {
+    let data = std::fs::read(path).context(format!(
+        "Failed to open screenshot file '{}'",
+        path.display()
+    ))?;
+    let data = match save_opts.strip_metadata {
+        Some(_) if data.starts_with(metadata::PNG_SIGNATURE) => metadata::strip(&data)?,
+        _ => data,
+    };
+    clipboard::copy(
+        &data,
+        encode::mime_for_path(path),
+        save_opts.primary,
+        save_opts.paste_once,
+    )?;
+    if !save_opts.silent {
+        save::notify(
+            i18n::tr("Screenshot copied"),
+            &i18n::trf(
+                "Image <i>{path}</i> copied to {target}.",
+                &[
+                    ("path", &path.display().to_string()),
+                    ("target", clipboard::description(save_opts.primary)),
+                ],
+            ),
+            Some(path),
+            save_opts,
+        );
+    }
+    Ok(())
+}
+
 fn print_help() {
     println!(
         r#"
 Usage: hyprshot-rs [options ..] [-m [mode] ..] -- [command]
+       hyprshot-rs [options ..] <subcommand>
 
 Hyprshot-rs is an utility to easily take screenshot in Hyprland using your mouse.
 
@@ -216,25 +1873,150 @@ Examples:
   capture a window                      `hyprshot-rs -m window`
   capture active window to clipboard    `hyprshot-rs -m window -m active --clipboard-only`
   capture selected monitor              `hyprshot-rs -m output -m DP-1`
+  capture a region saved in the config  `hyprshot-rs --region-name demo`
 
 Options:
   -h, --help                show help message
-  -m, --mode                one of: output, window, region, active, OUTPUT_NAME
-  -o, --output-folder       directory in which to save screenshot
+  -m, --mode                one of: output, window, region, pixel, layer, active, OUTPUT_NAME
+  -o, --output-folder       directory in which to save screenshot, or a remote folder:
+                            sftp://[USER@]HOST[:PORT]/PATH, or WebDAV as https://, http://, webdav://
   -f, --filename            the file name of the resulting screenshot
+  --filename-template TPL   name for generated files: strftime codes plus {{mode}}, {{class}} and
+                            {{title}} (default: %Y-%m-%d-%H%M%S_hyprshot, or
+                            %Y-%m-%d-%H%M%S_{{class}}_{{title}} for windows)
+  --ask-filename            ask for the file name in the --picker menu before saving
+  --organize daily|monthly  save into dated subfolders (YYYY/MM/DD or YYYY/MM); none disables
+  --on-conflict POLICY      rename (append _1, _2, ...), overwrite or fail when the file exists
+                            (default: rename, or overwrite when --filename is given)
+  --format                  image format: png, webp (needs cwebp) or jxl (needs cjxl from libjxl)
+                            (default: from the filename extension, or png)
+  --lossy                   use lossy compression for WebP and JPEG XL (lossless by default)
+  --quality N               WebP and JPEG XL quality from 1 to 100 (100 is lossless; overrides --lossy)
+  --png-compression LEVEL   fast, default or best (grim and native backends)
+  --hdr png16|avif          keep 10/16-bit captures as a 16-bit PNG or a 10-bit PQ AVIF
+                            instead of tone-mapping to 8-bit (native backend)
+  --frame STYLE             draw a title bar with the window title above the capture:
+                            macos, none or custom (colors from the [frame] config section)
+  --post STEPS              comma-separated post-processing steps run before saving:
+                            autocrop[=TOL], resize=PERCENT%|PX, shadow[=RADIUS], watermark=TEXT,
+                            optimize (overrides `post` from the config; `none` disables)
+  --edit-in EDITOR          annotate the capture in satty, swappy or gimp, then save and copy
+                            what the editor saved
+  --srgb-convert            convert to sRGB with the output's ICC profile instead of embedding it
+  --autocrop [TOLERANCE]    trim uniform-color borders (optionally allowing TOLERANCE per channel)
+  --snap PX                 round the selection's position and size to a PX pixel grid
+  --dim [COLOR]             tint the screen outside the selection while selecting (#rrggbb[aa])
+  --even-dims               trim a pixel off odd widths and heights (recordings always do this)
+  --resize PERCENT          downscale the image to PERCENT of its native size (e.g. 50%)
+  --max-width PX            downscale the image to at most PX pixels wide
+  --allow-black             don't fail when a fullscreen capture is completely black
+  --color-format hex|rgb    format of the color copied in pixel mode (default: hex)
+  --include-layers          also offer bars, panels and notifications when selecting a window
+  --allow-mirrors           offer mirrored outputs too when picking an output
+  --interactive slurp|list  pick the output or window by clicking on it (slurp, default) or from a list
+                            in the --picker menu
+  --picker MENU             rofi, wofi, fuzzel, bemenu, dmenu or fzf for lists and prompts
+                            (default: fzf in a terminal, else the first one installed)
+  --picker-theme THEME      menu theme: rofi -theme, wofi --style or fuzzel --config
+  --picker-arg ARG          extra argument for the menu (repeatable)
+  --grow PX, --shrink PX    pad or inset the captured window by PX pixels (clipped to its monitor)
+  --multi                   pick several windows (click again to deselect, Escape to finish)
+                            and capture their bounding box
+  --separate                with --multi, save every picked window to its own file
+  --toolbar                 after selecting, pick Save, Copy, Edit, Upload, Record or Cancel
+  --force                   capture even if a window listed in protected_classes is visible
+  --region-name NAME        capture the region saved as NAME under [regions] in the config
+  --include-class PATTERN   only offer windows (and layers) whose class matches PATTERN
+  --exclude-class PATTERN   hide windows (and layers) whose class matches PATTERN
+                            (PATTERN: case-insensitive globs separated by |, e.g. "ksnip|flameshot")
   -D, --delay               how long to delay taking the screenshot after selection (seconds)
+  --wait-idle [MS]          wait until the active window has stopped moving for MS ms (default 150)
+  --dismiss-notifications   close notification popups (mako, dunst, swaync, fnott) before capturing
   -z, --freeze              freeze the screen on initialization
+  --flash                   flash the screen when capturing (flash_color in the config)
+  --shutter-sound PATH      play a sound file when the capture is taken
+  --backend BACKEND         auto or screencast (pick an output or window in the
+                            xdg-desktop-portal dialog and grab a PipeWire frame)
   -d, --debug               print debug information
+  --log-file PATH           append debug information to PATH instead of stderr
+  --log-format text|json    format of debug information (json: one object per line)
+  --dry-run                 select and resolve the geometry, then print the backend, geometry,
+                            path and post steps instead of capturing
+  --stage-budget STAGE=MS   warn when capture, encode, save or clipboard takes longer than MS
+                            (defaults: capture 1000, encode 500, save 500, clipboard 500; 0 disables)
   -s, --silent              don't send notification when screenshot is saved
+  --a11y-announce           speak "Screenshot of firefox window saved" (or similar) via spd-say
   -r, --raw                 output raw image data to stdout
+  --json                    print {{"path", "geometry", "x", "y", "width", "height", ...}} per capture
   -t, --notif-timeout       notification timeout in milliseconds (default 5000)
+  --notify-sound PATH       play a sound (e.g. an .ogg file) with each notification
+  --config PATH             config file (default: $XDG_CONFIG_HOME/hyprshot-rs/config.toml)
+  -P, --profile NAME        apply the [profile.NAME] section of the config file
+  --private                 save with permissions 0600 (files) and 0700 (new directories)
   --clipboard-only          copy screenshot to clipboard and don't save image in disk
+  --no-clipboard            save the screenshot without copying it to the clipboard
+  --primary [both|only]     also (or only) set the primary selection for middle-click paste
+  --clipboard-paste-once    offer the clipboard copy for a single paste only
+  --strip-metadata [clipboard|all]
+                            drop text, EXIF and timestamp chunks from clipboard copies (and saved files)
+  --sign KEYFILE            embed the capture time and sign the saved file with openssl to FILE.sig
+  --thumbnail SIZE          also write a SIZE pixel thumbnail as NAME.thumb.png
+  --thumbnail-cache         put the thumbnail in the freedesktop thumbnail cache instead
+  --concurrent POLICY       when another capture is running: deny (default), queue to wait for
+                            it, or cancel-previous to close its selection
+  --timeout SECS            abort the whole run after SECS seconds, stopping helper processes
+  --clipboard-bundle N      copy the last N screenshots to the clipboard as a text/uri-list
+  --detach                  start -- [command] in its own session instead of waiting for it
   -- [command]              open screenshot with a command of your choosing. e.g. hyprshot-rs -m window -- mirage
+                            {{file}}, {{dir}}, {{geometry}} and {{mode}} in its arguments are filled in;
+                            without them the path is appended
+
+Subcommands:
+  history pick [-n N] [--open]   pick one of the last N screenshots in the --picker menu
+                                 (with thumbnails in rofi and wofi)
+                                 and copy it to the clipboard, or open it with --open
+  process FILE..                 run existing images through --post, --frame, --edit-in and -o
+                                 like a capture: save a copy, copy it and run -- [command]
+  convert --to FORMAT [--lossy] [--quality N] [-j N] [--keep] [FILE..]
+                                 re-encode screenshots (default: all in the output folder) and
+                                 update the history
+  diff BEFORE AFTER [--heatmap PATH] [--threshold N] [--json]
+                                 report changed regions between two captures, optionally
+                                 rendering a heatmap of the change intensity
+  verify FILE [SIG] [--key KEYFILE]
+                                 check FILE against the signature written by --sign (default:
+                                 FILE.sig) and print the capture time it carries
+  record PATH|--gif PATH [--record-format FORMAT] [--window] [--audio SOURCE]
+         [--audio-device NAME] [--fps N] [--max-duration DURATION]
+                                 record a selected region (or a window, following it) with
+                                 wf-recorder and encode it as an optimized GIF, animated WebP
+                                 or AVIF, or an MP4/WebM video with desktop or mic audio
+                                 (default: 15 fps, up to 30s)
+  record --stop|--pause          stop and save, or pause/resume, the recording in progress
+  paths                          print the config, state, cache and screenshot locations
+  capabilities [--json]          list supported modes, backends, compiled-in formats, Wayland
+                                 protocols and installed helper programs
+  debug-bundle [-o PATH]         collect hyprctl output, environment, tools, config and the last
+                                 run's log (secrets scrubbed) into hyprshot-debug.tar.gz
+  clean-temp [--dry-run]         remove temporary files left behind by crashed runs
+  install-desktop [--print]      install a .desktop entry so file managers can "Open With" hyprshot-rs
+  install-keybinds [--dry-run] [--append]
+                                 add Print-key bindings for region, window and output modes to
+                                 ~/.config/hypr/hyprshot-rs.conf (sourced from hyprland.conf)
+  tui                            list outputs and windows with their details in the terminal,
+                                 toggle clipboard, format and delay, and capture the selection
+  dbus-service [--install]       serve CaptureRegion/CaptureWindow/CaptureOutput as
+                                 org.hyprshot.Capture, or install a D-Bus activation file
+  portal-backend [--print-portal]
+                                 act as the xdg-desktop-portal Screenshot backend, or print the
+                                 hyprshot.portal file that registers it
 
 Modes:
   output        take screenshot of an entire monitor
   window        take screenshot of an open window
   region        take screenshot of selected region
+  layer         take screenshot of a bar, launcher or other layer surface picked from a menu
+  pixel         pick the color of a single pixel and copy it as #RRGGBB or rgb()
   active        take screenshot of active window|output
                 (you must use --mode again with the intended selection)
   OUTPUT_NAME   take screenshot of output with OUTPUT_NAME
@@ -0,0 +1,180 @@
+use anyhow::{Context, Result};
+
+pub const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// Chunks a stripped PNG keeps: the image itself, transparency and colour space.
+const KEPT_CHUNKS: &[&[u8; 4]] = &[
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", b"tRNS", b"iCCP", b"sRGB", b"gAMA", b"cHRM",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Strip {
+    Clipboard,
+    All,
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// Stored (uncompressed) deflate blocks; profiles are a few KiB at most.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        out.push(blocks.peek().is_none() as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 12);
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc32(&out[4..]).to_be_bytes());
+    out
+}
+
+// Splits a PNG into (type, whole chunk) pairs.
+fn chunks<'a>(png: &'a [u8], purpose: &str) -> Result<Vec<(&'a [u8], &'a [u8])>> {
+    if !png.starts_with(PNG_SIGNATURE) {
+        return Err(anyhow::anyhow!("Can only {} in PNG images", purpose));
+    }
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos < png.len() {
+        let header = png.get(pos..pos + 8).context("Truncated PNG chunk")?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let end = pos + 12 + len;
+        let data = png.get(pos..end).context("Truncated PNG chunk")?;
+        chunks.push((&header[4..8], data));
+        pos = end;
+    }
+    Ok(chunks)
+}
+
+pub fn embed_icc(png: &[u8], profile: &[u8]) -> Result<Vec<u8>> {
+    let chunks = chunks(png, "embed ICC profiles")?;
+    let mut iccp = b"ICC profile\0\0".to_vec();
+    iccp.extend_from_slice(&zlib_stored(profile));
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    for (kind, data) in chunks {
+        // A profile replaces any colour space the encoder already declared.
+        if !matches!(kind, b"iCCP" | b"sRGB" | b"gAMA" | b"cHRM") {
+            out.extend_from_slice(data);
+        }
+        if kind == b"IHDR" {
+            out.extend_from_slice(&chunk(b"iCCP", &iccp));
+        }
+    }
+    Ok(out)
+}
+
+// Drops text (tEXt, zTXt, iTXt), EXIF, timestamps and any other ancillary
+// chunk, so nothing but the pixels and their colour space is shared.
+pub fn strip(png: &[u8]) -> Result<Vec<u8>> {
+    let mut out = PNG_SIGNATURE.to_vec();
+    for (kind, data) in chunks(png, "strip metadata")? {
+        if KEPT_CHUNKS.iter().any(|kept| kept.as_slice() == kind) {
+            out.extend_from_slice(data);
+        } else {
+            log::debug!("Stripping {} chunk", String::from_utf8_lossy(kind));
+        }
+    }
+    Ok(out)
+}
+
+// Adds a tEXt chunk before IEND; the keyword and text must be Latin-1.
+pub fn embed_text(png: &[u8], keyword: &str, text: &str) -> Result<Vec<u8>> {
+    let mut tag = keyword.as_bytes().to_vec();
+    tag.push(0);
+    tag.extend_from_slice(text.as_bytes());
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    for (kind, data) in chunks(png, "embed text")? {
+        if kind == b"IEND" {
+            out.extend_from_slice(&chunk(b"tEXt", &tag));
+        }
+        out.extend_from_slice(data);
+    }
+    Ok(out)
+}
+
+pub fn text(png: &[u8], keyword: &str) -> Option<String> {
+    chunks(png, "read text")
+        .ok()?
+        .into_iter()
+        .find_map(|(kind, data)| {
+            let body = &data[8..data.len() - 4];
+            let (key, text) = body.split_at(body.iter().position(|b| *b == 0)?);
+            (kind == b"tEXt" && key == keyword.as_bytes())
+                .then(|| String::from_utf8_lossy(&text[1..]).into_owned())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_keeps_pixels_and_colour_space() {
+        let mut png = PNG_SIGNATURE.to_vec();
+        for (kind, data) in [
+            (b"IHDR", &[0u8; 13][..]),
+            (b"tEXt", b"date:create\x002026-10-14"),
+            (b"eXIf", b"MM\x00\x2a"),
+            (b"IDAT", b"pixels"),
+            (b"tIME", &[0u8; 7]),
+            (b"IEND", b""),
+        ] {
+            png.extend_from_slice(&chunk(kind, data));
+        }
+        let tagged = embed_icc(&png, b"profile").unwrap();
+
+        let stripped = strip(&tagged).unwrap();
+        let kinds: Vec<&[u8]> = chunks(&stripped, "test")
+            .unwrap()
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .collect();
+        assert_eq!(kinds, [&b"IHDR"[..], b"iCCP", b"IDAT", b"IEND"]);
+        assert!(strip(b"GIF89a").is_err());
+
+        let dated = embed_text(&stripped, "Creation Time", "2026-10-14T12:00:00+02:00").unwrap();
+        assert_eq!(
+            text(&dated, "Creation Time").as_deref(),
+            Some("2026-10-14T12:00:00+02:00")
+        );
+        assert_eq!(text(&stripped, "Creation Time"), None);
+    }
+}
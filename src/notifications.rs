@@ -0,0 +1,77 @@
+use crate::runner;
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+
+fn server_name() -> Result<String> {
+    let conn =
+        zbus::blocking::Connection::session().context("Failed to connect to session D-Bus")?;
+    let notifications = zbus::blocking::Proxy::new(
+        &conn,
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        "org.freedesktop.Notifications",
+    )
+    .context("Failed to create org.freedesktop.Notifications proxy")?;
+    let (name, _vendor, _version, _spec): (String, String, String, String) = notifications
+        .call("GetServerInformation", &())
+        .context("Failed to query the notification daemon")?;
+    Ok(name)
+}
+
+fn dismiss_command(server: &str) -> Option<(&'static str, &'static [&'static str])> {
+    let server = server.to_ascii_lowercase();
+    if server.contains("mako") {
+        Some(("makoctl", &["dismiss", "--all"]))
+    } else if server.contains("dunst") {
+        Some(("dunstctl", &["close-all"]))
+    } else if server.contains("swaync") || server.contains("swaynotificationcenter") {
+        Some(("swaync-client", &["--close-all"]))
+    } else if server.contains("fnott") {
+        Some(("fnottctl", &["dismiss"]))
+    } else {
+        None
+    }
+}
+
+pub fn dismiss_all() -> Result<()> {
+    let server = server_name()?;
+    let (program, args) = dismiss_command(&server).context(format!(
+        "Don't know how to dismiss notifications of '{}' (supported: mako, dunst, swaync, fnott)",
+        server
+    ))?;
+    log::debug!(
+        "Dismissing {} notifications with {} {}",
+        server,
+        program,
+        args.join(" ")
+    );
+    runner::checked(program, args, None)
+        .context(format!("{} failed to dismiss notifications", program))?;
+    sleep(Duration::from_millis(100));
+    Ok(())
+}
+
+pub fn play_sound(path: &Path) {
+    for player in ["pw-play", "paplay"] {
+        match Command::new(player)
+            .arg(path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(_) => {
+                log::debug!("Playing {} with {}", path.display(), player);
+                return;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                log::warn!("failed to run {}: {}", player, e);
+                return;
+            }
+        }
+    }
+    log::warn!("no audio player found to play notification sound (install pw-play or paplay)");
+}
@@ -0,0 +1,288 @@
+use crate::i18n;
+use crate::picker::Picker;
+use anyhow::{Context, Result};
+use std::fs::{File, create_dir_all};
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+
+pub fn expand(path: &Path) -> PathBuf {
+    let Some(raw) = path.to_str() else {
+        return path.to_path_buf();
+    };
+
+    let mut expanded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    if let Some(after_tilde) = rest.strip_prefix('~')
+        && (after_tilde.is_empty() || after_tilde.starts_with('/'))
+        && let Some(home) = dirs::home_dir()
+    {
+        expanded.push_str(&home.to_string_lossy());
+        rest = after_tilde;
+    }
+
+    while let Some(index) = rest.find('$') {
+        expanded.push_str(&rest[..index]);
+        let after = &rest[index + 1..];
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+
+        match (name.is_empty(), std::env::var(name)) {
+            (false, Ok(value)) => expanded.push_str(&value),
+            _ => {
+                expanded.push('$');
+                rest = after;
+                continue;
+            }
+        }
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    PathBuf::from(expanded)
+}
+
+pub fn absolute(path: PathBuf) -> PathBuf {
+    if path.is_absolute() {
+        return path;
+    }
+    std::env::current_dir()
+        .map(|cwd| cwd.join(&path))
+        .unwrap_or(path)
+}
+
+pub fn default_save_dir() -> PathBuf {
+    dirs::picture_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+pub fn resolve_save_path(
+    output_folder: Option<&Path>,
+    filename: Option<&Path>,
+    default_filename: &str,
+) -> PathBuf {
+    let save_dir = absolute(output_folder.map(expand).unwrap_or_else(default_save_dir));
+
+    let Some(filename) = filename else {
+        return save_dir.join(default_filename);
+    };
+    let filename = expand(filename);
+    let is_dir = filename
+        .to_str()
+        .is_some_and(|f| f.ends_with(MAIN_SEPARATOR))
+        || filename.is_dir();
+
+    let path = if filename.is_absolute() {
+        filename
+    } else {
+        save_dir.join(filename)
+    };
+    if is_dir {
+        path.join(default_filename)
+    } else {
+        path
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Organize {
+    None,
+    Daily,
+    Monthly,
+}
+
+impl Organize {
+    pub fn subfolder(self, now: &chrono::DateTime<chrono::Local>) -> Option<String> {
+        match self {
+            Organize::None => None,
+            Organize::Daily => Some(now.format("%Y/%m/%d").to_string()),
+            Organize::Monthly => Some(now.format("%Y/%m").to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnConflict {
+    Rename,
+    Overwrite,
+    Fail,
+}
+
+fn already_exists(path: &Path) -> anyhow::Error {
+    anyhow::anyhow!(
+        "'{}' already exists (use --on-conflict rename or overwrite)",
+        path.display()
+    )
+}
+
+pub fn resolve_conflict(path: PathBuf, policy: OnConflict) -> Result<PathBuf> {
+    if !path.exists() {
+        return Ok(path);
+    }
+    match policy {
+        OnConflict::Overwrite => Ok(path),
+        OnConflict::Fail => Err(already_exists(&path)),
+        OnConflict::Rename => {
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let extension = path
+                .extension()
+                .map(|ext| format!(".{}", ext.to_string_lossy()))
+                .unwrap_or_default();
+            (1..)
+                .map(|n| path.with_file_name(format!("{}_{}{}", stem, n, extension)))
+                .find(|candidate| !candidate.exists())
+                .context("No free file name left")
+        }
+    }
+}
+
+// The name resolve_conflict started from: `shot_2.png` came from `shot.png`
+// when that one exists.
+fn original_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let base = stem
+        .rsplit_once('_')
+        .filter(|(base, n)| !base.is_empty() && n.parse::<u32>().is_ok_and(|n| n > 0))
+        .map(|(base, _)| match path.extension() {
+            Some(ext) => format!("{}.{}", base, ext.to_string_lossy()),
+            None => base.to_string(),
+        });
+    base.map(|base| path.with_file_name(base))
+        .filter(|original| original.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+// Opens the file a screenshot is written to. resolve_conflict picked the name
+// before the capture, and another capture may have taken it since, so rename
+// and fail only ever create a file that doesn't exist yet. A rename starts
+// over from the original name, so a taken `shot_1.png` becomes `shot_2.png`.
+pub fn create(path: &Path, policy: OnConflict, file_mode: Option<u32>) -> Result<(PathBuf, File)> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let original = original_name(path);
+    let mut path = path.to_path_buf();
+    loop {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true);
+        match policy {
+            OnConflict::Overwrite => options.create(true).truncate(true),
+            OnConflict::Rename | OnConflict::Fail => options.create_new(true),
+        };
+        if let Some(mode) = file_mode {
+            options.mode(mode);
+        }
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                log::debug!("'{}' was taken before it was written", path.display());
+                path = resolve_conflict(original.clone(), policy)?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+pub fn ask_filename(suggested: &str, picker: &Picker) -> Result<String> {
+    let answer = picker
+        .ask(i18n::tr("Save as"), suggested)?
+        .context(i18n::tr("Filename prompt cancelled"))?;
+    log::debug!("Filename from prompt: {}", answer);
+    // Let the menu disappear before the screen is captured.
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    Ok(answer)
+}
+
+pub fn ensure_parent(path: &Path, dir_mode: Option<u32>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+    let missing: Vec<PathBuf> = parent
+        .ancestors()
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .map(Path::to_path_buf)
+        .collect();
+
+    create_dir_all(parent).context(format!(
+        "Failed to create screenshot directory '{}'",
+        parent.display()
+    ))?;
+    if let Some(mode) = dir_mode {
+        for dir in missing {
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(mode))
+                .context(format!("Failed to set permissions on '{}'", dir.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hyprshot-rs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn rename_takes_the_next_free_suffix() {
+        let dir = scratch("rename");
+        let shot = dir.join("shot.png");
+        let name = |path: &Path| path.file_name().unwrap().to_string_lossy().into_owned();
+
+        // Each create claims its name, so a capture that resolved the same
+        // name a moment earlier moves on to the next one.
+        let (first, _) = create(&shot, OnConflict::Rename, None).unwrap();
+        let (second, _) = create(&shot, OnConflict::Rename, None).unwrap();
+        let (third, _) = create(&shot, OnConflict::Rename, None).unwrap();
+        assert_eq!(
+            [name(&first), name(&second), name(&third)],
+            ["shot.png", "shot_1.png", "shot_2.png"]
+        );
+        assert_eq!(
+            name(&resolve_conflict(shot.clone(), OnConflict::Rename).unwrap()),
+            "shot_3.png"
+        );
+
+        // Two captures resolved shot_3.png and one of them wrote it first.
+        let planned = resolve_conflict(shot.clone(), OnConflict::Rename).unwrap();
+        create(&planned, OnConflict::Rename, None).unwrap();
+        let (late, _) = create(&planned, OnConflict::Rename, None).unwrap();
+        assert_eq!(name(&late), "shot_4.png");
+
+        // A name that only looks suffixed keeps its own stem.
+        let scan = dir.join("scan_1.png");
+        create(&scan, OnConflict::Rename, None).unwrap();
+        let (again, _) = create(&scan, OnConflict::Rename, None).unwrap();
+        assert_eq!(name(&again), "scan_1_1.png");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fail_and_overwrite_keep_the_name() {
+        let dir = scratch("fail");
+        let shot = dir.join("shot.png");
+        std::fs::write(&shot, "old").unwrap();
+
+        assert!(create(&shot, OnConflict::Fail, None).is_err());
+        assert!(resolve_conflict(shot.clone(), OnConflict::Fail).is_err());
+        let (path, _) = create(&shot, OnConflict::Overwrite, None).unwrap();
+        assert_eq!(path, shot);
+        assert_eq!(std::fs::read(&shot).unwrap(), b"");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,380 @@
+use crate::i18n;
+use crate::runner;
+use anyhow::{Context, Result};
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Kind {
+    Rofi,
+    Wofi,
+    Fuzzel,
+    Bemenu,
+    Dmenu,
+    Fzf,
+}
+
+// A dmenu-style program: items on stdin, the chosen line (or typed text) on stdout.
+pub trait Menu {
+    fn program(&self) -> &'static str;
+    fn args(&self, prompt: &str) -> Vec<String>;
+
+    fn theme_args(&self, _theme: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    // Extra arguments that pre-fill the input when asking for free text.
+    fn prefill_args(&self, _text: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    // Extra arguments for menus that only show image files when asked to.
+    fn image_args(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn row(&self, label: &str, _icon: Option<&str>) -> String {
+        label.to_string()
+    }
+
+    // The item label in a chosen row, for menus that print the row back as is.
+    fn label<'a>(&self, answer: &'a str) -> &'a str {
+        answer
+    }
+
+    fn can_ask(&self) -> bool {
+        true
+    }
+}
+
+struct Rofi;
+struct Wofi;
+struct Fuzzel;
+struct Bemenu;
+struct Dmenu;
+struct Fzf;
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+impl Menu for Rofi {
+    fn program(&self) -> &'static str {
+        "rofi"
+    }
+
+    fn args(&self, prompt: &str) -> Vec<String> {
+        strings(&["-dmenu", "-i", "-show-icons", "-p", prompt])
+    }
+
+    fn theme_args(&self, theme: &str) -> Vec<String> {
+        strings(&["-theme", theme])
+    }
+
+    fn prefill_args(&self, text: &str) -> Vec<String> {
+        strings(&["-filter", text])
+    }
+
+    fn row(&self, label: &str, icon: Option<&str>) -> String {
+        match icon {
+            Some(icon) => format!("{}\0icon\x1f{}", label, icon),
+            None => label.to_string(),
+        }
+    }
+}
+
+impl Menu for Wofi {
+    fn program(&self) -> &'static str {
+        "wofi"
+    }
+
+    fn args(&self, prompt: &str) -> Vec<String> {
+        strings(&["--dmenu", "-i", "--prompt", prompt])
+    }
+
+    fn theme_args(&self, theme: &str) -> Vec<String> {
+        strings(&["--style", theme])
+    }
+
+    fn image_args(&self) -> Vec<String> {
+        strings(&["--allow-images"])
+    }
+
+    // wofi loads images from files only, not icon names.
+    fn row(&self, label: &str, icon: Option<&str>) -> String {
+        match icon {
+            Some(icon) if icon.starts_with('/') => format!("img:{}:text:{}", icon, label),
+            _ => label.to_string(),
+        }
+    }
+
+    fn label<'a>(&self, answer: &'a str) -> &'a str {
+        match answer.strip_prefix("img:") {
+            Some(row) => row.split_once(":text:").map_or(answer, |(_, label)| label),
+            None => answer,
+        }
+    }
+}
+
+impl Menu for Fuzzel {
+    fn program(&self) -> &'static str {
+        "fuzzel"
+    }
+
+    fn args(&self, prompt: &str) -> Vec<String> {
+        strings(&["--dmenu", "--prompt", &format!("{}: ", prompt)])
+    }
+
+    fn theme_args(&self, theme: &str) -> Vec<String> {
+        strings(&["--config", theme])
+    }
+}
+
+impl Menu for Bemenu {
+    fn program(&self) -> &'static str {
+        "bemenu"
+    }
+
+    fn args(&self, prompt: &str) -> Vec<String> {
+        strings(&["-i", "-p", prompt])
+    }
+}
+
+impl Menu for Dmenu {
+    fn program(&self) -> &'static str {
+        "dmenu"
+    }
+
+    fn args(&self, prompt: &str) -> Vec<String> {
+        strings(&["-i", "-p", prompt])
+    }
+}
+
+impl Menu for Fzf {
+    fn program(&self) -> &'static str {
+        "fzf"
+    }
+
+    fn args(&self, prompt: &str) -> Vec<String> {
+        strings(&["--prompt", &format!("{}> ", prompt)])
+    }
+
+    // fzf only returns typed text that matches an item.
+    fn can_ask(&self) -> bool {
+        false
+    }
+}
+
+fn menu(kind: Kind) -> Box<dyn Menu> {
+    match kind {
+        Kind::Rofi => Box::new(Rofi),
+        Kind::Wofi => Box::new(Wofi),
+        Kind::Fuzzel => Box::new(Fuzzel),
+        Kind::Bemenu => Box::new(Bemenu),
+        Kind::Dmenu => Box::new(Dmenu),
+        Kind::Fzf => Box::new(Fzf),
+    }
+}
+
+pub struct Item {
+    pub label: String,
+    pub icon: Option<PathBuf>,
+}
+
+impl Item {
+    pub fn new(label: impl Into<String>) -> Item {
+        Item {
+            label: label.into(),
+            icon: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Picker {
+    pub kind: Option<Kind>,
+    pub theme: Option<String>,
+    pub args: Vec<String>,
+}
+
+impl Picker {
+    // Without --picker, fzf is tried first when running in a terminal, then the
+    // graphical menus in order.
+    fn candidates(&self) -> Vec<Kind> {
+        match self.kind {
+            Some(kind) => vec![kind],
+            None => {
+                let graphical = [
+                    Kind::Rofi,
+                    Kind::Wofi,
+                    Kind::Fuzzel,
+                    Kind::Bemenu,
+                    Kind::Dmenu,
+                ];
+                let terminal = std::io::stdin().is_terminal().then_some(Kind::Fzf);
+                terminal.into_iter().chain(graphical).collect()
+            }
+        }
+    }
+
+    // Runs the first available menu; None means the menu was dismissed.
+    fn run(
+        &self,
+        prompt: &str,
+        input: impl Fn(&dyn Menu) -> String,
+        prefill: Option<&str>,
+        images: bool,
+    ) -> Result<Option<(Kind, String)>> {
+        for kind in self.candidates() {
+            let menu = menu(kind);
+            if prefill.is_some() && !menu.can_ask() {
+                continue;
+            }
+            let mut args = menu.args(prompt);
+            if let Some(theme) = &self.theme {
+                args.extend(menu.theme_args(theme));
+            }
+            if let Some(text) = prefill {
+                args.extend(menu.prefill_args(text));
+            }
+            if images {
+                args.extend(menu.image_args());
+            }
+            args.extend(self.args.iter().cloned());
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+            let input = input(menu.as_ref());
+            let output = match runner::output(menu.program(), &args, Some(input.as_bytes())) {
+                Ok(output) => output,
+                Err(e) if self.kind.is_none() && e.kind() == std::io::ErrorKind::NotFound => {
+                    log::debug!("{} is not available", menu.program());
+                    continue;
+                }
+                Err(e) => return Err(e).context(format!("Failed to run {}", menu.program())),
+            };
+            let answer = String::from_utf8_lossy(&output.stdout);
+            let answer = menu.label(answer.trim()).to_string();
+            if !output.status.success() || answer.is_empty() {
+                log::debug!("{} was dismissed", menu.program());
+                return Ok(None);
+            }
+            return Ok(Some((kind, answer)));
+        }
+        Err(anyhow::anyhow!(
+            "{}",
+            i18n::tr(
+                "No menu program found (install rofi, wofi, fuzzel, bemenu or dmenu, or pass --picker)"
+            )
+        ))
+    }
+
+    pub fn choose(&self, prompt: &str, items: &[Item]) -> Result<Option<usize>> {
+        let rows = |menu: &dyn Menu| {
+            items
+                .iter()
+                .map(|item| {
+                    let icon = item.icon.as_ref().map(|icon| icon.to_string_lossy());
+                    menu.row(&item.label, icon.as_deref())
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let images = items
+            .iter()
+            .any(|item| item.icon.as_ref().is_some_and(|icon| icon.is_absolute()));
+        let Some((kind, answer)) = self.run(prompt, rows, None, images)? else {
+            return Ok(None);
+        };
+        items
+            .iter()
+            .position(|item| item.label == answer)
+            .map(Some)
+            .context(format!(
+                "{} returned an unknown entry '{}'",
+                menu(kind).program(),
+                answer
+            ))
+    }
+
+    pub fn ask(&self, prompt: &str, suggested: &str) -> Result<Option<String>> {
+        Ok(self
+            .run(
+                prompt,
+                |_| format!("{}\n", suggested),
+                Some(suggested),
+                false,
+            )?
+            .map(|(_, answer)| answer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::Fake;
+
+    fn items() -> Vec<Item> {
+        vec![Item::new("DP-1"), Item::new("HDMI-A-1")]
+    }
+
+    #[test]
+    fn choose_falls_back_to_the_next_menu() {
+        let fake = Fake::default()
+            .reply("wofi --dmenu -i --prompt Output", "HDMI-A-1\n")
+            .install();
+        let picker = Picker::default();
+        assert_eq!(picker.choose("Output", &items()).unwrap(), Some(1));
+        assert_eq!(
+            fake.stdin_of("wofi --dmenu -i --prompt Output").unwrap(),
+            "DP-1\nHDMI-A-1"
+        );
+    }
+
+    #[test]
+    fn explicit_picker_passes_theme_and_args() {
+        Fake::default()
+            .reply("bemenu -i -p Output --fn Inter", "DP-1")
+            .fail("rofi -dmenu -i -show-icons -p Output -theme nord", 1)
+            .install();
+        let bemenu = Picker {
+            kind: Some(Kind::Bemenu),
+            theme: Some("nord".to_string()),
+            args: vec!["--fn".to_string(), "Inter".to_string()],
+        };
+        assert_eq!(bemenu.choose("Output", &items()).unwrap(), Some(0));
+
+        let rofi = Picker {
+            kind: Some(Kind::Rofi),
+            theme: Some("nord".to_string()),
+            args: Vec::new(),
+        };
+        assert_eq!(rofi.choose("Output", &items()).unwrap(), None);
+
+        let wofi = Picker {
+            kind: Some(Kind::Wofi),
+            ..Picker::default()
+        };
+        let shots = [Item {
+            label: "a.png".to_string(),
+            icon: Some(PathBuf::from("/shots/a.thumb.png")),
+        }];
+        let fake = Fake::default()
+            .reply(
+                "wofi --dmenu -i --prompt History --allow-images",
+                "img:/shots/a.thumb.png:text:a.png\n",
+            )
+            .install();
+        assert_eq!(wofi.choose("History", &shots).unwrap(), Some(0));
+        assert_eq!(
+            fake.stdin_of("wofi --dmenu -i --prompt History --allow-images")
+                .unwrap(),
+            "img:/shots/a.thumb.png:text:a.png"
+        );
+
+        let missing = Picker {
+            kind: Some(Kind::Dmenu),
+            ..Picker::default()
+        };
+        assert!(missing.choose("Output", &items()).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::clipboard;
+use crate::color;
+use crate::encode::Format;
+use crate::service::{self, Capture};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+
+const BUS_NAME: &str = "org.freedesktop.impl.portal.desktop.hyprshot";
+const OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+
+const RESPONSE_SUCCESS: u32 = 0;
+const RESPONSE_CANCELLED: u32 = 1;
+const RESPONSE_OTHER: u32 = 2;
+
+type Results = HashMap<String, OwnedValue>;
+
+struct Portal {
+    capture: Capture,
+}
+
+fn result(key: &str, value: Value<'_>) -> zbus::fdo::Result<Results> {
+    let value = OwnedValue::try_from(value).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+    Ok(HashMap::from([(key.to_string(), value)]))
+}
+
+#[zbus::interface(name = "org.freedesktop.impl.portal.Screenshot")]
+impl Portal {
+    #[zbus(out_args("response", "results"))]
+    fn screenshot(
+        &self,
+        _handle: ObjectPath<'_>,
+        app_id: &str,
+        _parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::fdo::Result<(u32, Results)> {
+        let interactive = options
+            .get("interactive")
+            .and_then(|v| bool::try_from(v).ok())
+            .unwrap_or(false);
+        log::debug!(
+            "Portal screenshot for '{}' (interactive: {})",
+            app_id,
+            interactive
+        );
+        let modes: &[&str] = if interactive {
+            &["region"]
+        } else {
+            &["output", "active"]
+        };
+        match self.capture.capture(modes) {
+            Ok(path) => Ok((
+                RESPONSE_SUCCESS,
+                result("uri", Value::from(clipboard::file_uri(&path)))?,
+            )),
+            Err(e) => {
+                log::debug!("Portal screenshot failed: {}", e);
+                Ok((RESPONSE_CANCELLED, Results::new()))
+            }
+        }
+    }
+
+    #[zbus(out_args("response", "results"))]
+    fn pick_color(
+        &self,
+        _handle: ObjectPath<'_>,
+        app_id: &str,
+        _parent_window: &str,
+        _options: HashMap<&str, Value<'_>>,
+    ) -> zbus::fdo::Result<(u32, Results)> {
+        log::debug!("Portal color pick for '{}'", app_id);
+        match color::pick_color() {
+            Ok([r, g, b]) => {
+                let rgb = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+                Ok((RESPONSE_SUCCESS, result("color", Value::from(rgb))?))
+            }
+            Err(e) => {
+                log::debug!("Portal color pick failed: {:#}", e);
+                Ok((RESPONSE_OTHER, Results::new()))
+            }
+        }
+    }
+
+    #[zbus(property, name = "version")]
+    fn version(&self) -> u32 {
+        2
+    }
+}
+
+pub fn portal_file() -> String {
+    format!(
+        "[portal]\n\
+         DBusName={}\n\
+         Interfaces=org.freedesktop.impl.portal.Screenshot;\n\
+         UseIn=Hyprland\n",
+        BUS_NAME
+    )
+}
+
+pub fn run(output_folder: Option<&Path>, config: Option<&Path>, format: Format) -> Result<()> {
+    let portal = Portal {
+        capture: Capture::new(output_folder, config, format)?,
+    };
+    service::serve(BUS_NAME, OBJECT_PATH, portal)
+}
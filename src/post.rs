@@ -0,0 +1,338 @@
+use crate::crash;
+use crate::resize;
+use crate::storage;
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::process::Command;
+
+pub trait Step: std::fmt::Debug {
+    fn name(&self) -> &'static str;
+    fn apply(&self, png: &[u8], debug: bool) -> Result<Vec<u8>>;
+}
+
+pub fn magick(png: &[u8], before: &[String], after: &[String], purpose: &str) -> Result<Vec<u8>> {
+    let input = storage::temp_file(&format!("post-{}.png", std::process::id()))?;
+    std::fs::write(&input, png).context("Failed to write temporary PNG for magick")?;
+    let output = Command::new("magick")
+        .args(before)
+        .arg(&input)
+        .args(after)
+        .arg("png:-")
+        .output()
+        .context(format!(
+            "Failed to run magick (ImageMagick is needed for {})",
+            purpose
+        ));
+    let _ = std::fs::remove_file(&input);
+    let output = output?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "magick failed to apply {}: {}",
+            purpose,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[derive(Debug)]
+struct Autocrop {
+    tolerance: u8,
+}
+
+impl Step for Autocrop {
+    fn name(&self) -> &'static str {
+        "autocrop"
+    }
+
+    fn apply(&self, png: &[u8], _debug: bool) -> Result<Vec<u8>> {
+        autocrop(png, self.tolerance)
+    }
+}
+
+// Trims borders that match the corner color within `tolerance` per channel.
+pub fn autocrop(png: &[u8], tolerance: u8) -> Result<Vec<u8>> {
+    let fuzz = format!("{:.2}%", tolerance as f64 * 100.0 / 255.0);
+    magick(
+        png,
+        &[],
+        &args(&["-fuzz", &fuzz, "-trim", "+repage"]),
+        "autocrop",
+    )
+}
+
+#[derive(Debug)]
+enum Scale {
+    Percent(f64),
+    MaxWidth(u32),
+}
+
+impl Step for Scale {
+    fn name(&self) -> &'static str {
+        "resize"
+    }
+
+    fn apply(&self, png: &[u8], _debug: bool) -> Result<Vec<u8>> {
+        let geometry = match self {
+            Scale::Percent(factor) => format!("{}%", factor * 100.0),
+            Scale::MaxWidth(width) => format!("{}x>", width),
+        };
+        magick(png, &[], &args(&["-resize", &geometry]), "resize")
+    }
+}
+
+#[derive(Debug)]
+struct Shadow {
+    radius: u32,
+}
+
+impl Step for Shadow {
+    fn name(&self) -> &'static str {
+        "shadow"
+    }
+
+    fn apply(&self, png: &[u8], _debug: bool) -> Result<Vec<u8>> {
+        let shadow = format!("60x{}+0+{}", self.radius, self.radius / 2);
+        let after = args(&[
+            "(",
+            "+clone",
+            "-background",
+            "black",
+            "-shadow",
+            &shadow,
+            ")",
+            "+swap",
+            "-background",
+            "none",
+            "-layers",
+            "merge",
+            "+repage",
+        ]);
+        magick(png, &[], &after, "shadow")
+    }
+}
+
+#[derive(Debug)]
+struct Watermark {
+    text: String,
+}
+
+impl Step for Watermark {
+    fn name(&self) -> &'static str {
+        "watermark"
+    }
+
+    fn apply(&self, png: &[u8], _debug: bool) -> Result<Vec<u8>> {
+        let after = args(&[
+            "-gravity",
+            "southeast",
+            "-fill",
+            "rgba(255,255,255,0.7)",
+            "-stroke",
+            "rgba(0,0,0,0.4)",
+            "-pointsize",
+            "16",
+            "-annotate",
+            "+12+10",
+            &escape_text(&self.text),
+        ]);
+        magick(png, &[], &after, "watermark")
+    }
+}
+
+#[derive(Debug)]
+struct Optimize;
+
+impl Step for Optimize {
+    fn name(&self) -> &'static str {
+        "optimize"
+    }
+
+    fn apply(&self, png: &[u8], debug: bool) -> Result<Vec<u8>> {
+        use std::io::Write;
+        use std::process::Stdio;
+
+        let mut child = Command::new("oxipng")
+            .args(["-o", "2", "--strip", "safe", "--stdout", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(if debug {
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            })
+            .spawn()
+            .context("Failed to run oxipng (needed for the optimize step)")?;
+        let mut stdin = child.stdin.take().context("Failed to open oxipng stdin")?;
+        let output = std::thread::scope(|scope| {
+            scope.spawn(move || stdin.write_all(png));
+            child.wait_with_output()
+        })
+        .context("Failed to run oxipng")?;
+        if !output.status.success() || output.stdout.is_empty() {
+            return Err(anyhow::anyhow!("oxipng failed to optimize screenshot"));
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Editor {
+    Satty,
+    Swappy,
+    Gimp,
+}
+
+// Opens the capture in an annotation tool and carries on with what it saved.
+// swappy reads and writes the image over pipes; satty only reads stdin and
+// gimp takes neither, so their result goes through a temporary file.
+#[derive(Debug)]
+pub struct Edit(pub Editor);
+
+impl Step for Edit {
+    fn name(&self) -> &'static str {
+        "edit"
+    }
+
+    fn apply(&self, png: &[u8], debug: bool) -> Result<Vec<u8>> {
+        use std::io::Write;
+        use std::process::Stdio;
+
+        let temp = storage::temp_file(&format!("post-{}-edit.png", std::process::id()))?;
+        let temp_arg = temp.to_string_lossy().into_owned();
+        let (program, args, piped) = match self.0 {
+            Editor::Swappy => ("swappy", args(&["-f", "-", "-o", "-"]), true),
+            Editor::Satty => (
+                "satty",
+                args(&[
+                    "--filename",
+                    "-",
+                    "--output-filename",
+                    &temp_arg,
+                    "--early-exit",
+                ]),
+                true,
+            ),
+            Editor::Gimp => {
+                std::fs::write(&temp, png).context("Failed to write temporary PNG for gimp")?;
+                // -n: a new instance, so this waits even when gimp is already open.
+                ("gimp", args(&["-n", &temp_arg]), false)
+            }
+        };
+        log::debug!("Editing in {} {}", program, args.join(" "));
+        crash::disarm();
+        let mut child = Command::new(program)
+            .args(&args)
+            .stdin(if piped { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(if debug {
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            })
+            .spawn()
+            .context(format!("Failed to run {} (needed for --edit-in)", program))?;
+        let stdin = child.stdin.take();
+        let output = std::thread::scope(|scope| {
+            if let Some(mut stdin) = stdin {
+                scope.spawn(move || stdin.write_all(png));
+            }
+            child.wait_with_output()
+        })
+        .context(format!("Failed to run {}", program));
+        let edited = match self.0 {
+            Editor::Swappy => output.map(|output| output.stdout),
+            Editor::Satty | Editor::Gimp => output.and_then(|_| {
+                std::fs::read(&temp).context(format!("{} closed without saving", program))
+            }),
+        };
+        let _ = std::fs::remove_file(&temp);
+        let edited = edited?;
+        if edited.is_empty() {
+            return Err(anyhow::anyhow!("{} closed without saving", program));
+        }
+        Ok(edited)
+    }
+}
+
+// ImageMagick expands %-escapes and reads @file arguments in text.
+pub fn escape_text(text: &str) -> String {
+    let text = text.replace('\\', "\\\\").replace('%', "%%");
+    if text.starts_with('@') {
+        format!("\\{}", text)
+    } else {
+        text
+    }
+}
+
+pub fn parse_step(spec: &str) -> Result<Box<dyn Step>> {
+    let (name, value) = match spec.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (spec.trim(), None),
+    };
+    let number = |default: u32| -> Result<u32> {
+        value.map_or(Ok(default), |v| {
+            v.parse()
+                .context(format!("Invalid value '{}' for post step '{}'", v, name))
+        })
+    };
+    Ok(match name {
+        "autocrop" => Box::new(Autocrop {
+            tolerance: u8::try_from(number(0)?)
+                .context("autocrop tolerance must be at most 255")?,
+        }),
+        "resize" => {
+            let value = value.context("resize needs a value, e.g. resize=50% or resize=1600")?;
+            if value.ends_with('%') {
+                Box::new(Scale::Percent(
+                    resize::parse_percent(value).map_err(|e| anyhow::anyhow!(e))?,
+                ))
+            } else {
+                match number(0)? {
+                    0 => return Err(anyhow::anyhow!("resize width must be a positive number")),
+                    width => Box::new(Scale::MaxWidth(width)),
+                }
+            }
+        }
+        "shadow" => Box::new(Shadow {
+            radius: number(12)?,
+        }),
+        "watermark" => Box::new(Watermark {
+            text: value
+                .filter(|text| !text.is_empty())
+                .context("watermark needs text, e.g. watermark=example.com")?
+                .to_string(),
+        }),
+        "optimize" => Box::new(Optimize),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unknown post step '{}' (expected autocrop, resize, shadow, watermark or optimize)",
+                name
+            ));
+        }
+    })
+}
+
+pub fn parse(specs: &[String]) -> Result<Vec<Box<dyn Step>>> {
+    specs
+        .iter()
+        .filter(|spec| !spec.trim().is_empty() && spec.trim() != "none")
+        .map(|spec| parse_step(spec))
+        .collect()
+}
+
+pub fn run<'a>(png: &'a [u8], steps: &[Box<dyn Step>], debug: bool) -> Result<Cow<'a, [u8]>> {
+    let mut png = Cow::Borrowed(png);
+    for step in steps {
+        log::debug!("Post step {}: {:?}", step.name(), step);
+        png = Cow::Owned(
+            step.apply(&png, debug)
+                .context(format!("Post step '{}' failed", step.name()))?,
+        );
+    }
+    Ok(png)
+}
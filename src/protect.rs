@@ -0,0 +1,136 @@
+use crate::geometry::Region;
+use crate::post;
+use crate::utils;
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Action {
+    #[default]
+    Refuse,
+    Blur,
+}
+
+#[derive(Debug)]
+pub struct Protected {
+    pub class: String,
+    pub title: String,
+    // The part of the window inside the capture, in layout coordinates.
+    pub region: Region,
+}
+
+// Only windows on a workspace some monitor shows can end up in the picture.
+fn visible(client: &Value, monitors: &Value) -> bool {
+    let workspace = client["workspace"]["id"].as_i64();
+    monitors.as_array().into_iter().flatten().any(|m| {
+        m["activeWorkspace"]["id"].as_i64() == workspace
+            || m["specialWorkspace"]["id"].as_i64().filter(|id| *id != 0) == workspace
+    })
+}
+
+pub fn find(geometry: &str, classes: &[String]) -> Result<Vec<Protected>> {
+    if classes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let capture = Region::parse(geometry)?;
+    let monitors = utils::hyprctl_json("monitors")?;
+    let clients = utils::hyprctl_json("clients")?;
+    let pattern = classes.join("|");
+
+    let mut found = Vec::new();
+    for client in clients.as_array().into_iter().flatten() {
+        let class = client["class"].as_str().unwrap_or("");
+        if !utils::matches_pattern(&pattern, class) || !visible(client, &monitors) {
+            continue;
+        }
+        let (Some(x), Some(y), Some(width), Some(height)) = (
+            client["at"][0].as_i64(),
+            client["at"][1].as_i64(),
+            client["size"][0].as_i64(),
+            client["size"][1].as_i64(),
+        ) else {
+            continue;
+        };
+        let window = Region::new(x as i32, y as i32, width as i32, height as i32);
+        if let Some(region) = window.intersect(&capture) {
+            log::debug!(
+                "Protected window {} overlaps the capture at {}",
+                class,
+                region
+            );
+            found.push(Protected {
+                class: class.to_string(),
+                title: client["title"].as_str().unwrap_or("").to_string(),
+                region,
+            });
+        }
+    }
+    Ok(found)
+}
+
+fn png_size(png: &[u8]) -> Result<(u32, u32)> {
+    let be = |bytes: &[u8]| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    png.get(16..24)
+        .filter(|_| png.get(12..16) == Some(b"IHDR"))
+        .map(|size| (be(&size[..4]), be(&size[4..])))
+        .context("Protected windows can only be blurred in PNG images")
+}
+
+// Pixelates each region. The capture may be scaled (HiDPI, --resize), so the
+// regions are mapped by the ratio of the image to the captured area.
+pub fn blur(png: &[u8], geometry: &str, regions: &[Region]) -> Result<Vec<u8>> {
+    let capture = Region::parse(geometry)?;
+    let (width, height) = png_size(png)?;
+    let sx = width as f64 / capture.width as f64;
+    let sy = height as f64 / capture.height as f64;
+
+    let mut args = Vec::new();
+    for region in regions {
+        let x = ((region.x - capture.x) as f64 * sx).floor() as u32;
+        let y = ((region.y - capture.y) as f64 * sy).floor() as u32;
+        let w = ((region.width as f64 * sx).ceil() as u32).clamp(1, width - x.min(width - 1));
+        let h = ((region.height as f64 * sy).ceil() as u32).clamp(1, height - y.min(height - 1));
+        args.extend([
+            "(".to_string(),
+            "-clone".to_string(),
+            "0".to_string(),
+            "-crop".to_string(),
+            format!("{}x{}+{}+{}", w, h, x, y),
+            "+repage".to_string(),
+            "-scale".to_string(),
+            "4%".to_string(),
+            "-resize".to_string(),
+            format!("{}x{}!", w, h),
+            ")".to_string(),
+            "-geometry".to_string(),
+            format!("+{}+{}", x, y),
+            "-composite".to_string(),
+        ]);
+    }
+    post::magick(png, &[], &args, "blurring protected windows")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::Fake;
+
+    const MONITORS: &str = include_str!("../tests/fixtures/hyprctl/monitors.json");
+    const CLIENTS: &str = include_str!("../tests/fixtures/hyprctl/clients.json");
+
+    #[test]
+    fn finds_visible_protected_windows_in_the_capture() {
+        Fake::default()
+            .reply("hyprctl monitors -j", MONITORS)
+            .reply("hyprctl clients -j", CLIENTS)
+            .install();
+        let classes = vec!["Fire*".to_string(), "discord".to_string()];
+        let found = find("0,0 500x400", &classes).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].class, "firefox");
+        assert_eq!(found[0].region, Region::new(100, 100, 400, 300));
+
+        assert!(find("1000,0 500x400", &classes).unwrap().is_empty());
+        assert!(find("0,0 500x400", &[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use crate::runner;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+fn next_token<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < data.len() && data[*pos] == b'#' {
+            while *pos < data.len() && data[*pos] != b'\n' {
+                *pos += 1;
+            }
+        } else {
+            break;
+        }
+    }
+    let start = *pos;
+    while *pos < data.len() && !data[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    (start < *pos).then(|| &data[start..*pos])
+}
+
+impl Image {
+    pub fn from_ppm(data: &[u8]) -> Result<Image> {
+        if data.get(..2) != Some(b"P6") {
+            return Err(anyhow::anyhow!("Not a binary PPM image"));
+        }
+        let mut pos = 2;
+        let mut field = |name: &str| -> Result<usize> {
+            let token = next_token(data, &mut pos).context(format!("PPM is missing {}", name))?;
+            std::str::from_utf8(token)
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .context(format!("PPM has an invalid {}", name))
+        };
+
+        let width = field("width")?;
+        let height = field("height")?;
+        let max = field("max value")?;
+        if max != 255 {
+            return Err(anyhow::anyhow!("Unsupported PPM max value {}", max));
+        }
+        let pixels = data
+            .get(pos + 1..pos + 1 + width * height * 3)
+            .context("PPM pixel data is truncated")?
+            .to_vec();
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend_from_slice(&self.pixels);
+        data
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * self.width + x) * 3;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    pub fn is_black(&self, threshold: u8) -> bool {
+        self.pixels.iter().all(|&channel| channel <= threshold)
+    }
+}
+
+pub fn load(path: &Path) -> Result<Image> {
+    let data = std::fs::read(path).context(format!("Failed to read '{}'", path.display()))?;
+    if data.starts_with(b"P6") {
+        return Image::from_ppm(&data);
+    }
+    let output =
+        runner::checked("magick", &[&path.to_string_lossy(), "ppm:-"], None).context(format!(
+            "magick failed to decode '{}' (ImageMagick is needed to decode non-PPM images)",
+            path.display()
+        ))?;
+    Image::from_ppm(&output.stdout)
+}
+
+pub fn save(image: &Image, path: &Path) -> Result<()> {
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ppm"))
+    {
+        return std::fs::write(path, image.to_ppm())
+            .context(format!("Failed to write '{}'", path.display()));
+    }
+    runner::checked(
+        "magick",
+        &["ppm:-", &path.to_string_lossy()],
+        Some(&image.to_ppm()),
+    )
+    .context(format!(
+        "magick failed to write '{}' (ImageMagick is needed to encode non-PPM images)",
+        path.display()
+    ))?;
+    Ok(())
+}
@@ -0,0 +1,520 @@
+use crate::capture;
+use crate::environment;
+use crate::events::EventStream;
+use crate::geometry::Region;
+use crate::i18n;
+use crate::runner;
+use crate::save::{self, SaveOptions};
+use crate::storage;
+use crate::utils;
+use anyhow::{Context, Result};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum RecordFormat {
+    Gif,
+    Webp,
+    Avif,
+    Mp4,
+    Webm,
+}
+
+impl RecordFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordFormat::Gif => "gif",
+            RecordFormat::Webp => "webp",
+            RecordFormat::Avif => "avif",
+            RecordFormat::Mp4 => "mp4",
+            RecordFormat::Webm => "webm",
+        }
+    }
+
+    pub fn has_audio(self) -> bool {
+        matches!(self, RecordFormat::Mp4 | RecordFormat::Webm)
+    }
+
+    pub fn from_path(path: &Path) -> Option<RecordFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(RecordFormat::Gif),
+            "webp" => Some(RecordFormat::Webp),
+            "avif" => Some(RecordFormat::Avif),
+            "mp4" => Some(RecordFormat::Mp4),
+            "webm" => Some(RecordFormat::Webm),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum AudioSource {
+    Desktop,
+    Mic,
+    None,
+}
+
+const TRACK_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct RecordOptions {
+    pub format: RecordFormat,
+    pub window: bool,
+    pub fps: u32,
+    pub max_duration: Duration,
+    pub audio: AudioSource,
+    pub audio_device: Option<String>,
+    // An area that was already selected, e.g. from the toolbar.
+    pub region: Option<String>,
+}
+
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let value: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}': expected e.g. 30s or 2m", s))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        _ => Err(format!(
+            "invalid duration unit '{}': expected ms, s or m",
+            unit
+        )),
+    }
+}
+
+static STOP: AtomicBool = AtomicBool::new(false);
+static PAUSE_TOGGLES: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_stop(_: libc::c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn on_pause(_: libc::c_int) {
+    PAUSE_TOGGLES.fetch_add(1, Ordering::SeqCst);
+}
+
+fn install_signal_handlers() {
+    let stop = on_stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    let pause = on_pause as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, stop);
+        libc::signal(libc::SIGTERM, stop);
+        libc::signal(libc::SIGUSR1, pause);
+    }
+}
+
+fn pid_file() -> Result<PathBuf> {
+    storage::runtime_file("record.pid")
+}
+
+fn running_recording() -> Result<Option<i32>> {
+    let path = pid_file()?;
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    match contents.trim().parse::<i32>() {
+        Ok(pid) if environment::is_hyprshot(pid) => Ok(Some(pid)),
+        _ => Ok(None),
+    }
+}
+
+pub fn signal_recording(stop: bool) -> Result<()> {
+    let pid = running_recording()?.context("No recording in progress")?;
+    let signal = if stop { libc::SIGTERM } else { libc::SIGUSR1 };
+    log::debug!(
+        "Sending {} to recording {}",
+        if stop { "SIGTERM" } else { "SIGUSR1" },
+        pid
+    );
+    if unsafe { libc::kill(pid, signal) } != 0 {
+        return Err(anyhow::anyhow!(
+            "Failed to signal recording {}: {}",
+            pid,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+fn audio_device(opts: &RecordOptions) -> Result<Option<String>> {
+    let query = match opts.audio {
+        AudioSource::None => return Ok(None),
+        _ if opts.audio_device.is_some() => return Ok(opts.audio_device.clone()),
+        AudioSource::Desktop => "get-default-sink",
+        AudioSource::Mic => "get-default-source",
+    };
+    let output = runner::checked("pactl", &[query], None)
+        .context("pactl failed to find the default audio device")?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("pactl {} returned no device", query));
+    }
+    let device = if opts.audio == AudioSource::Desktop {
+        format!("{}.monitor", name)
+    } else {
+        name
+    };
+    log::debug!("Recording audio from {}", device);
+    Ok(Some(device))
+}
+
+// wf-recorder runs in the background, so its stderr goes to a file that a
+// failed recording can quote.
+fn recorder_log() -> Result<PathBuf> {
+    storage::temp_file(&format!("recording-{}.log", std::process::id()))
+}
+
+fn start_recorder(
+    geometry: &str,
+    video: &Path,
+    audio: Option<&str>,
+    fps: u32,
+    debug: bool,
+) -> Result<Child> {
+    // x264 and the other yuv420p encoders reject odd frame sizes.
+    let geometry = utils::even_dims(geometry)?;
+    let mut command = Command::new("wf-recorder");
+    command.args(["-g", &geometry, "-r", &fps.to_string()]);
+    if let Some(device) = audio {
+        command.arg(format!("--audio={}", device));
+    }
+    command
+        .arg("-f")
+        .arg(video)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(if debug {
+            Stdio::inherit()
+        } else {
+            std::fs::File::create(recorder_log()?)
+                .context("Failed to create the wf-recorder log")?
+                .into()
+        })
+        .process_group(0)
+        .spawn()
+        .context("Failed to run wf-recorder")
+}
+
+fn stop_recorder(mut child: Child) -> Result<()> {
+    if child.try_wait()?.is_none() {
+        unsafe { libc::kill(child.id() as i32, libc::SIGINT) };
+    }
+    child.wait().context("Failed to wait for wf-recorder")?;
+    Ok(())
+}
+
+enum Tracked {
+    Unchanged,
+    Moved(String),
+    Closed,
+}
+
+struct WindowTracker {
+    address: String,
+    events: EventStream,
+    last_check: Instant,
+}
+
+impl WindowTracker {
+    fn new(address: String) -> Result<WindowTracker> {
+        Ok(WindowTracker {
+            address,
+            events: EventStream::connect()?,
+            last_check: Instant::now(),
+        })
+    }
+
+    fn poll(&mut self, current: &str) -> Result<Tracked> {
+        // Resizing doesn't emit an event, so fall back to polling as well.
+        let events = self.events.pending()?;
+        for event in &events {
+            log::debug!("Hyprland event: {}>>{}", event.name, event.data);
+        }
+        if events.is_empty() && self.last_check.elapsed() < TRACK_INTERVAL {
+            return Ok(Tracked::Unchanged);
+        }
+        self.last_check = Instant::now();
+        Ok(match capture::window_geometry(&self.address)? {
+            None => Tracked::Closed,
+            Some(geometry) if geometry != current => Tracked::Moved(geometry),
+            Some(_) => Tracked::Unchanged,
+        })
+    }
+}
+
+fn record_segments(
+    mut geometry: String,
+    mut tracker: Option<WindowTracker>,
+    audio: Option<&str>,
+    opts: &RecordOptions,
+    debug: bool,
+) -> Result<Vec<PathBuf>> {
+    log::debug!(
+        "Recording {} at {} fps for up to {:?}",
+        geometry,
+        opts.fps,
+        opts.max_duration
+    );
+    let mut segments = Vec::new();
+    let mut recorded = Duration::ZERO;
+    let mut toggles = PAUSE_TOGGLES.load(Ordering::SeqCst);
+    let mut recorder: Option<(Child, Instant)> = None;
+    let mut paused = false;
+    loop {
+        if !paused && recorder.is_none() {
+            let video = storage::temp_file(&format!(
+                "recording-{}-{}.mkv",
+                std::process::id(),
+                segments.len()
+            ))?;
+            recorder = Some((
+                start_recorder(&geometry, &video, audio, opts.fps, debug)?,
+                Instant::now(),
+            ));
+            segments.push(video);
+        }
+
+        let exited = match &mut recorder {
+            Some((child, _)) => child.try_wait()?.is_some(),
+            None => false,
+        };
+        let elapsed = recorded
+            + recorder
+                .as_ref()
+                .map_or(Duration::ZERO, |(_, s)| s.elapsed());
+        let tracked = match &mut tracker {
+            Some(tracker) => tracker.poll(&geometry)?,
+            None => Tracked::Unchanged,
+        };
+        let stopping = STOP.load(Ordering::SeqCst)
+            || exited
+            || elapsed >= opts.max_duration
+            || matches!(tracked, Tracked::Closed);
+        let toggled = PAUSE_TOGGLES.load(Ordering::SeqCst) != toggles;
+        if let Tracked::Moved(moved) = tracked {
+            log::debug!("Window moved to {}", moved);
+            if let Some((child, started)) = recorder.take() {
+                stop_recorder(child)?;
+                recorded += started.elapsed();
+            }
+            geometry = moved;
+        }
+        if stopping || toggled {
+            if let Some((child, started)) = recorder.take() {
+                stop_recorder(child)?;
+                recorded += started.elapsed();
+            }
+            if stopping {
+                log::debug!("Stopping recording after {:?}", recorded);
+                break;
+            }
+            toggles = PAUSE_TOGGLES.load(Ordering::SeqCst);
+            paused = !paused;
+            log::debug!(
+                "{} recording after {:?}",
+                if paused { "Paused" } else { "Resumed" },
+                recorded
+            );
+        }
+        sleep(Duration::from_millis(50));
+    }
+
+    segments.retain(|segment| segment.exists());
+    let log = recorder_log()?;
+    let stderr = std::fs::read_to_string(&log).unwrap_or_default();
+    let _ = std::fs::remove_file(&log);
+    if segments.is_empty() {
+        return Err(match stderr.trim() {
+            "" => anyhow::anyhow!("wf-recorder failed to record {}", geometry),
+            stderr => anyhow::anyhow!("wf-recorder failed to record {}: {}", geometry, stderr),
+        });
+    }
+    Ok(segments)
+}
+
+fn encoder_args(format: RecordFormat, fps: u32, frame: Option<(u32, u32)>) -> Vec<String> {
+    let fit = frame
+        .map(|(width, height)| {
+            format!(
+                "scale={0}:{1}:force_original_aspect_ratio=decrease,pad={0}:{1}:(ow-iw)/2:(oh-ih)/2,",
+                width, height
+            )
+        })
+        .unwrap_or_default();
+    let filter = match format {
+        RecordFormat::Gif => format!(
+            "fps={},split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=dither=bayer:diff_mode=rectangle",
+            fps
+        ),
+        RecordFormat::Webp => format!("fps={}", fps),
+        // yuv420p needs even dimensions.
+        RecordFormat::Avif | RecordFormat::Mp4 | RecordFormat::Webm => {
+            format!("fps={},scale=trunc(iw/2)*2:trunc(ih/2)*2", fps)
+        }
+    };
+    let codec: &[&str] = match format {
+        RecordFormat::Gif => &["-loop", "0"],
+        RecordFormat::Webp => &["-c:v", "libwebp_anim", "-q:v", "75", "-loop", "0"],
+        RecordFormat::Avif => &[
+            "-c:v",
+            "libaom-av1",
+            "-crf",
+            "35",
+            "-b:v",
+            "0",
+            "-cpu-used",
+            "6",
+            "-pix_fmt",
+            "yuv420p",
+        ],
+        RecordFormat::Mp4 => &[
+            "-c:v",
+            "libx264",
+            "-crf",
+            "23",
+            "-pix_fmt",
+            "yuv420p",
+            "-c:a",
+            "aac",
+            "-movflags",
+            "+faststart",
+        ],
+        RecordFormat::Webm => &[
+            "-c:v",
+            "libvpx-vp9",
+            "-crf",
+            "35",
+            "-b:v",
+            "0",
+            "-c:a",
+            "libopus",
+        ],
+    };
+    let mut args = vec!["-vf".to_string(), fit + &filter];
+    args.extend(codec.iter().map(|a| a.to_string()));
+    args
+}
+
+fn concat_list(segments: &[PathBuf]) -> Result<PathBuf> {
+    let list = storage::temp_file(&format!("recording-{}.txt", std::process::id()))?;
+    let contents: String = segments
+        .iter()
+        .map(|s| format!("file '{}'\n", s.to_string_lossy().replace('\'', "'\\''")))
+        .collect();
+    std::fs::write(&list, contents).context(format!("Failed to write '{}'", list.display()))?;
+    Ok(list)
+}
+
+fn encode(
+    input: &Path,
+    concat: bool,
+    output: &Path,
+    opts: &RecordOptions,
+    frame: Option<(u32, u32)>,
+    debug: bool,
+) -> Result<()> {
+    let format = opts.format;
+    log::debug!(
+        "Encoding {} as {} with ffmpeg",
+        output.display(),
+        format.extension()
+    );
+    let (input, output_arg) = (input.to_string_lossy(), output.to_string_lossy());
+    let encoder = encoder_args(format, opts.fps, frame);
+    let mut args = vec!["-y", "-loglevel", if debug { "info" } else { "error" }];
+    if concat {
+        args.extend(["-f", "concat", "-safe", "0"]);
+    }
+    args.extend(["-i", &input]);
+    args.extend(encoder.iter().map(String::as_str));
+    args.extend(["-f", format.extension(), &output_arg]);
+    runner::checked("ffmpeg", &args, None)
+        .context(format!("ffmpeg failed to encode '{}'", output.display()))?;
+    Ok(())
+}
+
+fn remove_temp(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        log::debug!("Failed to remove '{}': {}", path.display(), e);
+    }
+}
+
+fn frame_size(geometry: &str) -> Result<(u32, u32)> {
+    let region = Region::parse(geometry)?.scale(utils::output_scale(geometry)?);
+    let even = |size: i32| (size as u32 / 2 * 2).max(2);
+    Ok((even(region.width), even(region.height)))
+}
+
+fn record_and_encode(output: &Path, opts: &RecordOptions, debug: bool) -> Result<()> {
+    if opts.audio != AudioSource::None && !opts.format.has_audio() {
+        return Err(anyhow::anyhow!(
+            "--audio needs a video format (mp4 or webm), not {}",
+            opts.format.extension()
+        ));
+    }
+    let audio = audio_device(opts)?;
+    let (geometry, tracker, frame) = if opts.window {
+        let geometry = capture::grab_window(false, &utils::WindowFilter::default())?;
+        let address = capture::window_address(&geometry)?;
+        let frame = frame_size(&geometry)?;
+        (geometry, Some(WindowTracker::new(address)?), Some(frame))
+    } else if let Some(region) = &opts.region {
+        (region.clone(), None, None)
+    } else {
+        (capture::grab_region()?, None, None)
+    };
+    let segments = record_segments(geometry, tracker, audio.as_deref(), opts, debug)?;
+    let result = if let [video] = segments.as_slice() {
+        encode(video, false, output, opts, None, debug)
+    } else {
+        concat_list(&segments).and_then(|list| {
+            let result = encode(&list, true, output, opts, frame, debug);
+            remove_temp(&list);
+            result
+        })
+    };
+    for segment in &segments {
+        remove_temp(segment);
+    }
+    result
+}
+
+pub fn record(output: &Path, opts: &RecordOptions, save_opts: &SaveOptions) -> Result<()> {
+    if let Some(pid) = running_recording()? {
+        return Err(anyhow::anyhow!(
+            "A recording is already in progress (pid {}); stop it with 'hyprshot-rs record --stop'",
+            pid
+        ));
+    }
+    let pid_file = pid_file()?;
+    std::fs::write(&pid_file, std::process::id().to_string())
+        .context(format!("Failed to write '{}'", pid_file.display()))?;
+    install_signal_handlers();
+
+    let result = record_and_encode(output, opts, save_opts.debug);
+    remove_temp(&pid_file);
+    result?;
+
+    if !save_opts.silent {
+        let message = i18n::trf(
+            "Recording saved in <i>{path}</i>.",
+            &[("path", &output.display().to_string())],
+        );
+        save::notify(
+            i18n::tr("Recording saved"),
+            &message,
+            Some(output),
+            save_opts,
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,324 @@
+use crate::clipboard;
+use crate::fault;
+use crate::storage;
+use anyhow::{Context, Result};
+use std::collections::hash_map::RandomState;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scheme {
+    Sftp,
+    Webdav,
+}
+
+#[derive(Clone, Debug)]
+pub struct Destination {
+    scheme: Scheme,
+    url: String,
+    user: Option<String>,
+    host: String,
+    port: Option<u16>,
+    path: String,
+}
+
+impl Destination {
+    pub fn parse(folder: &Path) -> Result<Option<Destination>> {
+        let Some(url) = folder.to_str() else {
+            return Ok(None);
+        };
+        let Some((scheme, rest)) = url.split_once("://") else {
+            return Ok(None);
+        };
+        let (scheme, base) = match scheme {
+            "sftp" | "ssh" => (Scheme::Sftp, format!("sftp://{}", rest)),
+            "http" | "https" => (Scheme::Webdav, url.to_string()),
+            "webdav" | "davs" => (Scheme::Webdav, format!("https://{}", rest)),
+            "dav" => (Scheme::Webdav, format!("http://{}", rest)),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported output folder scheme '{}://'",
+                    scheme
+                ));
+            }
+        };
+
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host),
+            None => (None, authority),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                Some(
+                    port.parse()
+                        .context(format!("Invalid port in output folder '{}'", url))?,
+                ),
+            ),
+            None => (host_port, None),
+        };
+        if host.is_empty() {
+            return Err(anyhow::anyhow!("Output folder '{}' has no host", url));
+        }
+        let destination = Destination {
+            scheme,
+            // Credentials never end up in file names, notifications or history.
+            url: match &user {
+                Some(user) => base.replacen(&format!("{}@", user), "", 1),
+                None => base,
+            }
+            .trim_end_matches('/')
+            .to_string(),
+            user,
+            host: host.to_string(),
+            port,
+            path: path.trim_end_matches('/').to_string(),
+        };
+        Ok(Some(destination))
+    }
+
+    pub fn url(&self, filename: &str) -> String {
+        format!("{}/{}", self.url, filename)
+    }
+
+    pub fn relative<'a>(&self, target: &'a Path) -> Option<&'a str> {
+        target.to_str()?.strip_prefix(&self.url)?.strip_prefix('/')
+    }
+
+    pub fn upload(&self, filename: &str, data: &[u8]) -> Result<()> {
+        log::debug!("Uploading {} bytes to {}", data.len(), self.url(filename));
+        fault::check(fault::Point::Upload)?;
+        match self.scheme {
+            Scheme::Sftp => self.upload_ssh(filename, data),
+            Scheme::Webdav => self.upload_webdav(filename, data),
+        }
+    }
+
+    fn upload_ssh(&self, filename: &str, data: &[u8]) -> Result<()> {
+        let quote = |s: &str| format!("'{}'", s.replace('\'', r"'\''"));
+        let remote_path = |path: &str| match path.strip_prefix("~/") {
+            Some(rest) => format!("\"$HOME\"/{}", quote(rest)),
+            None => quote(path),
+        };
+        let file = match self.path.as_str() {
+            "" => filename.to_string(),
+            path if path.starts_with('~') => format!("{}/{}", path, filename),
+            path => format!("/{}/{}", path, filename),
+        };
+        let dir = match file.rsplit_once('/') {
+            Some(("", _)) => "/".to_string(),
+            Some((dir, _)) => dir.to_string(),
+            None => ".".to_string(),
+        };
+        let mut ssh = Command::new("ssh");
+        if let Some(port) = self.port {
+            ssh.arg("-p").arg(port.to_string());
+        }
+        ssh.arg(match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        })
+        .arg(format!(
+            "mkdir -p {} && cat > {}",
+            remote_path(&dir),
+            remote_path(&file)
+        ));
+        pipe(ssh, data, "ssh")
+    }
+
+    fn upload_webdav(&self, filename: &str, data: &[u8]) -> Result<()> {
+        let mut curl = Command::new("curl");
+        curl.args(["--fail", "--silent", "--show-error", "--upload-file", "-"]);
+
+        let netrc = match self.password()? {
+            Some(password) => {
+                let file = write_private(
+                    "netrc",
+                    &format!(
+                        "machine {} login {} password {}\n",
+                        self.host,
+                        self.user.as_deref().unwrap_or(""),
+                        password
+                    ),
+                )?;
+                curl.arg("--netrc-file").arg(&file);
+                Some(file)
+            }
+            None => {
+                curl.arg("--netrc-optional");
+                None
+            }
+        };
+        curl.arg(format!(
+            "{}/{}",
+            self.url,
+            clipboard::percent_encode(filename.as_bytes())
+        ));
+        let uploaded = pipe(curl, data, "curl");
+        if let Some(file) = netrc {
+            let _ = std::fs::remove_file(file);
+        }
+        uploaded
+    }
+
+    fn password(&self) -> Result<Option<String>> {
+        let Some(user) = &self.user else {
+            return Ok(None);
+        };
+        let output = Command::new("secret-tool")
+            .args([
+                "lookup",
+                "service",
+                "hyprshot-rs",
+                "host",
+                &self.host,
+                "user",
+                user,
+            ])
+            .output();
+        match output {
+            Ok(output) if output.status.success() && !output.stdout.is_empty() => Ok(Some(
+                String::from_utf8_lossy(&output.stdout)
+                    .trim_end_matches('\n')
+                    .to_string(),
+            )),
+            Ok(_) => Err(anyhow::anyhow!(
+                "No password for {}@{} in the keyring (store one with: secret-tool store --label=hyprshot-rs service hyprshot-rs host {} user {})",
+                user,
+                self.host,
+                self.host,
+                user
+            )),
+            Err(e) => {
+                log::debug!("secret-tool is not available: {}", e);
+                Ok(None)
+            }
+        }
+    }
+}
+
+// Without XDG_RUNTIME_DIR this lands in the shared temp directory, so the
+// name can't be guessed and the file must be new: a file or symlink planted
+// there beforehand would otherwise receive the password.
+fn write_private(prefix: &str, contents: &str) -> Result<PathBuf> {
+    use std::hash::{BuildHasher, Hasher};
+    use std::os::unix::fs::OpenOptionsExt;
+
+    loop {
+        let token = RandomState::new().build_hasher().finish();
+        let path =
+            storage::temp_file(&format!("{}-{}-{:016x}", prefix, std::process::id(), token))?;
+        let failed = || format!("Failed to write '{}'", path.display());
+        let mut file = match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            opened => opened.context(failed())?,
+        };
+        if let Err(e) = file.write_all(contents.as_bytes()) {
+            let _ = std::fs::remove_file(&path);
+            return Err(e).context(failed());
+        }
+        return Ok(path);
+    }
+}
+
+fn pipe(mut command: Command, data: &[u8], name: &str) -> Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(format!("Failed to run {}", name))?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .context(format!("Failed to open {} stdin", name))?;
+    let output = std::thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(data));
+        child.wait_with_output()
+    })
+    .context(format!("Failed to run {}", name))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{} failed to upload screenshot: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> Destination {
+        Destination::parse(Path::new(url)).unwrap().unwrap()
+    }
+
+    #[test]
+    fn local_folders_are_not_remote() {
+        assert!(
+            Destination::parse(Path::new("/home/me/Pictures"))
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            Destination::parse(Path::new("~/Pictures"))
+                .unwrap()
+                .is_none()
+        );
+        assert!(Destination::parse(Path::new("ftp://host/shots")).is_err());
+    }
+
+    #[test]
+    fn schemes_map_to_sftp_and_webdav() {
+        let ssh = parse("ssh://host/shots");
+        assert_eq!(ssh.scheme, Scheme::Sftp);
+        assert_eq!(ssh.url("a.png"), "sftp://host/shots/a.png");
+        assert_eq!(
+            parse("davs://host/dav").url("a.png"),
+            "https://host/dav/a.png"
+        );
+        assert_eq!(
+            parse("dav://host/dav").url("a.png"),
+            "http://host/dav/a.png"
+        );
+        let https = parse("https://host/dav/");
+        assert_eq!(https.scheme, Scheme::Webdav);
+        assert_eq!(https.url("a.png"), "https://host/dav/a.png");
+    }
+
+    #[test]
+    fn user_and_port_are_split_off() {
+        let destination = parse("sftp://me@host:2222/~/shots/");
+        assert_eq!(destination.user.as_deref(), Some("me"));
+        assert_eq!(destination.host, "host");
+        assert_eq!(destination.port, Some(2222));
+        assert_eq!(destination.path, "~/shots");
+        assert!(Destination::parse(Path::new("sftp://host:ssh/shots")).is_err());
+        assert!(Destination::parse(Path::new("sftp://me@/shots")).is_err());
+    }
+
+    #[test]
+    fn credentials_are_stripped_from_urls() {
+        let destination = parse("webdav://me@cloud.example:8443/remote.php/dav");
+        assert_eq!(
+            destination.url("a.png"),
+            "https://cloud.example:8443/remote.php/dav/a.png"
+        );
+        assert_eq!(
+            destination.relative(Path::new(
+                "https://cloud.example:8443/remote.php/dav/2024/a.png"
+            )),
+            Some("2024/a.png")
+        );
+        assert_eq!(destination.relative(Path::new("/tmp/a.png")), None);
+    }
+}
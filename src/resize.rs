@@ -0,0 +1,59 @@
+use crate::geometry::Region;
+use crate::utils;
+use anyhow::Result;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Resize {
+    pub factor: Option<f64>,
+    pub max_width: Option<u32>,
+}
+
+pub fn parse_percent(s: &str) -> Result<f64, String> {
+    let percent: f64 = s
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("invalid size '{}': expected a percentage like 50%", s))?;
+    if percent <= 0.0 || percent > 100.0 {
+        return Err(format!("invalid size '{}': must be between 0% and 100%", s));
+    }
+    Ok(percent / 100.0)
+}
+
+impl Resize {
+    pub fn is_none(&self) -> bool {
+        self.factor.is_none() && self.max_width.is_none()
+    }
+
+    pub fn target_width(&self, width: u32) -> u32 {
+        let mut target = match self.factor {
+            Some(factor) => (width as f64 * factor).round() as u32,
+            None => width,
+        };
+        if let Some(max_width) = self.max_width {
+            target = target.min(max_width);
+        }
+        target.max(1)
+    }
+
+    pub fn grim_scale(&self, geometry: &str) -> Result<Option<f64>> {
+        if self.is_none() {
+            return Ok(None);
+        }
+        let width = Region::parse(geometry)?.width;
+        let output_scale = utils::output_scale(geometry)?;
+        let native_width = (width as f64 * output_scale).round() as u32;
+        let target = self.target_width(native_width);
+        if target >= native_width {
+            return Ok(None);
+        }
+        let scale = target as f64 / width as f64;
+        log::debug!(
+            "Resizing capture from {} to {} pixels wide (grim scale {:.4})",
+            native_width,
+            target,
+            scale
+        );
+        Ok(Some(scale))
+    }
+}
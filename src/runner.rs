@@ -0,0 +1,164 @@
+use anyhow::Context;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::process::{Command, Output, Stdio};
+use std::rc::Rc;
+
+pub trait CommandRunner {
+    fn output(&self, program: &str, args: &[&str], stdin: Option<&[u8]>) -> io::Result<Output>;
+}
+
+pub struct System;
+
+impl CommandRunner for System {
+    fn output(&self, program: &str, args: &[&str], stdin: Option<&[u8]>) -> io::Result<Output> {
+        let Some(data) = stdin else {
+            return Command::new(program).args(args).output();
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut input = child.stdin.take().expect("stdin is piped");
+        std::thread::scope(|scope| {
+            scope.spawn(move || input.write_all(data));
+            child.wait_with_output()
+        })
+    }
+}
+
+thread_local! {
+    static RUNNER: RefCell<Rc<dyn CommandRunner>> = RefCell::new(Rc::new(System));
+}
+
+pub fn output(program: &str, args: &[&str], stdin: Option<&[u8]>) -> io::Result<Output> {
+    let runner = RUNNER.with(|runner| runner.borrow().clone());
+    runner.output(program, args, stdin)
+}
+
+// Like `output`, for commands that must succeed: a failure carries what the
+// program printed to stderr, or its exit status when it printed nothing.
+// Stderr is logged either way, as warnings often explain a later failure.
+pub fn checked(program: &str, args: &[&str], stdin: Option<&[u8]>) -> anyhow::Result<Output> {
+    let output =
+        self::output(program, args, stdin).context(format!("Failed to run {}", program))?;
+    let command = std::iter::once(program)
+        .chain(args.iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    if !stderr.is_empty() {
+        log::debug!("{} printed to stderr: {}", command, stderr);
+    }
+    if !output.status.success() {
+        return Err(match stderr {
+            "" => anyhow::anyhow!("{} exited with {}", command, output.status),
+            stderr => anyhow::anyhow!("{}: {}", command, stderr),
+        });
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+pub fn set(runner: Rc<dyn CommandRunner>) {
+    RUNNER.with(|current| *current.borrow_mut() = runner);
+}
+
+#[cfg(test)]
+pub mod fake {
+    use super::CommandRunner;
+    use std::cell::RefCell;
+    use std::io;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Output};
+    use std::rc::Rc;
+
+    // Replies are keyed by the full command line, e.g. "hyprctl monitors -j".
+    // Several replies for one command are used in order, the last one repeats.
+    #[derive(Clone)]
+    struct Reply {
+        command: String,
+        code: i32,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+    }
+
+    #[derive(Default)]
+    pub struct Fake {
+        replies: RefCell<Vec<Reply>>,
+        pub calls: RefCell<Vec<(String, Option<String>)>>,
+    }
+
+    impl Fake {
+        fn push(mut self, command: &str, code: i32, stdout: &str, stderr: &str) -> Fake {
+            self.replies.get_mut().push(Reply {
+                command: command.to_string(),
+                code,
+                stdout: stdout.as_bytes().to_vec(),
+                stderr: stderr.as_bytes().to_vec(),
+            });
+            self
+        }
+
+        pub fn reply(self, command: &str, stdout: &str) -> Fake {
+            self.push(command, 0, stdout, "")
+        }
+
+        pub fn fail(self, command: &str, code: i32) -> Fake {
+            self.push(command, code, "", "")
+        }
+
+        pub fn fail_with(self, command: &str, code: i32, stderr: &str) -> Fake {
+            self.push(command, code, "", stderr)
+        }
+
+        pub fn install(self) -> Rc<Fake> {
+            let fake = Rc::new(self);
+            super::set(fake.clone());
+            fake
+        }
+
+        pub fn stdin_of(&self, command: &str) -> Option<String> {
+            self.calls
+                .borrow()
+                .iter()
+                .find(|(call, _)| call == command)
+                .and_then(|(_, stdin)| stdin.clone())
+        }
+    }
+
+    impl CommandRunner for Fake {
+        fn output(&self, program: &str, args: &[&str], stdin: Option<&[u8]>) -> io::Result<Output> {
+            let command = std::iter::once(program)
+                .chain(args.iter().copied())
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.calls.borrow_mut().push((
+                command.clone(),
+                stdin.map(|data| String::from_utf8_lossy(data).into_owned()),
+            ));
+            let mut replies = self.replies.borrow_mut();
+            let matching: Vec<usize> = (0..replies.len())
+                .filter(|&i| replies[i].command == command)
+                .collect();
+            let reply = match matching[..] {
+                [] => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("unexpected: {}", command),
+                    ));
+                }
+                [only] => replies[only].clone(),
+                [first, ..] => replies.remove(first),
+            };
+            Ok(Output {
+                status: ExitStatus::from_raw(reply.code << 8),
+                stdout: reply.stdout,
+                stderr: reply.stderr,
+            })
+        }
+    }
+}
@@ -1,139 +1,509 @@
+use crate::clipboard;
+use crate::crash;
+use crate::encode;
+use crate::frame;
+use crate::geometry::Region;
+#[cfg(feature = "native")]
+use crate::hdr;
+use crate::hooks::{self, Stage as Hook};
+use crate::i18n;
+use crate::icc;
+use crate::instance;
+use crate::metadata::{self, Strip};
+use crate::notifications;
+use crate::paths;
+use crate::post;
+use crate::protect;
+use crate::remote;
+use crate::resize::Resize;
+#[cfg(feature = "grim")]
+use crate::runner;
+use crate::sign;
+use crate::storage::{self, LowSpace};
+use crate::thumbnail;
+use crate::timing::{Budgets, Stage};
+#[cfg(feature = "native")]
+use crate::utils;
 use anyhow::{Context, Result};
 use notify_rust::Notification;
-use std::fs::create_dir_all;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug)]
+pub struct SaveOptions {
+    pub clipboard_only: bool,
+    pub no_clipboard: bool,
+    pub raw: bool,
+    pub command: Option<Vec<String>>,
+    pub detach: bool,
+    pub silent: bool,
+    pub notif_timeout: u32,
+    pub notify_sound: Option<PathBuf>,
+    pub primary: Option<clipboard::PrimarySelection>,
+    pub paste_once: bool,
+    pub strip_metadata: Option<Strip>,
+    pub sign: Option<PathBuf>,
+    pub thumbnail: Option<u32>,
+    pub thumbnail_cache: bool,
+    pub min_free_space: u64,
+    pub low_space: LowSpace,
+    pub flash: bool,
+    pub format: encode::Format,
+    pub lossy: bool,
+    pub quality: Option<u8>,
+    pub png_compression: Option<encode::PngCompression>,
+    pub hdr: Option<encode::HdrOutput>,
+    pub icc_profiles: Vec<(String, PathBuf)>,
+    pub srgb_convert: bool,
+    pub frame: Option<frame::FrameStyle>,
+    pub blur: Vec<Region>,
+    pub selection: Option<Region>,
+    pub autocrop: Option<u8>,
+    pub post: Vec<Box<dyn post::Step>>,
+    pub hooks: hooks::Hooks,
+    pub remote: Option<remote::Destination>,
+    pub resize: Resize,
+    pub budgets: Budgets,
+    pub lock: RefCell<Option<instance::Lock>>,
+    pub file_mode: Option<u32>,
+    pub dir_mode: Option<u32>,
+    pub on_conflict: paths::OnConflict,
+    pub debug: bool,
+}
 
-#[cfg(feature = "grim")]
-pub fn save_geometry_with_grim(
-    geometry: &str,
-    save_fullpath: &PathBuf,
-    clipboard_only: bool,
-    raw: bool,
-    command: Option<Vec<String>>,
-    silent: bool,
-    notif_timeout: u32,
-    debug: bool,
-) -> Result<()> {
-    use std::io::Write;
+// What a capture turned into, reported in the notification and by --json.
+#[derive(Clone, Debug, Default)]
+pub struct Delivery {
+    // Bytes of the PNG the backend captured, before post steps and encoding.
+    pub captured: usize,
+    // Bytes written to the file (or uploaded); None when nothing was saved.
+    pub saved: Option<usize>,
+    // Where it was saved: the planned path, or the next free name when another
+    // capture took that one first.
+    pub path: PathBuf,
+}
+
+impl Delivery {
+    pub fn saved_percent(&self) -> Option<u32> {
+        let saved = self.saved?;
+        (saved < self.captured && self.captured > 0)
+            .then(|| ((self.captured - saved) * 100 / self.captured) as u32)
+    }
+}
 
-    if debug {
-        eprintln!("Saving geometry with grim: {}", geometry);
+pub fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        1_048_576..1_073_741_824 => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+        _ => format!("{:.1} GiB", bytes as f64 / 1_073_741_824.0),
     }
+}
 
-    if raw {
-        let output = Command::new("grim")
-            .arg("-g")
-            .arg(geometry)
-            .arg("-")
-            .output()
-            .context("Failed to run grim")?;
-        if !output.status.success() {
-            return Err(anyhow::anyhow!("grim failed to capture screenshot"));
-        }
-        std::io::stdout().write_all(&output.stdout)?;
-        return Ok(());
+pub fn copy_bundle(paths: &[PathBuf], opts: &SaveOptions) -> Result<()> {
+    if paths.is_empty() {
+        return Err(anyhow::anyhow!("No screenshots in history to bundle"));
     }
+    let uri_list = paths
+        .iter()
+        .map(|p| clipboard::file_uri(p))
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    log::debug!("Clipboard bundle:\n{}", uri_list);
+    clipboard::copy(
+        uri_list.as_bytes(),
+        "text/uri-list",
+        opts.primary,
+        opts.paste_once,
+    )?;
+
+    if !opts.silent {
+        notify(
+            i18n::tr("Screenshots copied"),
+            &i18n::trf(
+                "{count} screenshot(s) copied to {target}.",
+                &[
+                    ("count", &paths.len().to_string()),
+                    ("target", clipboard::description(opts.primary)),
+                ],
+            ),
+            None,
+            opts,
+        );
+    }
+    Ok(())
+}
 
-    if !clipboard_only {
-        create_dir_all(save_fullpath.parent().unwrap())
-            .context("Failed to create screenshot directory")?;
-        let grim_status = Command::new("grim")
-            .arg("-g")
-            .arg(geometry)
-            .arg(save_fullpath)
-            .status()
-            .context("Failed to run grim")?;
-        if !grim_status.success() {
-            return Err(anyhow::anyhow!("grim failed to capture screenshot"));
-        }
+pub fn notify_saved(save_fullpath: &Path, delivery: &Delivery, opts: &SaveOptions) {
+    let path = save_fullpath.display().to_string();
+    let target = clipboard::description(opts.primary);
+    let message = if opts.clipboard_only {
+        i18n::trf("Image copied to {target}", &[("target", target)])
+    } else if opts.no_clipboard {
+        i18n::trf("Image saved in <i>{path}</i>.", &[("path", &path)])
+    } else {
+        i18n::trf(
+            "Image saved in <i>{path}</i> and copied to {target}.",
+            &[("path", &path), ("target", target)],
+        )
+    };
+    let mut details: Vec<String> = opts.selection.iter().map(describe_selection).collect();
+    if let Some(saved) = delivery.saved {
+        let size = format_size(saved);
+        details.push(match delivery.saved_percent() {
+            Some(percent) => i18n::trf(
+                "{size} ({percent}% saved)",
+                &[("size", &size), ("percent", &percent.to_string())],
+            ),
+            None => size,
+        });
+    }
+    let message = match details.is_empty() {
+        true => message,
+        false => format!("{}\n{}", message, details.join(" · ")),
+    };
+    let icon = Some(save_fullpath).filter(|path| path.exists());
+    notify(i18n::tr("Screenshot saved"), &message, icon, opts)
+}
+
+pub fn describe_selection(region: &Region) -> String {
+    i18n::trf(
+        "{width}x{height} at {x},{y}",
+        &[
+            ("width", &region.width.to_string()),
+            ("height", &region.height.to_string()),
+            ("x", &region.x.to_string()),
+            ("y", &region.y.to_string()),
+        ],
+    )
+}
+
+const PLACEHOLDERS: [&str; 4] = ["{file}", "{dir}", "{geometry}", "{mode}"];
+
+// Fills in the placeholders; a command without any gets the path appended,
+// as it always has.
+fn command_args(args: &[String], values: [String; 4]) -> Vec<String> {
+    if !args
+        .iter()
+        .any(|arg| PLACEHOLDERS.iter().any(|name| arg.contains(name)))
+    {
+        let mut args = args.to_vec();
+        args.push(values[0].clone());
+        return args;
+    }
+    args.iter()
+        .map(|arg| {
+            PLACEHOLDERS
+                .iter()
+                .zip(&values)
+                .fold(arg.clone(), |arg, (name, value)| arg.replace(name, value))
+        })
+        .collect()
+}
 
-        let wl_copy_status = Command::new("wl-copy")
-            .arg("--type")
-            .arg("image/png")
-            .stdin(std::fs::File::open(save_fullpath).context(format!(
-                "Failed to open screenshot file '{}'",
-                save_fullpath.display()
-            ))?)
+fn command_values(save_fullpath: &Path, opts: &SaveOptions) -> [String; 4] {
+    let dir = save_fullpath.parent().unwrap_or(Path::new(""));
+    [
+        save_fullpath.to_string_lossy().into_owned(),
+        dir.to_string_lossy().into_owned(),
+        opts.hooks.get("HYPRSHOT_GEOMETRY").unwrap_or_default(),
+        opts.hooks.get("HYPRSHOT_MODE").unwrap_or_default(),
+    ]
+}
+
+pub fn run_command(save_fullpath: &Path, opts: &SaveOptions) -> Result<()> {
+    if let Some(cmd) = &opts.command {
+        let mut command = Command::new(&cmd[0]);
+        command.args(command_args(&cmd[1..], command_values(save_fullpath, opts)));
+        if opts.detach {
+            return detach(command).context(format!("Failed to run command '{}'", cmd[0]));
+        }
+        crash::disarm();
+        let cmd_status = command
             .status()
-            .context("Failed to run wl-copy")?;
-        if !wl_copy_status.success() {
-            return Err(anyhow::anyhow!("wl-copy failed to copy screenshot"));
+            .context(format!("Failed to run command '{}'", cmd[0]))?;
+        if !cmd_status.success() {
+            return Err(anyhow::anyhow!("Command '{}' failed", cmd[0]));
         }
+    }
+    Ok(())
+}
+
+// A new session keeps the viewer open after the keybind's process group goes
+// away; nobody waits on it, so init reaps it once hyprshot-rs has exited.
+fn detach(mut command: Command) -> std::io::Result<()> {
+    use std::os::unix::process::CommandExt;
+    use std::process::Stdio;
+    unsafe {
+        command.pre_exec(|| match libc::setsid() {
+            -1 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    log::debug!("Detached command as pid {}", child.id());
+    Ok(())
+}
 
-        if let Some(cmd) = command {
-            let cmd_status = Command::new(&cmd[0])
-                .args(&cmd[1..])
-                .arg(save_fullpath)
-                .status()
-                .context(format!("Failed to run command '{}'", cmd[0]))?;
-            if !cmd_status.success() {
-                return Err(anyhow::anyhow!("Command '{}' failed", cmd[0]));
+pub fn notify(summary: &str, body: &str, icon: Option<&Path>, opts: &SaveOptions) {
+    if let Some(sound) = &opts.notify_sound {
+        notifications::play_sound(sound);
+    }
+    let shown = Notification::new()
+        .summary(summary)
+        .body(body)
+        .icon(icon.and_then(|icon| icon.to_str()).unwrap_or("screenshot"))
+        .timeout(opts.notif_timeout as i32)
+        .appname("Hyprshot-rs")
+        .show();
+    if let Err(e) = shown {
+        log::warn!("failed to show notification: {}", e);
+    }
+}
+
+// A full disk would otherwise leave a truncated file and an opaque write error.
+fn check_space(save_fullpath: &Path, size: usize, opts: &SaveOptions) -> Result<()> {
+    let Some(free) = storage::free_space(save_fullpath) else {
+        return Ok(());
+    };
+    let size = size as u64;
+    log::debug!("{} free for a {} screenshot", free, size);
+    if free >= size + opts.min_free_space {
+        return Ok(());
+    }
+    let folder = save_fullpath.parent().unwrap_or(save_fullpath);
+    let message = i18n::trf(
+        "Only {free} free in {folder}",
+        &[
+            ("free", &format_size(free as usize)),
+            ("folder", &folder.display().to_string()),
+        ],
+    );
+    if free >= size && opts.low_space == LowSpace::Warn {
+        log::warn!("{}", message);
+        return Ok(());
+    }
+    if !opts.silent {
+        notify(i18n::tr("Not enough disk space"), &message, None, opts);
+    }
+    Err(anyhow::anyhow!(
+        "{}; the {} screenshot was not saved",
+        message,
+        format_size(size as usize)
+    ))
+}
+
+// Returns where the screenshot ended up, which --on-conflict rename may have
+// moved on from `path`.
+fn write_screenshot(path: &Path, data: &[u8], opts: &SaveOptions) -> Result<PathBuf> {
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+
+    let (path, mut file) = paths::create(path, opts.on_conflict, opts.file_mode)?;
+    crash::writing(&path);
+    if let Some(mode) = opts.file_mode {
+        file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+    }
+    let written = file.write_all(data);
+    crash::written(&path);
+    written?;
+    Ok(path)
+}
+
+pub fn deliver(png: &[u8], save_fullpath: &Path, opts: &SaveOptions) -> Result<Delivery> {
+    deliver_as(png, None, save_fullpath, opts)
+}
+
+pub fn deliver_as(
+    png: &[u8],
+    encoded: Option<&[u8]>,
+    save_fullpath: &Path,
+    opts: &SaveOptions,
+) -> Result<Delivery> {
+    use std::io::Write;
+
+    // The image is in memory, so the next capture no longer has to wait for
+    // the upload, a viewer or an editor.
+    if opts.lock.take().is_some() {
+        log::debug!("Released the instance lock");
+    }
+    let mut delivery = Delivery {
+        captured: png.len(),
+        saved: None,
+        path: save_fullpath.to_path_buf(),
+    };
+    opts.hooks.run(Hook::PostCapture)?;
+    let processed = match encoded {
+        Some(_) => Cow::Borrowed(png),
+        None => post::run(png, &opts.post, opts.debug)?,
+    };
+    // ImageMagick post steps add tEXt dates, so copies are stripped after them.
+    let stripped = match opts.strip_metadata {
+        Some(_) => Cow::Owned(metadata::strip(&processed)?),
+        None => Cow::Borrowed(processed.as_ref()),
+    };
+    let shared = stripped.as_ref();
+    let png = match opts.strip_metadata {
+        Some(Strip::All) => shared,
+        _ => processed.as_ref(),
+    };
+    let timestamped = match &opts.sign {
+        Some(_) if encoded.is_none() => Some(sign::timestamp(png)?),
+        _ => None,
+    };
+    let png = timestamped.as_deref().unwrap_or(png);
+
+    let encode = || match encoded {
+        Some(data) => Ok(Cow::Borrowed(data)),
+        None => opts.budgets.measure(Stage::Encode, || {
+            encode::encode(png, opts.format, opts.lossy, opts.quality)
+        }),
+    };
+
+    if opts.raw {
+        std::io::stdout().write_all(&encode()?)?;
+        return Ok(delivery);
+    }
+
+    if !opts.clipboard_only {
+        let encoded = encode()?;
+        delivery.saved = Some(encoded.len());
+        opts.hooks
+            .set("HYPRSHOT_FILE", save_fullpath.to_string_lossy());
+        opts.hooks.run(Hook::PreSave)?;
+        delivery.path = opts.budgets.measure(Stage::Save, || match &opts.remote {
+            Some(remote) => {
+                let filename = remote
+                    .relative(save_fullpath)
+                    .context("Screenshot path is outside the remote output folder")?;
+                remote.upload(filename, &encoded)?;
+                Ok(save_fullpath.to_path_buf())
             }
+            None => {
+                check_space(save_fullpath, encoded.len(), opts)?;
+                paths::ensure_parent(save_fullpath, opts.dir_mode)?;
+                let saved = write_screenshot(save_fullpath, &encoded, opts).context(format!(
+                    "Failed to save screenshot to '{}'",
+                    save_fullpath.display()
+                ))?;
+                if saved != save_fullpath {
+                    log::debug!("Saving in: {}", saved.display());
+                    opts.hooks.set("HYPRSHOT_FILE", saved.to_string_lossy());
+                }
+                if let Some(key) = &opts.sign {
+                    sign::sign(&saved, key)?;
+                }
+                Ok(saved)
+            }
+        })?;
+        // A missing thumbnail is not worth losing the screenshot over.
+        if let Some(size) = opts.thumbnail
+            && opts.remote.is_none()
+            && let Err(e) = thumbnail::write(png, &delivery.path, size, opts.thumbnail_cache)
+        {
+            log::warn!("{:#}", e);
         }
-    } else {
-        let grim_output = Command::new("grim")
-            .arg("-g")
-            .arg(geometry)
-            .arg("-")
-            .output()
-            .context("Failed to run grim")?;
-        if !grim_output.status.success() {
-            return Err(anyhow::anyhow!("grim failed to capture screenshot"));
-        }
+    }
+    let save_fullpath = delivery.path.as_path();
 
-        let mut wl_copy = Command::new("wl-copy")
-            .arg("--type")
-            .arg("image/png")
-            .stdin(Stdio::piped())
-            .spawn()
-            .context("Failed to start wl-copy")?;
-        wl_copy
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(&grim_output.stdout)
-            .context("Failed to write to wl-copy stdin")?;
-        let wl_copy_status = wl_copy.wait().context("Failed to wait for wl-copy")?;
-        if !wl_copy_status.success() {
-            return Err(anyhow::anyhow!("wl-copy failed to copy screenshot"));
-        }
+    if !opts.no_clipboard {
+        opts.budgets.measure(Stage::Clipboard, || {
+            clipboard::copy(shared, "image/png", opts.primary, opts.paste_once)
+        })?;
     }
 
-    if !silent {
-        let message = if clipboard_only {
-            "Image copied to the clipboard".to_string()
-        } else {
-            format!(
-                "Image saved in <i>{}</i> and copied to the clipboard.",
-                save_fullpath.display()
-            )
-        };
-        Notification::new()
-            .summary("Screenshot saved")
-            .body(&message)
-            .icon(save_fullpath.to_str().unwrap_or("screenshot"))
-            .timeout(notif_timeout as i32)
-            .appname("Hyprshot-rs")
-            .show()
-            .context("Failed to show notification")?;
+    if !opts.clipboard_only {
+        run_command(save_fullpath, opts)?;
     }
+    opts.hooks.run(Hook::PostSave)?;
 
-    Ok(())
+    if !opts.silent {
+        notify_saved(save_fullpath, &delivery, opts);
+    }
+
+    Ok(delivery)
+}
+
+// An existing image given to `process`. It goes through the same steps as a
+// capture, except those that need a selection on screen: blurring protected
+// windows, ICC profiles and the window title in --frame.
+pub fn deliver_file(path: &Path, save_fullpath: &Path, opts: &SaveOptions) -> Result<Delivery> {
+    let data = std::fs::read(path).context(format!(
+        "Failed to open screenshot file '{}'",
+        path.display()
+    ))?;
+    let png = match encode::Format::from_path(path) {
+        Some(format) => encode::decode(&data, format)?,
+        _ if data.starts_with(metadata::PNG_SIGNATURE) => Cow::Borrowed(data.as_slice()),
+        None => Cow::Owned(post::magick(&data, &[], &[], "process")?),
+    };
+    let png = match &opts.frame {
+        Some(style) => Cow::Owned(frame::draw(&png, 1.0, "", style)?),
+        None => png,
+    };
+    deliver(&png, save_fullpath, opts)
+}
+
+fn decorate<'a>(png: &'a [u8], geometry: &str, opts: &SaveOptions) -> Result<Cow<'a, [u8]>> {
+    let png = match opts.blur.as_slice() {
+        [] => Cow::Borrowed(png),
+        regions => Cow::Owned(protect::blur(png, geometry, regions)?),
+    };
+    // Cropped after the blur, whose regions are placed against the selection.
+    let png = match opts.autocrop {
+        Some(tolerance) => Cow::Owned(post::autocrop(&png, tolerance)?),
+        None => png,
+    };
+    let png = match &opts.frame {
+        Some(style) => Cow::Owned(frame::apply(&png, geometry, style)?),
+        None => png,
+    };
+    if let Cow::Owned(converted) = icc::apply(&png, geometry, opts)? {
+        return Ok(Cow::Owned(converted));
+    }
+    Ok(png)
+}
+
+#[cfg(feature = "grim")]
+pub fn save_geometry_with_grim(
+    geometry: &str,
+    save_fullpath: &Path,
+    opts: &SaveOptions,
+) -> Result<Delivery> {
+    log::debug!("Saving geometry with grim: {}", geometry);
+
+    let scale = opts.resize.grim_scale(geometry)?;
+
+    let grim_output = opts.budgets.measure(Stage::Capture, || {
+        let mut args = vec!["-g".to_string(), geometry.to_string()];
+        if let Some(scale) = scale {
+            args.extend(["-s".to_string(), scale.to_string()]);
+        }
+        if let Some(compression) = opts.png_compression {
+            args.extend(["-l".to_string(), compression.grim_level().to_string()]);
+        }
+        args.push("-".to_string());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        runner::checked("grim", &args, None).context("grim failed to capture screenshot")
+    })?;
+
+    let png = decorate(&grim_output.stdout, geometry, opts)?;
+    deliver(&png, save_fullpath, opts)
 }
 
 #[cfg(feature = "native")]
 pub fn save_geometry_with_native(
     geometry: &str,
-    save_fullpath: &PathBuf,
-    clipboard_only: bool,
-    raw: bool,
-    command: Option<Vec<String>>,
-    silent: bool,
-    notif_timeout: u32,
-    debug: bool,
-) -> Result<()> {
-    use image::{DynamicImage, ImageBuffer, Rgba};
+    save_fullpath: &Path,
+    opts: &SaveOptions,
+) -> Result<Delivery> {
+    use image::{DynamicImage, ImageBuffer, Rgb};
     use wayland_client::{
         Connection, Dispatch, QueueHandle,
         protocol::{wl_compositor::WlCompositor, wl_output::WlOutput, wl_shm::WlShm},
@@ -143,20 +513,17 @@ pub fn save_geometry_with_native(
         zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
     };
 
-    if debug {
-        eprintln!("Saving geometry with native Wayland: {}", geometry);
-    }
+    log::debug!("Saving geometry with native Wayland: {}", geometry);
 
-    let parts: Vec<&str> = geometry.split(' ').collect();
-    if parts.len() != 2 {
-        return Err(anyhow::anyhow!("Invalid geometry format: '{}'", geometry));
-    }
-    let xy: Vec<&str> = parts[0].split(',').collect();
-    let wh: Vec<&str> = parts[1].split('x').collect();
-    let x: i32 = xy[0].parse().context("Invalid x coordinate")?;
-    let y: i32 = xy[1].parse().context("Invalid y coordinate")?;
-    let width: i32 = wh[0].parse().context("Invalid width")?;
-    let height: i32 = wh[1].parse().context("Invalid height")?;
+    let region = Region::parse(geometry)?;
+    let Region {
+        x,
+        y,
+        width,
+        height,
+    } = region;
+
+    let transform = utils::output_transform(&region)?;
 
     let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
     let mut event_queue = conn.new_event_queue();
@@ -234,7 +601,8 @@ pub fn save_geometry_with_native(
         buffer: Option<Vec<u8>>,
         width: u32,
         height: u32,
-        format: Option<wayland_client::protocol::wl_shm::Format>,
+        stride: u32,
+        format: Option<u32>,
     }
 
     impl Dispatch<ZwlrScreencopyFrameV1, ()> for FrameState {
@@ -255,7 +623,8 @@ pub fn save_geometry_with_native(
                 } => {
                     self.width = width;
                     self.height = height;
-                    self.format = Some(format);
+                    self.stride = stride;
+                    self.format = Some(u32::from(format));
                     self.buffer = Some(vec![0u8; (stride * height) as usize]);
                 }
                 zwlr_screencopy_frame_v1::Event::Ready { .. } => {
@@ -270,6 +639,7 @@ pub fn save_geometry_with_native(
         buffer: None,
         width: 0,
         height: 0,
+        stride: 0,
         format: None,
     };
 
@@ -280,133 +650,283 @@ pub fn save_geometry_with_native(
     let buffer = frame_state
         .buffer
         .context("Failed to receive frame buffer")?;
-    let width = frame_state.width;
-    let height = frame_state.height;
-
-    let img: ImageBuffer<Rgba<u8>, _> = ImageBuffer::from_raw(width, height, buffer)
-        .context("Failed to create image from buffer")?;
-    let dynamic_img = DynamicImage::ImageRgba8(img);
-
-    if raw {
-        let mut stdout = std::io::stdout();
-        dynamic_img
-            .write_to(&mut stdout, image::ImageOutputFormat::Png)
-            .context("Failed to write raw image to stdout")?;
-        return Ok(());
-    }
+    let format = frame_state
+        .format
+        .context("Compositor did not report a buffer format")?;
+    let frame = hdr::decode(
+        &buffer,
+        frame_state.width,
+        frame_state.height,
+        frame_state.stride,
+        format,
+    )?;
+    log::debug!(
+        "Screencopy buffer format: {}{}, output transform {}",
+        hdr::format_name(format),
+        if frame.is_hdr() { " (HDR)" } else { "" },
+        transform
+    );
 
-    if !clipboard_only {
-        create_dir_all(save_fullpath.parent().unwrap())
-            .context("Failed to create screenshot directory")?;
-        dynamic_img.save(save_fullpath).context(format!(
-            "Failed to save screenshot to '{}'",
-            save_fullpath.display()
-        ))?;
-
-        let wl_copy_status = Command::new("wl-copy")
-            .arg("--type")
-            .arg("image/png")
-            .stdin(std::fs::File::open(save_fullpath).context(format!(
-                "Failed to open screenshot file '{}'",
-                save_fullpath.display()
-            ))?)
-            .status()
-            .context("Failed to run wl-copy")?;
-        if !wl_copy_status.success() {
-            return Err(anyhow::anyhow!("wl-copy failed to copy screenshot"));
+    let to_image = |pixels: Vec<u16>| -> Result<DynamicImage> {
+        let img: ImageBuffer<Rgb<u16>, _> =
+            ImageBuffer::from_raw(frame.width, frame.height, pixels)
+                .context("Failed to create image from buffer")?;
+        Ok(DynamicImage::ImageRgb16(img))
+    };
+    let sdr = || -> Result<DynamicImage> {
+        let img: ImageBuffer<Rgb<u8>, _> =
+            ImageBuffer::from_raw(frame.width, frame.height, frame.to_rgb8())
+                .context("Failed to create image from buffer")?;
+        Ok(DynamicImage::ImageRgb8(img))
+    };
+    // Screencopy buffers are in the output's untransformed orientation, while
+    // the region was given in the rotated layout.
+    let orient = |img: DynamicImage| {
+        let img = match transform % 4 {
+            1 => img.rotate270(),
+            2 => img.rotate180(),
+            3 => img.rotate90(),
+            _ => img,
+        };
+        if transform >= 4 { img.fliph() } else { img }
+    };
+    let resize = |img: DynamicImage| {
+        let img = orient(img);
+        let (width, height) = (img.width(), img.height());
+        let target_width = opts.resize.target_width(width);
+        if opts.resize.is_none() || target_width >= width {
+            return img;
         }
+        let target_height =
+            ((height as f64 * target_width as f64 / width as f64).round() as u32).max(1);
+        img.resize_exact(
+            target_width,
+            target_height,
+            image::imageops::FilterType::Lanczos3,
+        )
+    };
+    let to_png = |img: &DynamicImage| -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        let compression = opts
+            .png_compression
+            .unwrap_or(encode::PngCompression::Default)
+            .native();
+        img.write_with_encoder(image::codecs::png::PngEncoder::new_with_quality(
+            &mut buffer,
+            compression,
+            image::codecs::png::FilterType::Adaptive,
+        ))
+        .context("Failed to encode image to PNG")?;
+        Ok(buffer)
+    };
 
-        if let Some(cmd) = command {
-            let cmd_status = Command::new(&cmd[0])
-                .args(&cmd[1..])
-                .arg(save_fullpath)
-                .status()
-                .context(format!("Failed to run command '{}'", cmd[0]))?;
-            if !cmd_status.success() {
-                return Err(anyhow::anyhow!("Command '{}' failed", cmd[0]));
-            }
+    match opts.hdr {
+        None => {
+            let png = to_png(&resize(sdr()?))?;
+            deliver(&decorate(&png, geometry, opts)?, save_fullpath, opts)
         }
-    } else {
-        let mut buffer = Vec::new();
-        dynamic_img
-            .write_to(
-                &mut std::io::Cursor::new(&mut buffer),
-                image::ImageOutputFormat::Png,
-            )
-            .context("Failed to encode image to PNG")?;
-
-        let mut wl_copy = Command::new("wl-copy")
-            .arg("--type")
-            .arg("image/png")
-            .stdin(Stdio::piped())
-            .spawn()
-            .context("Failed to start wl-copy")?;
-        wl_copy
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(&buffer)
-            .context("Failed to write to wl-copy stdin")?;
-        let wl_copy_status = wl_copy.wait().context("Failed to wait for wl-copy")?;
-        if !wl_copy_status.success() {
-            return Err(anyhow::anyhow!("wl-copy failed to copy screenshot"));
+        Some(encode::HdrOutput::Png16) => {
+            let png = to_png(&resize(to_image(frame.to_rgb16())?))?;
+            deliver(&decorate(&png, geometry, opts)?, save_fullpath, opts)
+        }
+        Some(encode::HdrOutput::Avif) => {
+            let pq = to_png(&resize(to_image(frame.to_pq16())?))?;
+            let avif = opts
+                .budgets
+                .measure(Stage::Encode, || hdr::encode_avif(&pq))?;
+            deliver_as(&to_png(&resize(sdr()?))?, Some(&avif), save_fullpath, opts)
         }
     }
+}
+
+pub fn backend() -> &'static str {
+    #[cfg(feature = "grim")]
+    return "grim";
+    #[cfg(all(feature = "native", not(feature = "grim")))]
+    return "native";
+}
 
-    if !silent {
-        let message = if clipboard_only {
-            "Image copied to the clipboard".to_string()
+// The command as run_command would run it, for --dry-run.
+fn command_line(command: &[String], values: [String; 4]) -> String {
+    std::iter::once(command[0].clone())
+        .chain(command_args(&command[1..], values))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn dry_run(backend: &str, target: &str, save_fullpath: &Path, opts: &SaveOptions) {
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    println!("Backend: {}", backend);
+    println!("Target: {}", target);
+    if opts.raw {
+        println!("Output: stdout");
+    } else if opts.clipboard_only {
+        println!("Output: clipboard only");
+    } else {
+        println!("Output: {}", save_fullpath.display());
+    }
+    println!("Clipboard: {}", yes_no(!opts.raw && !opts.no_clipboard));
+    let steps: Vec<&str> = opts
+        .autocrop
+        .map(|_| "autocrop")
+        .into_iter()
+        .chain(opts.post.iter().map(|step| step.name()))
+        .collect();
+    println!(
+        "Post steps: {}",
+        if steps.is_empty() {
+            "none".to_string()
         } else {
-            format!(
-                "Image saved in <i>{}</i> and copied to the clipboard.",
-                save_fullpath.display()
-            )
-        };
-        Notification::new()
-            .summary("Screenshot saved")
-            .body(&message)
-            .icon(save_fullpath.to_str().unwrap_or("screenshot"))
-            .timeout(notif_timeout as i32)
-            .appname("Hyprshot-rs")
-            .show()
-            .context("Failed to show notification")?;
+            steps.join(", ")
+        }
+    );
+    println!("Frame: {}", yes_no(opts.frame.is_some()));
+    if let Some(command) = &opts.command
+        && !opts.clipboard_only
+        && !opts.raw
+    {
+        println!(
+            "Command: {}",
+            command_line(command, command_values(save_fullpath, opts))
+        );
     }
-
-    Ok(())
+    println!("Notification: {}", yes_no(!opts.silent));
 }
 
-pub fn save_geometry(
-    geometry: &str,
-    save_fullpath: &PathBuf,
-    clipboard_only: bool,
-    raw: bool,
-    command: Option<Vec<String>>,
-    silent: bool,
-    notif_timeout: u32,
-    debug: bool,
-) -> Result<()> {
+pub fn save_geometry(geometry: &str, save_fullpath: &Path, opts: &SaveOptions) -> Result<Delivery> {
     #[cfg(feature = "grim")]
-    return save_geometry_with_grim(
-        geometry,
-        save_fullpath,
-        clipboard_only,
-        raw,
-        command,
-        silent,
-        notif_timeout,
-        debug,
-    );
+    return save_geometry_with_grim(geometry, save_fullpath, opts);
     #[cfg(feature = "native")]
-    return save_geometry_with_native(
-        geometry,
-        save_fullpath,
-        clipboard_only,
-        raw,
-        command,
-        silent,
-        notif_timeout,
-        debug,
-    );
+    return save_geometry_with_native(geometry, save_fullpath, opts);
     #[cfg(not(any(feature = "grim", feature = "native")))]
     compile_error!("At least one of 'grim' or 'native' features must be enabled");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault;
+    use crate::runner::fake::Fake;
+
+    fn options() -> SaveOptions {
+        SaveOptions {
+            clipboard_only: false,
+            no_clipboard: true,
+            raw: false,
+            command: None,
+            detach: false,
+            silent: true,
+            notif_timeout: 0,
+            notify_sound: None,
+            primary: None,
+            paste_once: false,
+            strip_metadata: None,
+            sign: None,
+            thumbnail: None,
+            thumbnail_cache: false,
+            min_free_space: 0,
+            low_space: LowSpace::Warn,
+            flash: false,
+            format: encode::Format::Png,
+            lossy: false,
+            quality: None,
+            png_compression: None,
+            hdr: None,
+            icc_profiles: Vec::new(),
+            srgb_convert: false,
+            frame: None,
+            blur: Vec::new(),
+            selection: None,
+            autocrop: None,
+            post: Vec::new(),
+            hooks: hooks::Hooks::default(),
+            remote: None,
+            resize: Resize::default(),
+            budgets: Budgets::new(Vec::new()),
+            lock: RefCell::new(None),
+            file_mode: None,
+            dir_mode: None,
+            on_conflict: paths::OnConflict::Rename,
+            debug: false,
+        }
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hyprshot-rs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    }
+
+    #[cfg(feature = "grim")]
+    #[test]
+    fn capture_failure_runs_no_grim_and_saves_nothing() {
+        let dir = scratch_dir("fault-capture");
+        let fake = Fake::default().install();
+        fault::inject(vec![fault::Point::Capture]);
+        let err =
+            save_geometry_with_grim("0,0 10x10", &dir.join("shot.png"), &options()).unwrap_err();
+        fault::inject(Vec::new());
+        assert_eq!(err.to_string(), "Injected failure at Capture stage");
+        assert!(fake.calls.borrow().is_empty());
+        assert_eq!(entries(&dir), Vec::<PathBuf>::new());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn save_failure_leaves_no_partial_file() {
+        let dir = scratch_dir("fault-save");
+        fault::inject(vec![fault::Point::Save]);
+        let err = deliver(metadata::PNG_SIGNATURE, &dir.join("shot.png"), &options()).unwrap_err();
+        fault::inject(Vec::new());
+        assert_eq!(err.to_string(), "Injected failure at Save stage");
+        assert_eq!(entries(&dir), Vec::<PathBuf>::new());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn upload_failure_sends_nothing() {
+        let fake = Fake::default().install();
+        let mut opts = options();
+        opts.remote = remote::Destination::parse(Path::new("sftp://example.com/shots")).unwrap();
+        let path = PathBuf::from(opts.remote.as_ref().unwrap().url("shot.png"));
+        fault::inject(vec![fault::Point::Upload]);
+        let err = deliver(metadata::PNG_SIGNATURE, &path, &opts).unwrap_err();
+        fault::inject(Vec::new());
+        assert_eq!(err.to_string(), "Injected failure at Upload stage");
+        assert!(fake.calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn command_placeholders_replace_the_appended_path() {
+        let values = || ["/shots/a.png", "/shots", "10,20 30x40", "region"].map(str::to_string);
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            command_args(&args(&["-f", "{file}", "-o", "{file}"]), values()),
+            args(&["-f", "/shots/a.png", "-o", "/shots/a.png"])
+        );
+        assert_eq!(
+            command_args(&args(&["--title={mode} {geometry}", "{dir}"]), values()),
+            args(&["--title=region 10,20 30x40", "/shots"])
+        );
+        assert_eq!(
+            command_args(&args(&["--fullscreen"]), values()),
+            args(&["--fullscreen", "/shots/a.png"])
+        );
+        assert_eq!(
+            command_line(
+                &args(&["satty", "-f", "{file}", "--title={mode}"]),
+                values()
+            ),
+            "satty -f /shots/a.png --title=region"
+        );
+        assert_eq!(command_line(&args(&["imv"]), values()), "imv /shots/a.png");
+    }
+}
@@ -0,0 +1,164 @@
+use crate::desktop::{self, Target};
+use crate::save::{self, SaveOptions};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use zbus::zvariant::{ObjectPath, OwnedFd, OwnedValue, Value};
+
+const SCREENCAST: &str = "org.freedesktop.portal.ScreenCast";
+
+const SOURCE_MONITOR: u32 = 1;
+const SOURCE_WINDOW: u32 = 2;
+const CURSOR_HIDDEN: u32 = 1;
+
+fn token(step: &str) -> String {
+    format!("hyprshot_rs_{}_{}", std::process::id(), step)
+}
+
+fn source_type(target: &Target) -> Result<u32> {
+    match target {
+        Target::Output { .. } => Ok(SOURCE_MONITOR),
+        Target::Window { .. } => Ok(SOURCE_WINDOW),
+        Target::Region | Target::NamedOutput(_) => Err(anyhow::anyhow!(
+            "The screencast backend can only capture outputs and windows picked in the portal dialog"
+        )),
+    }
+}
+
+fn start_stream(conn: &zbus::blocking::Connection, source: u32) -> Result<(String, u32)> {
+    let create_token = token("create");
+    let options = HashMap::from([
+        ("handle_token", Value::from(create_token.as_str())),
+        ("session_handle_token", Value::from(token("session"))),
+    ]);
+    let results = desktop::portal_request(
+        conn,
+        SCREENCAST,
+        "CreateSession",
+        &create_token,
+        &(options,),
+    )?;
+    let session: String = results
+        .get("session_handle")
+        .cloned()
+        .context("ScreenCast portal returned no session")?
+        .try_into()
+        .context("ScreenCast session handle is not a string")?;
+    let session_path = ObjectPath::try_from(session.as_str())
+        .context(format!("Invalid ScreenCast session handle '{}'", session))?;
+    log::debug!("ScreenCast session: {}", session);
+
+    let select_token = token("select");
+    let options = HashMap::from([
+        ("handle_token", Value::from(select_token.as_str())),
+        ("types", Value::from(source)),
+        ("multiple", Value::from(false)),
+        ("cursor_mode", Value::from(CURSOR_HIDDEN)),
+    ]);
+    desktop::portal_request(
+        conn,
+        SCREENCAST,
+        "SelectSources",
+        &select_token,
+        &(&session_path, options),
+    )?;
+
+    let start_token = token("start");
+    let options = HashMap::from([("handle_token", Value::from(start_token.as_str()))]);
+    let results = desktop::portal_request(
+        conn,
+        SCREENCAST,
+        "Start",
+        &start_token,
+        &(&session_path, "", options),
+    )?;
+    let streams: Vec<(u32, HashMap<String, OwnedValue>)> = results
+        .get("streams")
+        .cloned()
+        .context("ScreenCast portal returned no streams")?
+        .try_into()
+        .context("Invalid ScreenCast streams")?;
+    let (node, _) = streams
+        .into_iter()
+        .next()
+        .context("ScreenCast portal returned no streams")?;
+    log::debug!("ScreenCast PipeWire node: {}", node);
+    Ok((session, node))
+}
+
+fn open_remote(conn: &zbus::blocking::Connection, session: &str) -> Result<OwnedFd> {
+    let portal = zbus::blocking::Proxy::new(
+        conn,
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        SCREENCAST,
+    )
+    .context("Failed to create ScreenCast portal proxy")?;
+    let session_path = ObjectPath::try_from(session)?;
+    let options: HashMap<&str, Value> = HashMap::new();
+    portal
+        .call("OpenPipeWireRemote", &(&session_path, options))
+        .context("Failed to open the PipeWire remote")
+}
+
+fn close_session(conn: &zbus::blocking::Connection, session: &str) {
+    let closed = zbus::blocking::Proxy::new(
+        conn,
+        "org.freedesktop.portal.Desktop",
+        session,
+        "org.freedesktop.portal.Session",
+    )
+    .and_then(|proxy| proxy.call::<_, _, ()>("Close", &()));
+    if let Err(e) = closed {
+        log::debug!("Failed to close ScreenCast session: {}", e);
+    }
+}
+
+fn grab_frame(remote: &OwnedFd, node: u32) -> Result<Vec<u8>> {
+    let fd = remote.as_raw_fd();
+    // gst-launch opens the remote by number, so it has to survive exec.
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, 0) } != 0 {
+        return Err(anyhow::anyhow!(
+            "Failed to pass the PipeWire remote to gst-launch-1.0: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    let pipeline = format!(
+        "pipewiresrc fd={} path={} always-copy=true ! videoconvert ! pngenc snapshot=true ! fdsink fd=1",
+        fd, node
+    );
+    log::debug!("Grabbing frame: gst-launch-1.0 {}", pipeline);
+    let output = Command::new("gst-launch-1.0")
+        .arg("-q")
+        .args(pipeline.split(' '))
+        .stdin(Stdio::null())
+        .output()
+        .context("Failed to run gst-launch-1.0")?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(anyhow::anyhow!(
+            "gst-launch-1.0 failed to grab a frame from PipeWire node {}: {}",
+            node,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+pub fn save_geometry_with_screencast(
+    target: &Target,
+    save_fullpath: &Path,
+    opts: &SaveOptions,
+) -> Result<save::Delivery> {
+    let source = source_type(target)?;
+    let conn =
+        zbus::blocking::Connection::session().context("Failed to connect to session D-Bus")?;
+    log::debug!("Saving with the ScreenCast portal ({:?})", target);
+
+    let (session, node) = start_stream(&conn, source)?;
+    let frame = open_remote(&conn, &session).and_then(|remote| grab_frame(&remote, node));
+    close_session(&conn, &session);
+
+    save::deliver(&frame?, save_fullpath, opts)
+}
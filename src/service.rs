@@ -0,0 +1,125 @@
+use crate::encode::Format;
+use crate::paths;
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+pub const BUS_NAME: &str = "org.hyprshot.Capture";
+const OBJECT_PATH: &str = "/org/hyprshot/Capture";
+
+pub struct Capture {
+    exe: PathBuf,
+    output_folder: Option<PathBuf>,
+    config: Option<PathBuf>,
+    format: Format,
+}
+
+impl Capture {
+    pub fn new(
+        output_folder: Option<&Path>,
+        config: Option<&Path>,
+        format: Format,
+    ) -> Result<Capture> {
+        Ok(Capture {
+            exe: std::env::current_exe().context("Failed to locate the hyprshot-rs executable")?,
+            output_folder: output_folder.map(Path::to_path_buf),
+            config: config.map(Path::to_path_buf),
+            format,
+        })
+    }
+
+    pub fn capture(&self, modes: &[&str]) -> zbus::fdo::Result<PathBuf> {
+        let stamp = Local::now().format("%Y-%m-%d-%H%M%S_hyprshot").to_string();
+        let extension = self.format.extension();
+        let path = paths::resolve_save_path(
+            self.output_folder.as_deref(),
+            None,
+            &format!("{}.{}", stamp, extension),
+        );
+        let path = paths::resolve_conflict(path, paths::OnConflict::Rename)
+            .map_err(|e| zbus::fdo::Error::Failed(format!("{:#}", e)))?;
+
+        let mut command = Command::new(&self.exe);
+        if let Some(config) = &self.config {
+            command.arg("--config").arg(config);
+        }
+        for mode in modes {
+            command.args(["-m", mode]);
+        }
+        command
+            .arg("--silent")
+            .arg("--filename")
+            .arg(&path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null());
+        log::debug!("D-Bus capture: {:?}", command);
+
+        let output = command
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(|e| zbus::fdo::Error::Failed(format!("Failed to run hyprshot-rs: {}", e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.trim();
+            return Err(zbus::fdo::Error::Failed(if message.is_empty() {
+                format!("hyprshot-rs exited with {}", output.status)
+            } else {
+                message.to_string()
+            }));
+        }
+        if !path.exists() {
+            return Err(zbus::fdo::Error::Failed(
+                "Capture finished without saving a file (clipboard-only mode?)".to_string(),
+            ));
+        }
+        Ok(path)
+    }
+
+    fn capture_path(&self, modes: &[&str]) -> zbus::fdo::Result<String> {
+        Ok(self.capture(modes)?.to_string_lossy().into_owned())
+    }
+}
+
+#[zbus::interface(name = "org.hyprshot.Capture")]
+impl Capture {
+    fn capture_region(&self) -> zbus::fdo::Result<String> {
+        self.capture_path(&["region"])
+    }
+
+    fn capture_window(&self, active: bool) -> zbus::fdo::Result<String> {
+        if active {
+            self.capture_path(&["window", "active"])
+        } else {
+            self.capture_path(&["window"])
+        }
+    }
+
+    fn capture_output(&self, name: &str) -> zbus::fdo::Result<String> {
+        match name {
+            "" => self.capture_path(&["output"]),
+            "active" => self.capture_path(&["output", "active"]),
+            name => self.capture_path(&["output", name]),
+        }
+    }
+}
+
+pub fn serve(name: &str, path: &str, object: impl zbus::object_server::Interface) -> Result<()> {
+    let _conn = zbus::blocking::connection::Builder::session()
+        .context("Failed to connect to session D-Bus")?
+        .name(name)
+        .context(format!("Failed to request bus name {}", name))?
+        .serve_at(path, object)
+        .context(format!("Failed to serve {}", path))?
+        .build()
+        .context(format!("Failed to register {} on the session bus", name))?;
+    log::debug!("Serving {} at {}", name, path);
+    loop {
+        std::thread::park();
+    }
+}
+
+pub fn run(output_folder: Option<&Path>, config: Option<&Path>, format: Format) -> Result<()> {
+    let capture = Capture::new(output_folder, config, format)?;
+    serve(BUS_NAME, OBJECT_PATH, capture)
+}
@@ -0,0 +1,82 @@
+use crate::metadata;
+use crate::runner;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+// PNGs carry the capture time under this standard keyword, so the signature
+// covers when the screenshot was taken as well as its pixels.
+pub const TIMESTAMP_KEYWORD: &str = "Creation Time";
+
+pub fn timestamp(png: &[u8]) -> Result<Vec<u8>> {
+    metadata::embed_text(
+        png,
+        TIMESTAMP_KEYWORD,
+        &chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+    )
+}
+
+pub fn signature_path(file: &Path) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+fn openssl(args: &[&str]) -> Result<std::process::Output> {
+    runner::output("openssl", args, None)
+        .context("Failed to run openssl (OpenSSL 3 is needed for --sign and verify)")
+}
+
+// A detached signature of the file as saved. `pkeyutl -rawin` without a
+// digest only signs with Ed25519 and Ed448 keys; RSA and EC keys are refused.
+pub fn sign(file: &Path, key: &Path) -> Result<PathBuf> {
+    let signature = signature_path(file);
+    let (file_arg, key_arg, sig_arg) = (
+        file.to_string_lossy(),
+        key.to_string_lossy(),
+        signature.to_string_lossy(),
+    );
+    let output = openssl(&[
+        "pkeyutl", "-sign", "-rawin", "-inkey", &key_arg, "-in", &file_arg, "-out", &sig_arg,
+    ])?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "openssl failed to sign '{}' with '{}': {}",
+            file.display(),
+            key.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    log::debug!("Signed {} to {}", file.display(), signature.display());
+    Ok(signature)
+}
+
+pub fn verify(file: &Path, signature: Option<&Path>, key: &Path) -> Result<()> {
+    let signature = signature.map_or_else(|| signature_path(file), Path::to_path_buf);
+    let public = std::fs::read_to_string(key)
+        .context(format!("Failed to read key '{}'", key.display()))?
+        .contains("PUBLIC KEY");
+    let (file_arg, key_arg, sig_arg) = (
+        file.to_string_lossy(),
+        key.to_string_lossy(),
+        signature.to_string_lossy(),
+    );
+    let mut args = vec!["pkeyutl", "-verify", "-rawin"];
+    if public {
+        args.push("-pubin");
+    }
+    args.extend(["-inkey", &key_arg, "-sigfile", &sig_arg, "-in", &file_arg]);
+    if !openssl(&args)?.status.success() {
+        return Err(anyhow::anyhow!(
+            "Signature '{}' does not match '{}': the file was changed or signed with another key",
+            signature.display(),
+            file.display()
+        ));
+    }
+
+    let data = std::fs::read(file).context(format!("Failed to read '{}'", file.display()))?;
+    match metadata::text(&data, TIMESTAMP_KEYWORD) {
+        Some(time) => println!("{}: signature OK, captured {}", file.display(), time),
+        None => println!("{}: signature OK", file.display()),
+    }
+    Ok(())
+}
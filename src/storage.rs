@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "hyprshot-rs";
+
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR))
+}
+
+pub fn state_dir() -> Result<PathBuf> {
+    dirs::state_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".local/state")))
+        .map(|dir| dir.join(APP_DIR))
+        .context("Could not determine state directory")
+}
+
+pub fn cache_dir() -> Result<PathBuf> {
+    dirs::cache_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".cache")))
+        .map(|dir| dir.join(APP_DIR))
+        .context("Could not determine cache directory")
+}
+
+// Without XDG_RUNTIME_DIR, a per-user directory in the shared temp directory,
+// so intermediate screenshots and lock files never sit in /tmp itself.
+pub fn runtime_dir() -> PathBuf {
+    dirs::runtime_dir()
+        .map(|dir| dir.join(APP_DIR))
+        .unwrap_or_else(|| {
+            std::env::temp_dir().join(format!("{}-{}", APP_DIR, unsafe { libc::getuid() }))
+        })
+}
+
+// The runtime directory, created if needed. Another user may have made it
+// first in the shared temp directory, so it has to be ours and private.
+pub fn private_runtime_dir() -> Result<PathBuf> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let dir = runtime_dir();
+    ensure_dir(&dir)?;
+    let metadata = std::fs::symlink_metadata(&dir)
+        .context(format!("Failed to inspect '{}'", dir.display()))?;
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::getuid() } {
+        return Err(anyhow::anyhow!(
+            "Refusing to use runtime directory '{}': it is not a directory owned by you",
+            dir.display()
+        ));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
+            .context(format!("Failed to set permissions on '{}'", dir.display()))?;
+    }
+    Ok(dir)
+}
+
+pub fn config_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+pub fn history_file() -> Result<PathBuf> {
+    Ok(state_dir()?.join("history"))
+}
+
+fn ensure_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .context(format!("Failed to create directory '{}'", dir.display()))
+}
+
+pub fn cache_file(name: &str) -> Result<PathBuf> {
+    let dir = cache_dir()?;
+    ensure_dir(&dir)?;
+    Ok(dir.join(name))
+}
+
+pub fn runtime_file(name: &str) -> Result<PathBuf> {
+    Ok(private_runtime_dir()?.join(name))
+}
+
+// Names of short-lived files start with this, so startup cleanup can never
+// mistake anything else for one of them.
+pub const TEMP_PREFIX: &str = "hyprshot-";
+
+// A short-lived intermediate file; `name` starts with its kind and the pid of
+// the process that writes it, e.g. "capture-1234.png".
+pub fn temp_file(name: &str) -> Result<PathBuf> {
+    runtime_file(&format!("{}{}", TEMP_PREFIX, name))
+}
+
+pub const DEFAULT_MIN_FREE_SPACE: u64 = 100 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LowSpace {
+    #[default]
+    Warn,
+    Fail,
+}
+
+// Bytes available to unprivileged users on the filesystem holding `path`, or
+// its nearest existing ancestor since the output folder may not exist yet.
+pub fn free_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = path.ancestors().find(|dir| dir.exists())?;
+    let c_path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        log::debug!(
+            "statvfs failed for {}: {}",
+            dir.display(),
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+    Some(stat.f_bavail * stat.f_frsize)
+}
+
+// "500M", "2G", "64K" (powers of 1024) or a plain number of bytes.
+pub fn parse_size(text: &str) -> Result<u64> {
+    let text = text.trim();
+    let (number, unit) = match text.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&text[..i], c.to_ascii_uppercase()),
+        _ => (text, 'B'),
+    };
+    let shift = match unit {
+        'B' => 0,
+        'K' => 10,
+        'M' => 20,
+        'G' => 30,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Invalid size '{}' (expected e.g. 500M)",
+                text
+            ));
+        }
+    };
+    number
+        .trim()
+        .parse::<u64>()
+        .map(|n| n << shift)
+        .context(format!("Invalid size '{}' (expected e.g. 500M)", text))
+}
+
+pub fn print_paths(config_override: Option<&Path>, screenshots: &Path) -> Result<()> {
+    let show = |path: Option<PathBuf>| {
+        path.map(|p| p.display().to_string())
+            .unwrap_or_else(|| "(unavailable)".to_string())
+    };
+    let config = config_override.map(Path::to_path_buf).or_else(config_file);
+    println!("config:      {}", show(config));
+    println!("state:       {}", show(state_dir().ok()));
+    println!("history:     {}", show(history_file().ok()));
+    println!("cache:       {}", show(cache_dir().ok()));
+    println!("runtime:     {}", runtime_dir().display());
+    println!("screenshots: {}", screenshots.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_parse_with_binary_units() {
+        assert_eq!(parse_size("500M").unwrap(), 500 << 20);
+        assert_eq!(parse_size("2g").unwrap(), 2 << 30);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert!(parse_size("5T").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(free_space(Path::new("/nonexistent/hyprshot-rs")).is_some());
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+
+pub const DEFAULT: &str = "%Y-%m-%d-%H%M%S_hyprshot";
+pub const WINDOW_DEFAULT: &str = "%Y-%m-%d-%H%M%S_{class}_{title}";
+
+const TITLE_LENGTH: usize = 40;
+
+pub struct Values<'a> {
+    pub mode: &'a str,
+    pub class: Option<&'a str>,
+    pub title: Option<&'a str>,
+}
+
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ą' | 'а' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ą' | 'А' => "A",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'ç' | 'ć' | 'č' => "c",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'ď' | 'đ' | 'д' => "d",
+        'Ď' | 'Đ' | 'Д' => "D",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' | 'е' | 'э' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ę' | 'Ě' | 'Е' | 'Э' => "E",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'и' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' | 'И' => "I",
+        'ł' | 'ľ' | 'л' => "l",
+        'Ł' | 'Ľ' | 'Л' => "L",
+        'ñ' | 'ń' | 'ň' | 'н' => "n",
+        'Ñ' | 'Ń' | 'Ň' | 'Н' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' | 'о' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ő' | 'О' => "O",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ř' | 'р' => "r",
+        'Ř' | 'Р' => "R",
+        'ś' | 'š' | 'ş' | 'с' => "s",
+        'Ś' | 'Š' | 'Ş' | 'С' => "S",
+        'ß' => "ss",
+        'ť' | 'ţ' | 'т' => "t",
+        'Ť' | 'Ţ' | 'Т' => "T",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' | 'у' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' | 'Ű' | 'У' => "U",
+        'ý' | 'ÿ' | 'ы' | 'й' => "y",
+        'Ý' | 'Ÿ' | 'Ы' | 'Й' => "Y",
+        'ź' | 'ż' | 'ž' | 'з' => "z",
+        'Ź' | 'Ż' | 'Ž' | 'З' => "Z",
+        'б' => "b",
+        'Б' => "B",
+        'в' => "v",
+        'В' => "V",
+        'г' => "g",
+        'Г' => "G",
+        'ё' => "yo",
+        'Ё' => "Yo",
+        'ж' => "zh",
+        'Ж' => "Zh",
+        'к' => "k",
+        'К' => "K",
+        'м' => "m",
+        'М' => "M",
+        'п' => "p",
+        'П' => "P",
+        'ф' => "f",
+        'Ф' => "F",
+        'х' => "kh",
+        'Х' => "Kh",
+        'ц' => "ts",
+        'Ц' => "Ts",
+        'ч' => "ch",
+        'Ч' => "Ch",
+        'ш' => "sh",
+        'Ш' => "Sh",
+        'щ' => "shch",
+        'Щ' => "Shch",
+        'ю' => "yu",
+        'Ю' => "Yu",
+        'я' => "ya",
+        'Я' => "Ya",
+        'ъ' | 'Ъ' | 'ь' | 'Ь' => "",
+        _ => return None,
+    })
+}
+
+pub fn sanitize(text: &str, max_len: usize) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            out.push(c);
+        } else if let Some(ascii) = transliterate(c) {
+            out.push_str(ascii);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    let out = out.trim_matches(|c| c == '-' || c == '.');
+    out.chars()
+        .take(max_len)
+        .collect::<String>()
+        .trim_end_matches(['-', '.'])
+        .to_string()
+}
+
+pub fn render(template: &str, now: &DateTime<Local>, values: &Values) -> Result<String> {
+    use std::fmt::Write;
+
+    let filled = template
+        .replace("{mode}", values.mode)
+        .replace("{class}", &sanitize(values.class.unwrap_or(""), usize::MAX))
+        .replace(
+            "{title}",
+            &sanitize(values.title.unwrap_or(""), TITLE_LENGTH),
+        );
+    let mut name = String::new();
+    write!(name, "{}", now.format(&filled))
+        .map_err(|_| anyhow::anyhow!("Invalid filename template '{}'", template))?;
+    // Missing values leave doubled or dangling separators behind.
+    while name.contains("__") {
+        name = name.replace("__", "_");
+    }
+    let name = name.trim_matches(['_', '-']);
+    if name.is_empty() {
+        Ok(now.format(DEFAULT).to_string())
+    } else {
+        Ok(name.to_string())
+    }
+}
@@ -0,0 +1,183 @@
+use crate::clipboard;
+use crate::metadata;
+use crate::post;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+const SUFFIX: &str = ".thumb.png";
+
+// Freedesktop thumbnail directories and the largest side each one holds.
+const CACHE_SIZES: [(&str, u32); 4] = [
+    ("normal", 128),
+    ("large", 256),
+    ("x-large", 512),
+    ("xx-large", 1024),
+];
+
+pub fn is_thumbnail(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.ends_with(SUFFIX))
+}
+
+// `shot.png` gets `shot.thumb.png` next to it.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or(path.as_os_str());
+    let mut name = stem.to_os_string();
+    name.push(SUFFIX);
+    path.with_file_name(name)
+}
+
+// Where file managers look for a thumbnail of `path`, per the freedesktop
+// thumbnail spec: the MD5 of the file URI, in a directory chosen by size.
+pub fn cache_path(path: &Path, size: u32) -> Result<PathBuf> {
+    let (dir, _) = CACHE_SIZES
+        .iter()
+        .find(|(_, max)| size <= *max)
+        .unwrap_or(&CACHE_SIZES[CACHE_SIZES.len() - 1]);
+    let hash: String = md5(clipboard::file_uri(path).as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let cache = dirs::cache_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".cache")))
+        .context("Could not determine cache directory")?;
+    Ok(cache
+        .join("thumbnails")
+        .join(dir)
+        .join(format!("{}.png", hash)))
+}
+
+// An existing thumbnail of `path`, next to it or in the cache, smallest first.
+pub fn find(path: &Path) -> Option<PathBuf> {
+    let sidecar = sidecar_path(path);
+    if sidecar.is_file() {
+        return Some(sidecar);
+    }
+    let path = std::fs::canonicalize(path).ok()?;
+    CACHE_SIZES
+        .iter()
+        .filter_map(|(_, size)| cache_path(&path, *size).ok())
+        .find(|cached| cached.is_file())
+}
+
+// Writes a thumbnail of a just-saved screenshot and returns where it went.
+pub fn write(png: &[u8], saved: &Path, size: u32, cache: bool) -> Result<PathBuf> {
+    let size = size.min(CACHE_SIZES[CACHE_SIZES.len() - 1].1);
+    let geometry = format!("{}x{}>", size, size);
+    let mut thumbnail = post::magick(
+        png,
+        &[],
+        &["-thumbnail".to_string(), geometry, "-strip".to_string()],
+        "thumbnails",
+    )?;
+    let path = if cache {
+        let saved = std::fs::canonicalize(saved)
+            .context(format!("Failed to resolve '{}'", saved.display()))?;
+        let mtime = std::fs::metadata(&saved)?
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        thumbnail = metadata::embed_text(&thumbnail, "Thumb::URI", &clipboard::file_uri(&saved))?;
+        thumbnail = metadata::embed_text(&thumbnail, "Thumb::MTime", &mtime.to_string())?;
+        cache_path(&saved, size)?
+    } else {
+        sidecar_path(saved)
+    };
+    write_atomically(&path, &thumbnail, cache)?;
+    log::debug!("Wrote {}px thumbnail to {}", size, path.display());
+    Ok(path)
+}
+
+// Readers may pick up the thumbnail at any moment, so it is renamed into place.
+fn write_atomically(path: &Path, data: &[u8], private: bool) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+
+    if let Some(parent) = path.parent() {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        if private {
+            builder.mode(0o700);
+        }
+        builder
+            .create(parent)
+            .context(format!("Failed to create '{}'", parent.display()))?;
+    }
+    let temp = path.with_extension(format!("png.{}", std::process::id()));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    if private {
+        options.mode(0o600);
+    }
+    std::io::Write::write_all(&mut options.open(&temp)?, data)
+        .and_then(|_| std::fs::rename(&temp, path))
+        .context(format!("Failed to write thumbnail '{}'", path.display()))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp);
+        })
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+        .collect();
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for block in message.chunks(64) {
+        let words: Vec<u32> = block
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[(i / 16) * 4 + i % 4]);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+        for (value, added) in state.iter_mut().zip([a, b, c, d]) {
+            *value = value.wrapping_add(added);
+        }
+    }
+
+    let mut digest = [0; 16];
+    for (bytes, value) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_follow_the_sidecar_and_freedesktop_layouts() {
+        assert_eq!(
+            sidecar_path(Path::new("/shots/a.b.png")),
+            PathBuf::from("/shots/a.b.thumb.png")
+        );
+        assert!(is_thumbnail(&sidecar_path(Path::new("shot.webp"))));
+        assert!(!is_thumbnail(Path::new("shot.png")));
+
+        // The example from the thumbnail spec.
+        let cache = cache_path(Path::new("/home/jens/photos/me.png"), 256).unwrap();
+        assert!(cache.ends_with("thumbnails/large/c6ee772d9e49320e97ec29a7eb5b1697.png"));
+        let hash = md5(b"");
+        assert_eq!(hash[..4], [0xd4, 0x1d, 0x8c, 0xd9]);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::fault;
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Stage {
+    Capture,
+    Encode,
+    Save,
+    Clipboard,
+}
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Capture => "capture",
+            Stage::Encode => "encode",
+            Stage::Save => "save",
+            Stage::Clipboard => "clipboard",
+        }
+    }
+
+    fn default_budget_ms(self) -> u64 {
+        match self {
+            Stage::Capture => 1000,
+            Stage::Encode => 500,
+            Stage::Save => 500,
+            Stage::Clipboard => 500,
+        }
+    }
+
+    fn hint(self) -> &'static str {
+        match self {
+            Stage::Capture => "capture a smaller region or check compositor load",
+            Stage::Encode => "use a faster format (--format png) or --lossy for JPEG XL",
+            Stage::Save => "the output folder may be on a slow or network filesystem",
+            Stage::Clipboard => "a clipboard manager may be slow to take the image",
+        }
+    }
+}
+
+pub fn parse_budget(s: &str) -> Result<(Stage, u64), String> {
+    use clap::ValueEnum;
+
+    let (stage, ms) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected STAGE=MS, got '{}'", s))?;
+    let stage = Stage::from_str(stage, true).map_err(|e| format!("invalid stage: {}", e))?;
+    let ms = ms
+        .parse()
+        .map_err(|_| format!("invalid budget '{}': expected milliseconds", ms))?;
+    Ok((stage, ms))
+}
+
+#[derive(Clone, Debug)]
+pub struct Budgets {
+    overrides: Vec<(Stage, u64)>,
+}
+
+impl Budgets {
+    pub fn new(overrides: Vec<(Stage, u64)>) -> Budgets {
+        Budgets { overrides }
+    }
+
+    fn budget(&self, stage: Stage) -> Option<Duration> {
+        let ms = self
+            .overrides
+            .iter()
+            .rev()
+            .find(|(s, _)| *s == stage)
+            .map(|(_, ms)| *ms)
+            .unwrap_or_else(|| stage.default_budget_ms());
+        (ms > 0).then(|| Duration::from_millis(ms))
+    }
+
+    pub fn measure<T>(&self, stage: Stage, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let start = Instant::now();
+        let result = fault::check(stage.into()).and_then(|()| f());
+        let elapsed = start.elapsed();
+
+        log::debug!("Stage {} took {} ms", stage.name(), elapsed.as_millis());
+        if let Some(budget) = self.budget(stage)
+            && elapsed > budget
+        {
+            log::warn!(
+                "slow stage stage={} elapsed_ms={} budget_ms={} hint=\"{}\"",
+                stage.name(),
+                elapsed.as_millis(),
+                budget.as_millis(),
+                stage.hint()
+            );
+        }
+        result
+    }
+}
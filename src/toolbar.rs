@@ -0,0 +1,122 @@
+use crate::clipboard;
+use crate::i18n;
+use crate::picker::{Item, Picker};
+use crate::remote;
+use crate::save::{self, SaveOptions};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Save,
+    Copy,
+    Edit,
+    Upload,
+    Record,
+    Cancel,
+}
+
+// Labels and freedesktop icon names, in the order the buttons are shown.
+const ACTIONS: [(Action, &str, &str); 6] = [
+    (Action::Save, "Save", "document-save"),
+    (Action::Copy, "Copy", "edit-copy"),
+    (Action::Edit, "Edit", "document-edit"),
+    (Action::Upload, "Upload", "document-send"),
+    (Action::Record, "Record", "media-record"),
+    (Action::Cancel, "Cancel", "process-stop"),
+];
+
+pub const DEFAULT_EDITOR: [&str; 2] = ["satty", "--filename"];
+
+// The menu programs are layer-shell surfaces on Hyprland, so the toolbar floats
+// over the selection and takes pointer input like any other launcher.
+pub fn ask(geometry: &str, picker: &Picker) -> Result<Action> {
+    let items: Vec<Item> = ACTIONS
+        .iter()
+        .map(|(_, label, icon)| Item {
+            label: i18n::tr(label).to_string(),
+            icon: Some(PathBuf::from(icon)),
+        })
+        .collect();
+    let prompt = i18n::trf("Capture {geometry}", &[("geometry", geometry)]);
+    let action = match picker.choose(&prompt, &items)? {
+        Some(index) => ACTIONS[index].0,
+        None => Action::Cancel,
+    };
+    log::debug!("Toolbar action: {:?}", action);
+    Ok(action)
+}
+
+// Adjusts what happens to the capture for Save, Copy, Edit and Upload.
+pub fn apply(action: Action, editor: Option<&[String]>, opts: &mut SaveOptions) {
+    match action {
+        Action::Save | Action::Upload => {
+            opts.clipboard_only = false;
+            opts.no_clipboard = true;
+        }
+        Action::Copy => {
+            opts.clipboard_only = true;
+            opts.no_clipboard = false;
+        }
+        Action::Edit => {
+            opts.clipboard_only = false;
+            opts.command = Some(match editor {
+                Some(editor) => editor.to_vec(),
+                None => DEFAULT_EDITOR.iter().map(|arg| arg.to_string()).collect(),
+            });
+        }
+        Action::Record | Action::Cancel => {}
+    }
+}
+
+pub fn destination(folder: Option<&Path>) -> Result<remote::Destination> {
+    let folder =
+        folder.context("Upload needs 'upload' set to a remote folder in the config file")?;
+    remote::Destination::parse(folder)?.context(format!(
+        "'upload' must be an sftp:// or https:// folder, got '{}'",
+        folder.display()
+    ))
+}
+
+// Uploads a saved screenshot to the remote folder and copies its URL.
+pub fn upload(path: &Path, destination: &remote::Destination, opts: &SaveOptions) -> Result<()> {
+    let filename = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context(format!("Invalid screenshot path '{}'", path.display()))?;
+    let data = std::fs::read(path).context(format!("Failed to read '{}'", path.display()))?;
+    destination.upload(filename, &data)?;
+
+    let url = destination.url(filename);
+    clipboard::copy(url.as_bytes(), "text/plain", opts.primary, opts.paste_once)?;
+    if !opts.silent {
+        save::notify(i18n::tr("Screenshot uploaded"), &url, Some(path), opts);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picker::Kind;
+    use crate::runner::fake::Fake;
+
+    #[test]
+    fn ask_maps_buttons_to_actions() {
+        let command = "fuzzel --dmenu --prompt Capture 10,20 300x200: ";
+        let fake = Fake::default()
+            .reply(command, "Edit\n")
+            .fail(command, 1)
+            .install();
+        let picker = Picker {
+            kind: Some(Kind::Fuzzel),
+            ..Picker::default()
+        };
+        assert_eq!(ask("10,20 300x200", &picker).unwrap(), Action::Edit);
+        assert_eq!(
+            fake.stdin_of(command).unwrap(),
+            "Save\nCopy\nEdit\nUpload\nRecord\nCancel"
+        );
+        assert_eq!(ask("10,20 300x200", &picker).unwrap(), Action::Cancel);
+    }
+}
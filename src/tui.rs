@@ -0,0 +1,533 @@
+use crate::encode::Format;
+use crate::events::EventStream;
+use crate::geometry::Region;
+use crate::runner;
+use crate::utils;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::Value;
+use std::io::{IsTerminal, Read, Write};
+use std::os::fd::RawFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// Something the TUI can capture, with the modes that capture it from the command line.
+struct Target {
+    kind: &'static str,
+    label: String,
+    modes: Vec<String>,
+    // Windows are captured as the active window after focusing them.
+    focus: Option<String>,
+    details: Vec<(&'static str, String)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Clipboard {
+    SaveAndCopy,
+    Only,
+    Off,
+}
+
+impl Clipboard {
+    fn next(self) -> Clipboard {
+        match self {
+            Clipboard::SaveAndCopy => Clipboard::Only,
+            Clipboard::Only => Clipboard::Off,
+            Clipboard::Off => Clipboard::SaveAndCopy,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Clipboard::SaveAndCopy => "save and copy",
+            Clipboard::Only => "clipboard only",
+            Clipboard::Off => "save only",
+        }
+    }
+}
+
+struct Options {
+    clipboard: Clipboard,
+    // None leaves the format to the config file and --filename.
+    format: Option<Format>,
+    delay: u64,
+}
+
+impl Options {
+    fn next_format(&mut self) {
+        let formats = Format::value_variants();
+        self.format = match self.format {
+            None => formats.first().copied(),
+            Some(format) => formats
+                .iter()
+                .position(|f| *f == format)
+                .and_then(|i| formats.get(i + 1))
+                .copied(),
+        };
+    }
+}
+
+fn format_name(format: Option<Format>) -> String {
+    format
+        .and_then(|format| format.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+fn targets() -> Result<Vec<Target>> {
+    let mut targets = vec![Target {
+        kind: "Region",
+        label: "select with slurp".to_string(),
+        modes: vec!["region".to_string()],
+        focus: None,
+        details: vec![(
+            "Selection",
+            "drag a rectangle after pressing Enter".to_string(),
+        )],
+    }];
+
+    let monitors = utils::hyprctl_json("monitors")?;
+    for monitor in monitors.as_array().into_iter().flatten() {
+        let Some(name) = monitor["name"].as_str() else {
+            continue;
+        };
+        let region = Region::of_monitor(monitor);
+        targets.push(Target {
+            kind: "Output",
+            label: format!("{}  {}x{}", name, region.width, region.height),
+            modes: vec!["output".to_string(), name.to_string()],
+            focus: None,
+            details: vec![
+                ("Name", name.to_string()),
+                ("Description", text(&monitor["description"])),
+                (
+                    "Mode",
+                    format!(
+                        "{}x{} @ {:.2} Hz",
+                        monitor["width"].as_i64().unwrap_or(0),
+                        monitor["height"].as_i64().unwrap_or(0),
+                        monitor["refreshRate"].as_f64().unwrap_or(0.0)
+                    ),
+                ),
+                ("Layout", region.to_string()),
+                ("Scale", text(&monitor["scale"])),
+                ("Transform", text(&monitor["transform"])),
+                ("Workspace", text(&monitor["activeWorkspace"]["name"])),
+                ("Focused", text(&monitor["focused"])),
+            ],
+        });
+    }
+
+    let clients = utils::hyprctl_json("clients")?;
+    for client in clients.as_array().into_iter().flatten() {
+        if !client["mapped"].as_bool().unwrap_or(true) || client["hidden"].as_bool() == Some(true) {
+            continue;
+        }
+        let Some(address) = client["address"].as_str() else {
+            continue;
+        };
+        let class = client["class"].as_str().unwrap_or("");
+        let title = client["title"].as_str().unwrap_or("");
+        targets.push(Target {
+            kind: "Window",
+            label: format!("{}  {}", class, title),
+            modes: vec!["window".to_string(), "active".to_string()],
+            focus: Some(address.to_string()),
+            details: vec![
+                ("Class", class.to_string()),
+                ("Title", title.to_string()),
+                ("Workspace", text(&client["workspace"]["name"])),
+                ("Position", pair(&client["at"], ",")),
+                ("Size", pair(&client["size"], "x")),
+                ("Floating", text(&client["floating"])),
+                ("Fullscreen", text(&client["fullscreen"])),
+                ("PID", text(&client["pid"])),
+                ("Address", address.to_string()),
+            ],
+        });
+    }
+    Ok(targets)
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn pair(value: &Value, separator: &str) -> String {
+    match (value[0].as_i64(), value[1].as_i64()) {
+        (Some(a), Some(b)) => format!("{}{}{}", a, separator, b),
+        _ => "-".to_string(),
+    }
+}
+
+fn capture_command(
+    exe: &Path,
+    output_folder: Option<&Path>,
+    config: Option<&Path>,
+    target: &Target,
+    options: &Options,
+) -> Command {
+    let mut command = Command::new(exe);
+    if let Some(config) = config {
+        command.arg("--config").arg(config);
+    }
+    if let Some(folder) = output_folder {
+        command.arg("--output-folder").arg(folder);
+    }
+    for mode in &target.modes {
+        command.args(["-m", mode]);
+    }
+    match options.clipboard {
+        Clipboard::SaveAndCopy => {}
+        Clipboard::Only => {
+            command.arg("--clipboard-only");
+        }
+        Clipboard::Off => {
+            command.arg("--no-clipboard");
+        }
+    }
+    if let Some(format) = options.format {
+        command.args(["--format", &format_name(Some(format))]);
+    }
+    if options.delay > 0 {
+        command.args(["--delay", &options.delay.to_string()]);
+    }
+    command
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Quit,
+    Char(char),
+    Other,
+}
+
+fn parse_key(bytes: &[u8]) -> Key {
+    match bytes {
+        b"\x1b[A" | b"\x1bOA" | b"k" => Key::Up,
+        b"\x1b[B" | b"\x1bOB" | b"j" => Key::Down,
+        b"\r" | b"\n" => Key::Enter,
+        b"\x1b" | b"q" | b"\x03" => Key::Quit,
+        [c] if c.is_ascii_graphic() => Key::Char(*c as char),
+        _ => Key::Other,
+    }
+}
+
+// Puts the terminal in raw mode on the alternate screen until dropped.
+struct Screen(libc::termios);
+
+impl Screen {
+    fn enter() -> Result<Screen> {
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .context("Failed to read terminal settings");
+        }
+        let original = termios;
+        unsafe { libc::cfmakeraw(&mut termios) };
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to enable raw mode");
+        }
+        print!("\x1b[?1049h\x1b[?25l");
+        std::io::stdout().flush()?;
+        Ok(Screen(original))
+    }
+
+    fn rows() -> usize {
+        let mut size = unsafe { std::mem::zeroed::<libc::winsize>() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+            && size.ws_row > 0
+        {
+            size.ws_row as usize
+        } else {
+            24
+        }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0) };
+    }
+}
+
+fn render(targets: &[Target], selected: usize, options: &Options, status: &str) -> String {
+    let selected_target = &targets[selected];
+    let mut lines = vec![
+        "hyprshot-rs  Up/Down select  Enter capture  c clipboard  f format  +/- delay  r refresh  q quit".to_string(),
+        String::new(),
+    ];
+
+    // Keep the selection visible above the details, options and status lines.
+    let reserved = selected_target.details.len() + 7;
+    let visible = Screen::rows().saturating_sub(reserved).max(3);
+    let first = selected.saturating_sub(visible - 1);
+    for (i, target) in targets.iter().enumerate().skip(first).take(visible) {
+        let marker = if i == selected { ">" } else { " " };
+        lines.push(format!("{} {:<7} {}", marker, target.kind, target.label));
+    }
+
+    lines.push(String::new());
+    for (name, value) in &selected_target.details {
+        lines.push(format!("  {:<12} {}", name, value));
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "Clipboard: {}   Format: {}   Delay: {}s",
+        options.clipboard.label(),
+        format_name(options.format),
+        options.delay
+    ));
+    lines.push(status.to_string());
+
+    // Raw mode turns off output processing, so every line needs its own carriage return.
+    format!("\x1b[H\x1b[2J{}", lines.join("\r\n"))
+}
+
+fn capture(
+    exe: &Path,
+    output_folder: Option<&Path>,
+    config: Option<&Path>,
+    target: &Target,
+    options: &Options,
+) -> Result<String> {
+    if let Some(address) = &target.focus {
+        let focus = format!("address:{}", address);
+        runner::checked("hyprctl", &["dispatch", "focuswindow", &focus], None)
+            .context(format!("Failed to focus window {}", address))?;
+    }
+
+    let mut command = capture_command(exe, output_folder, config, target, options);
+    log::debug!("TUI capture: {:?}", command);
+    let output = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .context("Failed to run hyprshot-rs")?;
+    if output.status.success() {
+        return Ok(format!(
+            "Captured {} {}",
+            target.kind.to_lowercase(),
+            target.label
+        ));
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(anyhow::anyhow!(
+        "{}",
+        stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("hyprshot-rs exited with {}", output.status))
+    ))
+}
+
+pub fn run(output_folder: Option<&Path>, config: Option<&Path>) -> Result<()> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "hyprshot-rs tui needs an interactive terminal"
+        ));
+    }
+    let exe: PathBuf =
+        std::env::current_exe().context("Failed to locate the hyprshot-rs executable")?;
+
+    let mut targets = targets()?;
+    let mut options = Options {
+        clipboard: Clipboard::SaveAndCopy,
+        format: None,
+        delay: 0,
+    };
+    let mut selected = 0;
+    let mut status = String::new();
+
+    // Output details go stale when a monitor is plugged in or reconfigured.
+    let mut events = match EventStream::connect() {
+        Ok(events) => Some(events),
+        Err(e) => {
+            log::debug!("Not refreshing on output changes: {:#}", e);
+            None
+        }
+    };
+
+    let mut screen = Screen::enter()?;
+    let mut stdin = std::io::stdin();
+    loop {
+        print!("{}", render(&targets, selected, &options, &status));
+        std::io::stdout().flush()?;
+
+        if let Some(stream) = &mut events
+            && wait_for_input(stream.fd())?
+        {
+            match stream.pending() {
+                Ok(pending) if pending.iter().any(|event| event.changes_outputs()) => {
+                    targets = targets_or_keep(targets, &mut status);
+                    selected = selected.min(targets.len() - 1);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log::debug!("Stopped listening for output changes: {:#}", e);
+                    events = None;
+                }
+            }
+            continue;
+        }
+
+        let mut buf = [0u8; 16];
+        let n = stdin
+            .read(&mut buf)
+            .context("Failed to read from the terminal")?;
+        if n == 0 {
+            return Ok(());
+        }
+        match parse_key(&buf[..n]) {
+            Key::Up => selected = selected.saturating_sub(1),
+            Key::Down => selected = (selected + 1).min(targets.len() - 1),
+            Key::Quit => return Ok(()),
+            Key::Char('c') => options.clipboard = options.clipboard.next(),
+            Key::Char('f') => options.next_format(),
+            Key::Char('+') => options.delay += 1,
+            Key::Char('-') => options.delay = options.delay.saturating_sub(1),
+            Key::Char('r') => {
+                targets = targets_or_keep(targets, &mut status);
+                selected = selected.min(targets.len() - 1);
+            }
+            Key::Enter => {
+                // Give the terminal back while slurp or the delay runs.
+                drop(screen);
+                println!(
+                    "Capturing {} {}...",
+                    targets[selected].kind, targets[selected].label
+                );
+                status = match capture(&exe, output_folder, config, &targets[selected], &options) {
+                    Ok(message) => message,
+                    Err(e) => format!("Capture failed: {:#}", e),
+                };
+                screen = Screen::enter()?;
+                targets = targets_or_keep(targets, &mut status);
+                selected = selected.min(targets.len() - 1);
+            }
+            Key::Char(_) | Key::Other => {}
+        }
+    }
+}
+
+// Waits for a key press or a Hyprland event; true means the event socket is
+// readable and the terminal isn't.
+fn wait_for_input(events: RawFd) -> Result<bool> {
+    let mut fds = [
+        libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: events,
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+    loop {
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } >= 0 {
+            break;
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error).context("Failed to wait for terminal input");
+        }
+    }
+    Ok(fds[0].revents == 0 && fds[1].revents != 0)
+}
+
+fn targets_or_keep(current: Vec<Target>, status: &mut String) -> Vec<Target> {
+    match targets() {
+        Ok(targets) => targets,
+        Err(e) => {
+            *status = format!("Refresh failed: {:#}", e);
+            current
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::Fake;
+
+    const MONITORS: &str = include_str!("../tests/fixtures/hyprctl/monitors.json");
+    const CLIENTS: &str = include_str!("../tests/fixtures/hyprctl/clients.json");
+
+    #[test]
+    fn lists_region_outputs_and_windows() {
+        Fake::default()
+            .reply("hyprctl monitors -j", MONITORS)
+            .reply("hyprctl clients -j", CLIENTS)
+            .install();
+        let targets = targets().unwrap();
+        let labels: Vec<String> = targets
+            .iter()
+            .map(|t| format!("{} {}", t.kind, t.label))
+            .collect();
+        assert_eq!(
+            labels[..4],
+            [
+                "Region select with slurp",
+                "Output DP-1  2048x1152",
+                "Output HDMI-A-1  1920x1080",
+                "Window firefox  Issue 123",
+            ]
+        );
+        assert_eq!(targets[1].modes, ["output", "DP-1"]);
+        assert_eq!(targets[3].focus.as_deref(), Some("0x1111"));
+
+        let options = Options {
+            clipboard: Clipboard::Off,
+            format: Some(Format::Png),
+            delay: 3,
+        };
+        let command = capture_command(
+            Path::new("hyprshot-rs"),
+            Some(Path::new("/tmp/shots")),
+            None,
+            &targets[3],
+            &options,
+        );
+        let args: Vec<_> = command.get_args().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(
+            args,
+            [
+                "--output-folder",
+                "/tmp/shots",
+                "-m",
+                "window",
+                "-m",
+                "active",
+                "--no-clipboard",
+                "--format",
+                "png",
+                "--delay",
+                "3",
+            ]
+        );
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(parse_key(b"\x1b[A"), Key::Up);
+        assert_eq!(parse_key(b"j"), Key::Down);
+        assert_eq!(parse_key(b"\r"), Key::Enter);
+        assert_eq!(parse_key(b"\x1b"), Key::Quit);
+        assert_eq!(parse_key(b"+"), Key::Char('+'));
+        assert_eq!(parse_key(b"\x1b[5~"), Key::Other);
+    }
+}
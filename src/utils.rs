@@ -1,122 +1,257 @@
+use crate::geometry::Region;
+use crate::runner;
 use anyhow::{Context, Result};
 use serde_json::Value;
-use std::process::Command;
+
+pub fn hyprctl_json(command: &str) -> Result<Value> {
+    let output = runner::checked("hyprctl", &[command, "-j"], None)
+        .context(format!("hyprctl {} failed", command))?;
+    // hyprctl reports a missing Hyprland instance on stdout, with status 0.
+    serde_json::from_slice(&output.stdout).context(format!(
+        "Failed to parse hyprctl {} output: {}",
+        command,
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .unwrap_or_default()
+    ))
+}
 
 pub fn is_valid_monitor(name: &str) -> Result<bool> {
-    let output = Command::new("hyprctl")
-        .arg("monitors")
-        .arg("-j")
-        .output()
-        .context("Failed to run hyprctl monitors")?;
-    let monitors: Value = serde_json::from_slice(&output.stdout)?;
+    let monitors = hyprctl_json("monitors")?;
     Ok(monitors
         .as_array()
         .map(|arr| arr.iter().any(|m| m["name"].as_str() == Some(name)))
         .unwrap_or(false))
 }
 
-pub fn trim(geometry: &str, debug: bool) -> Result<String> {
-    if debug {
-        eprintln!("Input geometry: {}", geometry);
-    }
+fn monitors_in(region: &Region) -> Result<Vec<Value>> {
+    let monitors = hyprctl_json("monitors")?;
+    Ok(monitors
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter(|m| Region::of_monitor(m).intersects(region))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default())
+}
 
-    let parts: Vec<&str> = geometry.split(' ').collect();
-    if parts.len() != 2 {
-        return Err(anyhow::anyhow!(
-            "Invalid geometry format: expected 'x,y wxh', got '{}'",
-            geometry
-        ));
-    }
+pub fn intersecting_monitors(region: &Region) -> Result<Vec<String>> {
+    Ok(monitors_in(region)?
+        .iter()
+        .filter_map(|m| m["name"].as_str().map(str::to_string))
+        .collect())
+}
 
-    let xy: Vec<&str> = parts[0].split(',').collect();
-    let wh: Vec<&str> = parts[1].split('x').collect();
-    if xy.len() != 2 || wh.len() != 2 {
-        return Err(anyhow::anyhow!(
-            "Invalid geometry format: expected 'x,y wxh', got '{}'",
-            geometry
-        ));
+pub fn output_scale(geometry: &str) -> Result<f64> {
+    Ok(monitors_in(&Region::parse(geometry)?)?
+        .iter()
+        .filter_map(|m| m["scale"].as_f64())
+        .reduce(f64::max)
+        .unwrap_or(1.0))
+}
+
+pub fn even_dims(geometry: &str) -> Result<String> {
+    let region = Region::parse(geometry)?;
+    let even = region.even(output_scale(geometry)?);
+    if even != region {
+        log::debug!("Evened {} to {}", region, even);
     }
+    Ok(even.to_string())
+}
+
+#[cfg(feature = "native")]
+pub fn output_transform(region: &Region) -> Result<i64> {
+    Ok(monitors_in(region)?
+        .first()
+        .and_then(|m| m["transform"].as_i64())
+        .unwrap_or(0))
+}
 
-    let x: i32 = xy[0]
-        .parse()
-        .context(format!("Failed to parse x coordinate from '{}'", xy[0]))?;
-    let y: i32 = xy[1]
-        .parse()
-        .context(format!("Failed to parse y coordinate from '{}'", xy[1]))?;
-    let width: i32 = wh[0]
-        .parse()
-        .context(format!("Failed to parse width from '{}'", wh[0]))?;
-    let height: i32 = wh[1]
-        .parse()
-        .context(format!("Failed to parse height from '{}'", wh[1]))?;
-
-    if width <= 0 || height <= 0 {
+// Padding grows (or, when negative, shrinks) the window before it is clipped to
+// the monitor the window itself is on.
+pub fn trim(geometry: &str, padding: i32) -> Result<String> {
+    log::debug!("Input geometry: {}", geometry);
+
+    let region = Region::parse(geometry)?;
+
+    if region.is_empty() {
         return Err(anyhow::anyhow!(
             "Invalid geometry dimensions: width={} or height={} is non-positive",
-            width,
-            height
+            region.width,
+            region.height
         ));
     }
 
-    let monitors_output = Command::new("hyprctl")
-        .arg("monitors")
-        .arg("-j")
-        .output()
-        .context("Failed to run hyprctl monitors")?;
-    let monitors: Value = serde_json::from_slice(&monitors_output.stdout)?;
+    let monitors = hyprctl_json("monitors")?;
 
-    let monitor = monitors
+    // Window coordinates are in the logical (scaled) layout. A window hanging off
+    // the left or top edge of the layout has its origin outside every monitor, so
+    // fall back to the monitor it overlaps most.
+    let monitors: Vec<Region> = monitors
         .as_array()
-        .and_then(|arr| {
-            arr.iter().find(|m| {
-                let mon_x = m["x"].as_i64().unwrap_or(0) as i32;
-                let mon_y = m["y"].as_i64().unwrap_or(0) as i32;
-                let mon_width = m["width"].as_i64().unwrap_or(0) as i32;
-                let mon_height = m["height"].as_i64().unwrap_or(0) as i32;
-                x >= mon_x && x < mon_x + mon_width && y >= mon_y && y < mon_y + mon_height
-            })
+        .into_iter()
+        .flatten()
+        .map(Region::of_monitor)
+        .collect();
+    let monitor = monitors
+        .iter()
+        .find(|m| m.contains(region.x, region.y))
+        .or_else(|| {
+            monitors
+                .iter()
+                .filter_map(|m| Some((m, region.intersect(m)?)))
+                .max_by_key(|(_, overlap)| overlap.width as i64 * overlap.height as i64)
+                .map(|(m, _)| m)
         })
         .context("No monitor found for window coordinates")?;
 
-    let mon_x = monitor["x"].as_i64().unwrap_or(0) as i32;
-    let mon_y = monitor["y"].as_i64().unwrap_or(0) as i32;
-    let mon_width = monitor["width"].as_i64().unwrap_or(0) as i32;
-    let mon_height = monitor["height"].as_i64().unwrap_or(0) as i32;
+    let padded = region.grow(padding);
+    if padded.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Shrinking {} by {} pixels leaves nothing to capture",
+            region,
+            -padding
+        ));
+    }
+    let cropped = padded.clamp_to(monitor).context(format!(
+        "Invalid cropped dimensions for {} on monitor {}",
+        padded, monitor
+    ))?;
+    log::debug!("Cropped geometry: {}", cropped);
+    Ok(cropped.to_string())
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        Some(('?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+pub fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    pattern
+        .split('|')
+        .map(str::trim)
+        .filter(|alternative| !alternative.is_empty())
+        .any(|alternative| {
+            let alternative: Vec<char> = alternative.to_lowercase().chars().collect();
+            glob_match(&alternative, &text)
+        })
+}
 
-    let mut cropped_x = x;
-    let mut cropped_y = y;
-    let mut cropped_width = width;
-    let mut cropped_height = height;
+#[derive(Debug, Default)]
+pub struct WindowFilter {
+    pub include_class: Option<String>,
+    pub exclude_class: Option<String>,
+}
 
-    if x + width > mon_x + mon_width {
-        cropped_width = mon_x + mon_width - x;
+impl WindowFilter {
+    pub fn allows(&self, class: &str) -> bool {
+        if let Some(include) = &self.include_class
+            && !matches_pattern(include, class)
+        {
+            return false;
+        }
+        !self
+            .exclude_class
+            .as_ref()
+            .is_some_and(|exclude| matches_pattern(exclude, class))
     }
-    if y + height > mon_y + mon_height {
-        cropped_height = mon_y + mon_height - y;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::Fake;
+
+    const MONITORS: &str = include_str!("../tests/fixtures/hyprctl/monitors.json");
+    // DP-2 sits left of and above eDP-1, at -1920,-200.
+    const MONITORS_LEFT: &str = include_str!("../tests/fixtures/hyprctl/monitors_left.json");
+
+    fn trim_on(monitors: &str, geometry: &str) -> Result<String> {
+        Fake::default()
+            .reply("hyprctl monitors -j", monitors)
+            .install();
+        trim(geometry, 0)
     }
-    if x < mon_x {
-        cropped_x = mon_x;
-        cropped_width -= mon_x - x;
+
+    fn trim_on_fixture(geometry: &str) -> Result<String> {
+        trim_on(MONITORS, geometry)
     }
-    if y < mon_y {
-        cropped_y = mon_y;
-        cropped_height -= mon_y - y;
+
+    #[test]
+    fn trim_keeps_window_inside_monitor() {
+        assert_eq!(
+            trim_on_fixture("100,100 800x600").unwrap(),
+            "100,100 800x600"
+        );
     }
 
-    if cropped_width <= 0 || cropped_height <= 0 {
-        return Err(anyhow::anyhow!(
-            "Invalid cropped dimensions: width={} or height={}",
-            cropped_width,
-            cropped_height
-        ));
+    #[test]
+    fn trim_clips_to_scaled_monitor() {
+        // DP-1 is 2560x1440 at scale 1.25, i.e. 2048x1152 in layout coordinates.
+        assert_eq!(
+            trim_on_fixture("1900,900 400x300").unwrap(),
+            "1900,900 148x252"
+        );
+    }
+
+    #[test]
+    fn trim_uses_monitor_under_window_origin() {
+        assert_eq!(
+            trim_on_fixture("2148,50 600x400").unwrap(),
+            "2148,50 600x400"
+        );
+        assert_eq!(
+            trim_on_fixture("3800,1000 400x300").unwrap(),
+            "3800,1000 168x80"
+        );
+    }
+
+    #[test]
+    fn trim_rejects_empty_and_offscreen_geometry() {
+        assert!(trim_on_fixture("10,10 0x100").is_err());
+        assert!(trim_on_fixture("5000,5000 10x10").is_err());
+    }
+
+    #[test]
+    fn trim_handles_negative_origins() {
+        assert_eq!(
+            trim_on(MONITORS_LEFT, "-1800,-100 800x600").unwrap(),
+            "-1800,-100 800x600"
+        );
+        assert_eq!(
+            trim_on(MONITORS_LEFT, "-400,300 800x700").unwrap(),
+            "-400,300 400x580"
+        );
+        assert_eq!(
+            trim_on(MONITORS_LEFT, "-1950,-250 800x600").unwrap(),
+            "-1920,-200 770x550"
+        );
+        assert!(trim_on(MONITORS_LEFT, "-3000,100 500x500").is_err());
     }
 
-    let cropped = format!(
-        "{0},{1} {2}x{3}",
-        cropped_x, cropped_y, cropped_width, cropped_height
-    );
-    if debug {
-        eprintln!("Cropped geometry: {}", cropped);
+    #[test]
+    fn trim_pads_within_monitor() {
+        let trim_padded = |geometry, padding| {
+            Fake::default()
+                .reply("hyprctl monitors -j", MONITORS_LEFT)
+                .install();
+            trim(geometry, padding)
+        };
+        assert_eq!(trim_padded("100,100 800x600", 10).unwrap(), "90,90 820x620");
+        // Growing past the left edge must not spill onto DP-2.
+        assert_eq!(trim_padded("0,0 800x600", 10).unwrap(), "0,0 810x610");
+        assert_eq!(
+            trim_padded("100,100 800x600", -2).unwrap(),
+            "102,102 796x596"
+        );
+        assert!(trim_padded("100,100 10x10", -5).is_err());
     }
-    Ok(cropped)
 }
@@ -0,0 +1,263 @@
+// Captures a known test pattern from a headless sway and compares it pixel by pixel.
+//
+// Needs sway, swaybg, grim and ImageMagick, so it is ignored by default:
+//
+//     cargo test --test headless -- --ignored
+//
+// The native backend doesn't compile at the moment; once it does, the same
+// test runs against it with --features native --no-default-features.
+//
+// Only the selection and the monitor layout are stubbed out: `slurp` always
+// returns the whole output, and `hyprctl monitors` describes HEADLESS-1 for the
+// native backend.
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 480;
+const BLOCK: usize = 8;
+
+fn pattern() -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(WIDTH * HEIGHT * 3);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let (bx, by) = (x / BLOCK, y / BLOCK);
+            rgb.extend_from_slice(&[
+                (bx * 3 % 256) as u8,
+                (by * 5 % 256) as u8,
+                if (bx + by) % 2 == 0 { 0x20 } else { 0xe0 },
+            ]);
+        }
+    }
+    rgb
+}
+
+fn ppm(rgb: &[u8]) -> Vec<u8> {
+    let mut data = format!("P6\n{} {}\n255\n", WIDTH, HEIGHT).into_bytes();
+    data.extend_from_slice(rgb);
+    data
+}
+
+fn missing_tools() -> Vec<&'static str> {
+    ["sway", "swaybg", "grim", "magick"]
+        .into_iter()
+        .filter(|tool| {
+            Command::new("sh")
+                .args(["-c", &format!("command -v {}", tool)])
+                .stdout(Stdio::null())
+                .status()
+                .map_or(true, |status| !status.success())
+        })
+        .collect()
+}
+
+fn run(command: &mut Command) {
+    let output = command.output().expect("failed to run command");
+    assert!(
+        output.status.success(),
+        "{:?} failed: {}",
+        command,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+struct Sway {
+    child: Child,
+    runtime_dir: PathBuf,
+    display: String,
+}
+
+impl Sway {
+    fn start(dir: &Path, background: &Path) -> Sway {
+        let runtime_dir = dir.join("runtime");
+        std::fs::create_dir_all(&runtime_dir).unwrap();
+        run(Command::new("chmod").arg("700").arg(&runtime_dir));
+        let config = dir.join("sway.conf");
+        std::fs::write(
+            &config,
+            format!(
+                "output HEADLESS-1 resolution {}x{} position 0 0 bg {} center #000000\n\
+                 default_border none\n\
+                 seat * hide_cursor 1\n",
+                WIDTH,
+                HEIGHT,
+                background.display()
+            ),
+        )
+        .unwrap();
+        let child = Command::new("sway")
+            .arg("--config")
+            .arg(&config)
+            .env("XDG_RUNTIME_DIR", &runtime_dir)
+            .env("WLR_BACKENDS", "headless")
+            .env("WLR_RENDERER", "pixman")
+            .env("WLR_LIBINPUT_NO_DEVICES", "1")
+            .env_remove("WAYLAND_DISPLAY")
+            .env_remove("DISPLAY")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to start sway");
+
+        let start = Instant::now();
+        let display = loop {
+            let socket = std::fs::read_dir(&runtime_dir)
+                .unwrap()
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .find(|name| name.starts_with("wayland-") && !name.ends_with(".lock"));
+            if let Some(socket) = socket {
+                break socket;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "sway did not create a Wayland socket"
+            );
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        Sway {
+            child,
+            runtime_dir,
+            display,
+        }
+    }
+
+    fn command(&self, program: impl AsRef<std::ffi::OsStr>, stubs: &Path) -> Command {
+        let mut command = Command::new(program);
+        command
+            .env("XDG_RUNTIME_DIR", &self.runtime_dir)
+            .env("WAYLAND_DISPLAY", &self.display)
+            .env("XDG_CURRENT_DESKTOP", "sway")
+            .env("XDG_STATE_HOME", self.runtime_dir.join("state"))
+            .env("XDG_CONFIG_HOME", self.runtime_dir.join("config"))
+            .env(
+                "PATH",
+                format!(
+                    "{}:{}",
+                    stubs.display(),
+                    std::env::var("PATH").unwrap_or_default()
+                ),
+            )
+            .env_remove("HYPRLAND_INSTANCE_SIGNATURE")
+            .env_remove("SWAYSOCK")
+            .env_remove("DISPLAY");
+        command
+    }
+}
+
+impl Drop for Sway {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn decode(path: &Path) -> Vec<u8> {
+    let output = Command::new("magick")
+        .arg(path)
+        .args(["-depth", "8", "rgb:-"])
+        .output()
+        .expect("failed to run magick");
+    assert!(
+        output.status.success(),
+        "magick could not decode the capture"
+    );
+    output.stdout
+}
+
+fn first_difference(expected: &[u8], actual: &[u8]) -> Option<(usize, usize)> {
+    expected
+        .chunks(3)
+        .zip(actual.chunks(3))
+        .position(|(e, a)| e != a)
+        .map(|i| (i % WIDTH, i / WIDTH))
+}
+
+#[test]
+#[ignore = "needs sway, swaybg, grim and ImageMagick"]
+fn region_capture_matches_pattern() {
+    let missing = missing_tools();
+    if !missing.is_empty() {
+        eprintln!(
+            "skipping headless capture test, missing: {}",
+            missing.join(", ")
+        );
+        return;
+    }
+
+    let dir = std::env::temp_dir().join(format!("hyprshot-headless-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let stubs = dir.join("bin");
+    std::fs::create_dir_all(&stubs).unwrap();
+    std::fs::write(
+        stubs.join("slurp"),
+        format!("#!/bin/sh\necho '0,0 {}x{}'\n", WIDTH, HEIGHT),
+    )
+    .unwrap();
+    std::fs::write(
+        stubs.join("hyprctl"),
+        format!(
+            "#!/bin/sh\n\
+             if [ \"$1\" = monitors ]; then\n\
+             echo '[{{\"id\":0,\"name\":\"HEADLESS-1\",\"x\":0,\"y\":0,\"width\":{},\"height\":{},\"scale\":1.0,\"activeWorkspace\":{{\"id\":1}}}}]'\n\
+             else\n\
+             echo '{{}}'\n\
+             fi\n",
+            WIDTH, HEIGHT
+        ),
+    )
+    .unwrap();
+    run(Command::new("chmod")
+        .arg("+x")
+        .arg(stubs.join("slurp"))
+        .arg(stubs.join("hyprctl")));
+
+    let expected = pattern();
+    let pattern_ppm = dir.join("pattern.ppm");
+    let pattern_png = dir.join("pattern.png");
+    std::fs::write(&pattern_ppm, ppm(&expected)).unwrap();
+    run(Command::new("magick").arg(&pattern_ppm).arg(&pattern_png));
+
+    let sway = Sway::start(&dir, &pattern_png);
+    let shot = dir.join("shot.png");
+
+    // swaybg draws the background shortly after the output appears.
+    let start = Instant::now();
+    let actual = loop {
+        let _ = std::fs::remove_file(&shot);
+        run(sway
+            .command(env!("CARGO_BIN_EXE_hyprshot-rs"), &stubs)
+            .args(["-m", "region", "-s", "--no-clipboard", "--filename"])
+            .arg(&shot));
+        let actual = decode(&shot);
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "capture is not {}x{}",
+            WIDTH,
+            HEIGHT
+        );
+        if first_difference(&expected, &actual).is_none()
+            || start.elapsed() > Duration::from_secs(5)
+        {
+            break actual;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    };
+
+    drop(sway);
+    if let Some((x, y)) = first_difference(&expected, &actual) {
+        let i = (y * WIDTH + x) * 3;
+        panic!(
+            "capture differs from the pattern at {},{}: expected {:?}, got {:?} (files kept in {})",
+            x,
+            y,
+            &expected[i..i + 3],
+            &actual[i..i + 3],
+            dir.display()
+        );
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}